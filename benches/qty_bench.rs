@@ -0,0 +1,31 @@
+//! Benchmark of `Qty::from_str`, called once per resource per container per pod during the collection.
+//!
+//! run with `cargo bench --bench qty_bench`
+
+use std::hint::black_box;
+use std::str::FromStr;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use kubectl_view_allocations::qty::Qty;
+
+/// The number of parsing by measured iteration (eg: the resources of a large cluster)
+const ITERATIONS: u64 = 10_000;
+const INPUTS: [&str; 6] = ["1", "500m", "1Gi", "2Ti", "0.5", "999999999n"];
+
+fn bench_from_str(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Qty::from_str");
+    group.throughput(Throughput::Elements(ITERATIONS));
+    for input in INPUTS {
+        group.bench_with_input(BenchmarkId::from_parameter(input), input, |b, input| {
+            b.iter(|| {
+                for _ in 0..ITERATIONS {
+                    black_box(Qty::from_str(black_box(input)).unwrap());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_from_str);
+criterion_main!(benches);
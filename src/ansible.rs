@@ -0,0 +1,72 @@
+//! ansible inventory output
+use crate::qty::Qty;
+use crate::{write_output, Error, QtyByQualifier};
+use itertools::Itertools;
+
+/// An ansible host variable `<kind>_<qualifier>=<value>`, the byte-sized resources
+/// (memory, storage,...) are converted into GiB (and suffixed by `_gib`)
+fn ansible_host_var(kind: &str, qualifier: &str, qty: &Qty) -> String {
+    let name = kind.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+    if kind == "memory" || kind.ends_with("storage") || kind.starts_with("hugepages-") {
+        format!(
+            "{}_{}_gib={:.2}",
+            name,
+            qualifier,
+            f64::from(qty) / 1024f64.powi(3)
+        )
+    } else {
+        format!("{}_{}={:.2}", name, qualifier, f64::from(qty))
+    }
+}
+
+/// Display an ansible inventory (INI format) with the nodes as hosts of the group
+/// `kubernetes_nodes`, and their resources as host variables
+/// - `data` should be grouped by node, resource
+pub fn display_as_ansible(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    write_output(out, "[kubernetes_nodes]\n")?;
+    for (node_name, rows) in &data
+        .iter()
+        .filter(|(k, _)| k.len() == 2)
+        .group_by(|(k, _)| k[0].clone())
+    {
+        let mut vars = vec![];
+        for (k, qtys) in rows.filter_map(|(k, oqtys)| oqtys.as_ref().map(|qtys| (k, qtys))) {
+            for (qualifier, oqty) in [
+                ("allocatable", &qtys.allocatable),
+                ("requested", &qtys.requested),
+                ("free", &qtys.calc_free()),
+            ] {
+                if let Some(qty) = oqty {
+                    vars.push(ansible_host_var(&k[1], qualifier, qty));
+                }
+            }
+        }
+        write_output(out, &format!("{} {}\n", node_name, vars.join(" ")))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_ansible_host_var() {
+        assert_eq!(
+            ansible_host_var("cpu", "allocatable", &Qty::from_str("3500m").unwrap()),
+            "cpu_allocatable=3.50"
+        );
+        assert_eq!(
+            ansible_host_var("memory", "free", &Qty::from_str("1536Mi").unwrap()),
+            "memory_free_gib=1.50"
+        );
+        assert_eq!(
+            ansible_host_var("nvidia.com/gpu", "requested", &Qty::from_str("2").unwrap()),
+            "nvidia_com_gpu_requested=2.00"
+        );
+    }
+}
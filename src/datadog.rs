@@ -0,0 +1,83 @@
+//! Datadog Events output
+use crate::{threshold_violations, write_output, Error, GroupBy, QtyByQualifier};
+
+/// Display the rows over the thresholds (`--threshold-request`, `--threshold-limit`)
+/// as a json array of payloads for the [Datadog Events API](https://docs.datadoghq.com/api/latest/events/)
+/// (to post one by one to `https://api.datadoghq.com/api/v1/events`)
+pub fn display_as_datadog_events(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+    threshold_request: Option<f64>,
+    threshold_limit: Option<f64>,
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    let events = datadog_events(data, group_by, threshold_request, threshold_limit);
+    let json = serde_json::to_string_pretty(&events).map_err(|source| Error::JsonError {
+        context: "serialize the datadog events".to_string(),
+        source,
+    })?;
+    write_output(out, &format!("{}\n", json))
+}
+
+fn datadog_events(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+    threshold_request: Option<f64>,
+    threshold_limit: Option<f64>,
+) -> Vec<serde_json::Value> {
+    threshold_violations(data, threshold_request, threshold_limit)
+        .iter()
+        .map(|v| {
+            let mut tags = v
+                .key
+                .iter()
+                .zip(group_by.iter())
+                .map(|(value, g)| format!("{}:{}", g, value))
+                .collect::<Vec<_>>();
+            tags.push(format!("qualifier:{:?}", v.qualifier).to_lowercase());
+            tags.push("source:kubectl-view-allocations".to_string());
+            serde_json::json!({
+                "title": format!("{:?} over threshold on {}", v.qualifier, v.key.join("/")),
+                "text": Error::from(v).to_string(),
+                "tags": tags,
+                // over the allocatable is more than a warning
+                "alert_type": if v.ratio > 1.0 { "error" } else { "warning" },
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn test_datadog_events() {
+        let rows = vec![(
+            key(&["cpu", "node1"]),
+            Some(QtyByQualifier {
+                requested: qty("3"),
+                limit: qty("6"),
+                allocatable: qty("4"),
+                ..QtyByQualifier::default()
+            }),
+        )];
+        let group_by = vec![GroupBy::resource, GroupBy::node];
+        assert!(datadog_events(&rows, &group_by, None, None).is_empty());
+        let events = datadog_events(&rows, &group_by, Some(0.5), Some(1.2));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["alert_type"], "warning");
+        assert_eq!(
+            events[0]["tags"],
+            serde_json::json!([
+                "resource:cpu",
+                "node:node1",
+                "qualifier:requested",
+                "source:kubectl-view-allocations"
+            ])
+        );
+        assert_eq!(events[1]["alert_type"], "error");
+        assert_eq!(events[1]["title"], "Limit over threshold on cpu/node1");
+    }
+}
@@ -0,0 +1,244 @@
+//! delimiter-separated values output (csv, tsv)
+use crate::qty::Qty;
+use crate::{
+    format_overcommit_ratio, format_utilization_ratio, write_output, Column, DisplayOptions, Error,
+    GroupBy, QtyByQualifier,
+};
+use chrono::prelude::*;
+
+/// Display as csv, see `format_as_csv`
+pub fn display_as_csv(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+    with_schema: bool,
+    options: &DisplayOptions,
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    display_as_dsv(data, group_by, with_schema, options, ',', out)
+}
+
+/// Display as delimiter-separated values (`,` for csv, `\t` for tsv), see `format_as_dsv`
+pub fn display_as_dsv(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+    with_schema: bool,
+    options: &DisplayOptions,
+    sep: char,
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    write_output(
+        out,
+        &format_as_dsv(data, group_by, with_schema, options, sep),
+    )
+}
+
+/// Format as csv, see `format_as_dsv`
+pub fn format_as_csv(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+    with_schema: bool,
+    options: &DisplayOptions,
+) -> String {
+    format_as_dsv(data, group_by, with_schema, options, ',')
+}
+
+/// Format as values separated by `sep`, with a second header row of the types of the columns
+/// (`string`, `float64`, `percent`) if `with_schema`, and 2 decimals for the quantities by default.
+/// With `percent_only`, the columns with a percentage have no quantity.
+pub fn format_as_dsv(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+    with_schema: bool,
+    options: &DisplayOptions,
+    sep: char,
+) -> String {
+    let precision = options.precision.unwrap_or(2);
+    let percent_only = options.percent_only;
+    let columns = Column::visible(options.show_utilization, false, &options.hidden_columns);
+    let with_ratio = columns.contains(&Column::utilization);
+    let sep = sep.to_string();
+    let mut out = String::new();
+    // print header
+    let mut titles = vec!["Date".to_string(), "Kind".to_string()];
+    titles.extend(group_by.iter().map(|x| x.to_string()));
+    for c in columns.iter() {
+        if !(c.with_percentage() && percent_only) {
+            titles.push(c.title().to_string());
+        }
+        if c.with_percentage() {
+            titles.push(format!("%{}", c.title()));
+        }
+    }
+    if with_ratio {
+        titles.push("%Util/Req".to_string());
+    }
+    if options.show_overcommit {
+        titles.push("Overcommit".to_string());
+    }
+    out.push_str(&titles.join(&sep));
+    out.push('\n');
+    if with_schema {
+        let mut types = vec!["string"; 2 + group_by.len()];
+        for c in columns.iter() {
+            if !(c.with_percentage() && percent_only) {
+                types.push("float64");
+            }
+            if c.with_percentage() {
+                types.push("percent");
+            }
+        }
+        if with_ratio {
+            types.push("percent");
+        }
+        if options.show_overcommit {
+            types.push("float64");
+        }
+        out.push_str(&types.join(&sep));
+        out.push('\n');
+    }
+
+    // print data
+    let empty = "".to_string();
+    let datetime = Utc::now().to_rfc3339();
+    for (k, oqtys) in data {
+        if let Some(qtys) = oqtys {
+            let mut row = vec![
+                datetime.clone(),
+                group_by
+                    .get(k.len() - 1)
+                    .map(|x| x.to_string())
+                    .unwrap_or_else(|| empty.clone()),
+            ];
+            for i in 0..group_by.len() {
+                row.push(k.get(i).cloned().unwrap_or_else(|| empty.clone()));
+            }
+            for column in columns.iter() {
+                let oqty = column.value_of(qtys);
+                if column.with_percentage() {
+                    add_cells_for_cvs(&mut row, &oqty, &qtys.allocatable, precision, percent_only);
+                } else {
+                    row.push(
+                        oqty.as_ref()
+                            .map(|qty| format!("{:.*}", precision, f64::from(qty)))
+                            .unwrap_or_else(|| empty.clone()),
+                    );
+                }
+            }
+            if with_ratio {
+                row.push(format_utilization_ratio(qtys.calc_utilization_ratio()));
+            }
+            if options.show_overcommit {
+                row.push(format_overcommit_ratio(qtys.calc_overcommit_ratio()));
+            }
+            out.push_str(&row.join(&sep));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Add the cells of the quantity and of its percentage (only the percentage if `percent_only`)
+fn add_cells_for_cvs(
+    row: &mut Vec<String>,
+    oqty: &Option<Qty>,
+    o100: &Option<Qty>,
+    precision: usize,
+    percent_only: bool,
+) {
+    match oqty {
+        None => {
+            if !percent_only {
+                row.push("".to_string());
+            }
+            row.push("".to_string());
+        }
+        Some(ref qty) => {
+            if !percent_only {
+                row.push(format!("{:.*}", precision, f64::from(qty)));
+            }
+            row.push(match o100 {
+                None => "".to_string(),
+                Some(q100) => format!("{:.0}%", qty.calc_percentage(q100)),
+            });
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_format_as_csv() {
+        let rows = vec![(
+            key(&["cpu", "node1"]),
+            Some(QtyByQualifier {
+                requested: qty("1500m"),
+                allocatable: qty("4"),
+                ..QtyByQualifier::default()
+            }),
+        )];
+        let group_by = [GroupBy::resource, GroupBy::node];
+        let options = DisplayOptions {
+            precision: Some(3),
+            ..DisplayOptions::default()
+        };
+        let csv = format_as_csv(&rows, &group_by, false, &options);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "Date,Kind,resource,node,Requested,%Requested,Limit,%Limit,Allocatable,Free"
+        );
+        assert!(lines[1].ends_with(",node,cpu,node1,1.500,38%,,,4.000,2.500"));
+        let options = DisplayOptions {
+            show_utilization: true,
+            hidden_columns: HashSet::from([Column::limit, Column::free]),
+            ..options
+        };
+        let csv = format_as_csv(&rows, &group_by, true, &options);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "Date,Kind,resource,node,Utilization,%Utilization,Requested,%Requested,Allocatable,%Util/Req"
+        );
+        assert_eq!(
+            lines[1],
+            "string,string,string,string,float64,percent,float64,percent,float64,percent"
+        );
+        assert!(lines[2].ends_with(",node,cpu,node1,,,1.500,38%,4.000,"));
+        let options = DisplayOptions {
+            show_utilization: false,
+            percent_only: true,
+            ..options
+        };
+        let csv = format_as_csv(&rows, &group_by, false, &options);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "Date,Kind,resource,node,%Requested,Allocatable");
+        assert!(lines[1].ends_with(",node,cpu,node1,38%,4.000"));
+        let options = DisplayOptions {
+            show_overcommit: true,
+            ..options
+        };
+        let csv = format_as_csv(&rows, &group_by, false, &options);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "Date,Kind,resource,node,%Requested,Allocatable,Overcommit"
+        );
+        // without limit
+        assert!(lines[1].ends_with(",node,cpu,node1,38%,4.000,"));
+        let tsv = format_as_dsv(&rows, &group_by, true, &options, '\t');
+        let lines = tsv.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "Date\tKind\tresource\tnode\t%Requested\tAllocatable\tOvercommit"
+        );
+        assert_eq!(
+            lines[1],
+            "string\tstring\tstring\tstring\tpercent\tfloat64\tfloat64"
+        );
+        assert!(lines[2].ends_with("\tnode\tcpu\tnode1\t38%\t4.000\t"));
+    }
+}
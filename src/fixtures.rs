@@ -0,0 +1,58 @@
+//! fixtures shared by the tests of the modules
+use crate::qty::Qty;
+use crate::{Location, QtyByQualifier, Resource, ResourceQualifier};
+use std::str::FromStr;
+
+pub fn qty(s: &str) -> Option<Qty> {
+    Some(Qty::from_str(s).unwrap())
+}
+
+pub fn new_resource(
+    kind: &str,
+    qualifier: ResourceQualifier,
+    qty: &str,
+    location: Location,
+) -> Resource {
+    Resource {
+        kind: kind.to_string(),
+        quantity: Qty::from_str(qty).unwrap(),
+        location,
+        qualifier,
+    }
+}
+
+pub fn node_location(node: &str) -> Location {
+    Location {
+        node_name: Some(node.to_string()),
+        ..Location::default()
+    }
+}
+
+pub fn pod_location(namespace: &str, pod: &str) -> Location {
+    Location {
+        namespace: Some(namespace.to_string()),
+        pod_name: Some(pod.to_string()),
+        ..Location::default()
+    }
+}
+
+/// the path of a row (eg: `key(&["cpu", "node1"])`)
+pub fn key(k: &[&str]) -> Vec<String> {
+    k.iter().map(|x| x.to_string()).collect()
+}
+
+/// a row (as made by `make_qualifiers`) with the requested and the allocatable quantities
+pub fn row(
+    k: &[&str],
+    requested: &str,
+    allocatable: Option<&str>,
+) -> (Vec<String>, Option<QtyByQualifier>) {
+    (
+        key(k),
+        Some(QtyByQualifier {
+            requested: qty(requested),
+            allocatable: allocatable.and_then(qty),
+            ..QtyByQualifier::default()
+        }),
+    )
+}
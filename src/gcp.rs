@@ -0,0 +1,195 @@
+//! Google Cloud Monitoring output
+use crate::prometheus::{exported_gauges, metric_label_name, prometheus_unit};
+use crate::{write_output, CliOpts, Error, GroupBy, QtyByQualifier};
+use chrono::prelude::*;
+
+/// The maximum number of TimeSeries by request of `projects.timeSeries.create`
+const GCP_MONITORING_MAX_TIME_SERIES: usize = 200;
+
+/// Display as a json array of bodies for the `projects.timeSeries.create` API of
+/// [Google Cloud Monitoring](https://cloud.google.com/monitoring/api/ref_v3/rest/v3/projects.timeSeries/create)
+/// (to post one by one to `https://monitoring.googleapis.com/v3/projects/<project>/timeSeries`)
+pub fn display_as_gcp_monitoring(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+    cli_opts: &CliOpts,
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    let (project_id, location) = match (&cli_opts.gcp_project, &cli_opts.gcp_location) {
+        (Some(project_id), Some(location)) => (project_id, location),
+        _ => {
+            return Err(Error::MissingOption {
+                option: "--gcp-project or --gcp-location".to_string(),
+                by: "--output gcp-monitoring".to_string(),
+            })
+        }
+    };
+    if cli_opts.gcp_cluster.is_none() && !group_by.contains(&GroupBy::cluster) {
+        return Err(Error::MissingOption {
+            option: "--gcp-cluster (or --group-by cluster)".to_string(),
+            by: "--output gcp-monitoring".to_string(),
+        });
+    }
+    let time_series = gcp_time_series(
+        data,
+        group_by,
+        project_id,
+        location,
+        cli_opts.gcp_cluster.as_deref(),
+        &Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    );
+    let bodies = time_series
+        .chunks(GCP_MONITORING_MAX_TIME_SERIES)
+        .map(|chunk| serde_json::json!({ "timeSeries": chunk }))
+        .collect::<Vec<_>>();
+    let json = serde_json::to_string_pretty(&bodies).map_err(|source| Error::JsonError {
+        context: "serialize the gcp time series".to_string(),
+        source,
+    })?;
+    write_output(out, &format!("{}\n", json))
+}
+
+/// The TimeSeries of the gauges (see `exported_gauges`), as custom metrics
+/// (eg: `custom.googleapis.com/kubectl_view_allocations/requested_cores`) on the monitored
+/// resource of the row: `k8s_pod` (with a namespace and a pod), `k8s_node` (with a node),
+/// or `k8s_cluster`. The other values of the path (eg: the resource) are labels of the metric.
+fn gcp_time_series(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+    project_id: &str,
+    location: &str,
+    cluster_name: Option<&str>,
+    end_time: &str,
+) -> Vec<serde_json::Value> {
+    exported_gauges(data, group_by)
+        .iter()
+        .map(|gauge| {
+            let mut values = gauge
+                .key
+                .iter()
+                .zip(group_by.iter())
+                .map(|(v, g)| (g.clone(), v.as_str()))
+                .collect::<Vec<_>>();
+            let has = |g: &GroupBy| values.iter().any(|(x, _)| x == g);
+            let resource_type = if has(&GroupBy::namespace) && has(&GroupBy::pod) {
+                "k8s_pod"
+            } else if has(&GroupBy::node) {
+                "k8s_node"
+            } else {
+                "k8s_cluster"
+            };
+            let mut take = |g: &GroupBy| {
+                values
+                    .iter()
+                    .position(|(x, _)| x == g)
+                    .map(|i| values.remove(i).1)
+            };
+            let cluster_name = take(&GroupBy::cluster).or(cluster_name);
+            let mut resource_labels = serde_json::json!({
+                "project_id": project_id,
+                "location": location,
+                "cluster_name": cluster_name.unwrap_or_default(),
+            });
+            // the others values stay as labels of the metric
+            match resource_type {
+                "k8s_pod" => {
+                    resource_labels["namespace_name"] = take(&GroupBy::namespace).into();
+                    resource_labels["pod_name"] = take(&GroupBy::pod).into();
+                }
+                "k8s_node" => resource_labels["node_name"] = take(&GroupBy::node).into(),
+                _ => {}
+            }
+            let metric_labels = values
+                .iter()
+                .map(|(g, v)| (metric_label_name(g), serde_json::Value::from(*v)))
+                .collect::<serde_json::Map<_, _>>();
+            serde_json::json!({
+                "metric": {
+                    "type": format!(
+                        "custom.googleapis.com/kubectl_view_allocations/{}_{}",
+                        gauge.qualifier,
+                        prometheus_unit(gauge.kind)
+                    ),
+                    "labels": metric_labels,
+                },
+                "resource": {
+                    "type": resource_type,
+                    "labels": resource_labels,
+                },
+                "metricKind": "GAUGE",
+                "valueType": "DOUBLE",
+                "points": [{
+                    "interval": { "endTime": end_time },
+                    "value": { "doubleValue": gauge.value },
+                }],
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn test_gcp_time_series() {
+        let rows = vec![
+            row(&["cpu"], "1500m", None),
+            row(&["cpu", "node1"], "1500m", None),
+            row(&["cpu", "node1", "default"], "1500m", None),
+            row(&["cpu", "node1", "default", "nginx"], "1500m", None),
+        ];
+        let group_by = vec![
+            GroupBy::resource,
+            GroupBy::node,
+            GroupBy::namespace,
+            GroupBy::pod,
+        ];
+        let series = gcp_time_series(
+            &rows,
+            &group_by,
+            "my-project",
+            "europe-west1",
+            Some("prod"),
+            "2026-10-15T12:00:00Z",
+        );
+        assert_eq!(
+            series,
+            vec![serde_json::json!({
+                "metric": {
+                    "type": "custom.googleapis.com/kubectl_view_allocations/requested_cores",
+                    "labels": { "resource": "cpu", "node": "node1" },
+                },
+                "resource": {
+                    "type": "k8s_pod",
+                    "labels": {
+                        "project_id": "my-project",
+                        "location": "europe-west1",
+                        "cluster_name": "prod",
+                        "namespace_name": "default",
+                        "pod_name": "nginx",
+                    },
+                },
+                "metricKind": "GAUGE",
+                "valueType": "DOUBLE",
+                "points": [{
+                    "interval": { "endTime": "2026-10-15T12:00:00Z" },
+                    "value": { "doubleValue": 1.5 },
+                }],
+            })]
+        );
+        // a node
+        let series = gcp_time_series(
+            &rows[..2],
+            &group_by,
+            "my-project",
+            "europe-west1",
+            None,
+            "2026-10-15T12:00:00Z",
+        );
+        assert_eq!(series[0]["resource"]["type"], "k8s_node");
+        assert_eq!(series[0]["resource"]["labels"]["node_name"], "node1");
+        assert_eq!(series[0]["resource"]["labels"]["cluster_name"], "");
+    }
+}
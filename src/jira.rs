@@ -0,0 +1,207 @@
+//! JIRA issues output
+use crate::{threshold_violations, write_output, CliOpts, Error, GroupBy, QtyByQualifier};
+use itertools::Itertools;
+
+/// The path of the JIRA REST API to create an issue
+const JIRA_CREATE_ISSUE_PATH: &str = "/rest/api/2/issue";
+
+/// Print the JIRA issues of the threshold violations (see `jira_issues`), or create them
+/// on `--jira-url` (with `curl`, one request per issue)
+pub fn create_jira_issues(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+    cli_opts: &CliOpts,
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    let issues = jira_issues(
+        data,
+        group_by,
+        &cli_opts.jira_project,
+        cli_opts.threshold_request,
+        cli_opts.threshold_limit,
+    );
+    let base_url = match &cli_opts.jira_url {
+        Some(base_url) => base_url,
+        None => {
+            let json =
+                serde_json::to_string_pretty(&issues).map_err(|source| Error::JsonError {
+                    context: "serialize the jira issues".to_string(),
+                    source,
+                })?;
+            return write_output(out, &format!("{}\n", json));
+        }
+    };
+    let token = jira_token(cli_opts)?;
+    let url = format!(
+        "{}{}",
+        base_url.trim_end_matches('/'),
+        JIRA_CREATE_ISSUE_PATH
+    );
+    for issue in issues {
+        let result = Command::new("curl")
+            .args(["--silent", "--show-error", "--fail", "--config", "-"])
+            .args(["--header", "Content-Type: application/json"])
+            .args(["--request", "POST", "--output", "/dev/null"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .and_then(|mut child| {
+                if let Some(stdin) = child.stdin.as_mut() {
+                    // by stdin, to not expose the token in the arguments (eg: with `ps`),
+                    // as a json string to escape the quotes
+                    writeln!(stdin, "url = {}", serde_json::Value::from(url.as_str()))?;
+                    writeln!(
+                        stdin,
+                        "header = {}",
+                        serde_json::Value::from(format!("Authorization: Bearer {}", token))
+                    )?;
+                    writeln!(
+                        stdin,
+                        "data-binary = {}",
+                        serde_json::Value::from(issue.to_string())
+                    )?;
+                }
+                child.wait_with_output()
+            });
+        let output = result.map_err(|source| Error::CmdError {
+            cmd: "curl".to_owned(),
+            output: None,
+            source: Some(source),
+        })?;
+        if !output.status.success() {
+            return Err(Error::CmdError {
+                cmd: "curl".to_owned(),
+                output: Some(output),
+                source: None,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The token of `--jira-token-file`, or else of the env var `JIRA_TOKEN`
+fn jira_token(cli_opts: &CliOpts) -> Result<String, Error> {
+    let token = match &cli_opts.jira_token_file {
+        Some(path) => std::fs::read_to_string(path).map_err(|source| Error::ReadFileError {
+            path: path.clone(),
+            source,
+        })?,
+        None => std::env::var("JIRA_TOKEN").unwrap_or_default(),
+    };
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(Error::MissingOption {
+            option: "--jira-token-file (or $JIRA_TOKEN)".to_string(),
+            by: "--jira-url".to_string(),
+        });
+    }
+    Ok(token.to_string())
+}
+
+/// The JIRA issues (body of `/rest/api/2/issue`) of the threshold violations,
+/// with the priority `High` over the allocatable, `Medium` otherwise
+fn jira_issues(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+    project_key: &str,
+    threshold_request: Option<f64>,
+    threshold_limit: Option<f64>,
+) -> Vec<serde_json::Value> {
+    threshold_violations(data, threshold_request, threshold_limit)
+        .iter()
+        .map(|v| {
+            // labels can not contain spaces
+            let mut labels = v
+                .key
+                .iter()
+                .zip(group_by.iter())
+                .map(|(value, g)| format!("{}:{}", g, value).replace(' ', "_"))
+                .collect::<Vec<_>>();
+            labels.push(format!("qualifier:{:?}", v.qualifier).to_lowercase());
+            labels.push("kubectl-view-allocations".to_string());
+            let description = v
+                .key
+                .iter()
+                .zip(group_by.iter())
+                .map(|(value, g)| format!("* {}: {}", g, value))
+                .chain(std::iter::once(format!(
+                    "* {:?} / allocatable: {:.0}% (threshold: {:.0}%)",
+                    v.qualifier,
+                    v.ratio * 100.0,
+                    v.threshold * 100.0
+                )))
+                .join("\n");
+            serde_json::json!({
+                "fields": {
+                    "project": { "key": project_key },
+                    "issuetype": { "name": "Task" },
+                    "summary": format!("{:?} over threshold on {}", v.qualifier, v.key.join("/")),
+                    "description": description,
+                    "labels": labels,
+                    "priority": { "name": if v.ratio > 1.0 { "High" } else { "Medium" } },
+                }
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn test_jira_issues() {
+        let rows = vec![(
+            key(&["cpu", "node1"]),
+            Some(QtyByQualifier {
+                requested: qty("3"),
+                limit: qty("6"),
+                allocatable: qty("4"),
+                ..QtyByQualifier::default()
+            }),
+        )];
+        let group_by = vec![GroupBy::resource, GroupBy::node];
+        assert!(jira_issues(&rows, &group_by, "OPS", None, None).is_empty());
+        let issues = jira_issues(&rows, &group_by, "OPS", Some(0.5), Some(1.2));
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0]["fields"]["project"]["key"], "OPS");
+        assert_eq!(issues[0]["fields"]["priority"]["name"], "Medium");
+        assert_eq!(
+            issues[0]["fields"]["labels"],
+            serde_json::json!([
+                "resource:cpu",
+                "node:node1",
+                "qualifier:requested",
+                "kubectl-view-allocations"
+            ])
+        );
+        assert_eq!(
+            issues[1]["fields"]["summary"],
+            "Limit over threshold on cpu/node1"
+        );
+        assert_eq!(issues[1]["fields"]["priority"]["name"], "High");
+    }
+
+    #[test]
+    fn test_jira_token_file() {
+        use clap::Parser;
+        let path = std::env::temp_dir().join(format!("jira-token-{}", std::process::id()));
+        std::fs::write(&path, "secret\n").unwrap();
+        let cli_opts = CliOpts::parse_from([
+            "view-allocations",
+            "--jira-token-file",
+            path.to_str().unwrap(),
+        ]);
+        assert_eq!(jira_token(&cli_opts).unwrap(), "secret");
+        std::fs::write(&path, "").unwrap();
+        assert!(matches!(
+            jira_token(&cli_opts),
+            Err(Error::MissingOption { .. })
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
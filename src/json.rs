@@ -0,0 +1,83 @@
+//! json output
+use crate::qty::Qty;
+use crate::{write_output, Error, QtyByQualifier};
+
+/// A row of the json (and yaml) output, with the quantities of the table
+/// (not the ones of the extra columns, like quotas or system reserved)
+#[derive(serde::Serialize)]
+pub(crate) struct JsonRow<'a> {
+    path: &'a [String],
+    requested: &'a Option<Qty>,
+    limit: &'a Option<Qty>,
+    allocatable: &'a Option<Qty>,
+    utilization: &'a Option<Qty>,
+    free: Option<Qty>,
+}
+
+pub(crate) fn json_rows(data: &[(Vec<String>, Option<QtyByQualifier>)]) -> Vec<JsonRow<'_>> {
+    data.iter()
+        .filter_map(|(k, oqtys)| {
+            oqtys.as_ref().map(|qtys| JsonRow {
+                path: k,
+                requested: &qtys.requested,
+                limit: &qtys.limit,
+                allocatable: &qtys.allocatable,
+                utilization: &qtys.utilization,
+                free: qtys.calc_free(),
+            })
+        })
+        .collect()
+}
+
+/// Format the rows as a json array of objects with the `path` of the row (the keys of the groups)
+/// and the quantities (value & human readable) by qualifier
+pub fn format_as_json(data: &[(Vec<String>, Option<QtyByQualifier>)]) -> Result<String, Error> {
+    serde_json::to_string_pretty(&json_rows(data)).map_err(|source| Error::JsonError {
+        context: "serialize the resources as json".to_string(),
+        source,
+    })
+}
+
+/// Display the rows as json, see `format_as_json`
+pub fn display_as_json(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    write_output(out, &format!("{}\n", format_as_json(data)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn test_format_as_json() {
+        let rows = vec![(
+            key(&["cpu", "ns1"]),
+            Some(QtyByQualifier {
+                requested: qty("1500m"),
+                allocatable: qty("4"),
+                quota_hard: qty("2"),
+                system_reserved: qty("1"),
+                ..QtyByQualifier::default()
+            }),
+        )];
+        let json: serde_json::Value =
+            serde_json::from_str(&format_as_json(&rows).unwrap()).unwrap();
+        let entry = json[0].as_object().unwrap();
+        assert_eq!(
+            entry.keys().collect::<Vec<_>>(),
+            [
+                "path",
+                "requested",
+                "limit",
+                "allocatable",
+                "utilization",
+                "free"
+            ]
+        );
+        assert_eq!(entry["free"]["human"], "2.5");
+        assert!(entry["limit"].is_null());
+    }
+}
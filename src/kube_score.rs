@@ -0,0 +1,109 @@
+//! kube-score output
+use crate::{is_empty, write_output, Error, QtyByQualifier};
+use itertools::Itertools;
+
+/// Display issues on pods' resources as the json output of [kube-score](https://github.com/zegl/kube-score)
+/// (one object per pod, with a single check)
+/// - `data` should be grouped by namespace, pod, resource
+pub fn display_as_kube_score(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    let objects = data
+        .iter()
+        .filter(|(k, _)| k.len() == 3 && k[2] != "pods")
+        .group_by(|(k, _)| (k[0].clone(), k[1].clone()))
+        .into_iter()
+        .map(|((namespace, pod_name), rows)| {
+            let mut grade = KUBE_SCORE_GRADE_ALL_OK;
+            let mut comments = vec![];
+            for (k, oqtys) in rows {
+                for (severity, summary) in kube_score_issues(oqtys) {
+                    grade = grade.min(severity);
+                    comments.push(serde_json::json!({
+                        "Path": k[2],
+                        "Summary": summary,
+                        "Description": "",
+                    }));
+                }
+            }
+            serde_json::json!({
+                "ObjectName": format!("{}/{}", namespace, pod_name),
+                "TypeMeta": { "apiVersion": "v1", "kind": "Pod" },
+                "ObjectMeta": { "name": pod_name, "namespace": namespace },
+                "Checks": [{
+                    "Check": {
+                        "Name": "Resource Allocations",
+                        "ID": "view-allocations",
+                        "TargetType": "Pod",
+                        "Comment": "Makes sure that requests and limits of resources are set and consistent",
+                        "Optional": false,
+                    },
+                    "Grade": grade,
+                    "Skipped": false,
+                    "Comments": comments,
+                }],
+                "FileName": "",
+                "FileRow": 0,
+            })
+        })
+        .collect::<Vec<_>>();
+    let json = serde_json::to_string_pretty(&objects).map_err(|source| Error::JsonError {
+        context: "serialize the kube-score objects".to_string(),
+        source,
+    })?;
+    write_output(out, &format!("{}\n", json))
+}
+
+// grades used by kube-score
+const KUBE_SCORE_GRADE_CRITICAL: u8 = 1;
+const KUBE_SCORE_GRADE_WARNING: u8 = 5;
+const KUBE_SCORE_GRADE_ALL_OK: u8 = 10;
+
+fn kube_score_issues(oqtys: &Option<QtyByQualifier>) -> Vec<(u8, &'static str)> {
+    let mut issues = vec![];
+    if let Some(qtys) = oqtys {
+        if is_empty(&qtys.requested) {
+            issues.push((KUBE_SCORE_GRADE_CRITICAL, "Requested is not set"));
+        }
+        if is_empty(&qtys.limit) {
+            issues.push((KUBE_SCORE_GRADE_WARNING, "Limit is not set"));
+        } else {
+            if qtys.requested > qtys.limit {
+                issues.push((KUBE_SCORE_GRADE_CRITICAL, "Requested is over Limit"));
+            }
+            if qtys.utilization > qtys.limit {
+                issues.push((KUBE_SCORE_GRADE_CRITICAL, "Utilization is over Limit"));
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn test_kube_score_issues() {
+        let qtys = QtyByQualifier {
+            requested: qty("2"),
+            limit: qty("1"),
+            ..QtyByQualifier::default()
+        };
+        assert_eq!(
+            kube_score_issues(&Some(qtys)),
+            vec![(KUBE_SCORE_GRADE_CRITICAL, "Requested is over Limit")]
+        );
+        let qtys = QtyByQualifier {
+            requested: qty("1"),
+            ..QtyByQualifier::default()
+        };
+        assert_eq!(
+            kube_score_issues(&Some(qtys)),
+            vec![(KUBE_SCORE_GRADE_WARNING, "Limit is not set")]
+        );
+        assert!(kube_score_issues(&None).is_empty());
+    }
+}
@@ -1,6 +1,23 @@
+pub mod ansible;
+pub mod datadog;
+pub mod dsv;
+pub mod gcp;
+pub mod jira;
+pub mod json;
+pub mod kube_score;
+pub mod markdown;
 pub mod metrics;
+pub mod prometheus;
 pub mod qty;
+pub mod resource_quota;
+pub mod sftp;
+pub mod statsd;
+pub mod table;
 pub mod tree;
+pub mod yaml;
+
+#[cfg(test)]
+mod fixtures;
 
 // mod human_format;
 use chrono::prelude::*;
@@ -9,12 +26,13 @@ use clap::ArgEnum;
 use clap::Parser;
 use core::convert::TryFrom;
 use itertools::Itertools;
-use k8s_openapi::api::core::v1::{Node, Pod};
+use k8s_openapi::api::apps::v1::ReplicaSet;
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::{LimitRange, Namespace, Node, Pod, ResourceQuota};
 use kube::api::{Api, ListParams, ObjectList};
-#[cfg(feature = "prettytable")]
-use prettytable::{cell, format, row, Cell, Row, Table};
 use qty::Qty;
-use std::collections::BTreeMap;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::str::FromStr;
 use tracing::{info, instrument, warn};
 
@@ -29,7 +47,7 @@ pub enum Error {
 
     #[error("Failed to read Qty of location {location:?} / {qualifier:?} {kind}={input}")]
     ResourceQtyParseError {
-        location: Location,
+        location: Box<Location>,
         qualifier: ResourceQualifier,
         kind: String,
         input: String,
@@ -42,6 +60,95 @@ pub enum Error {
         source: qty::Error,
     },
 
+    #[error("Failed to collect resources of context '{context}': timed out after {secs}s")]
+    ContextTimeout { context: String, secs: u64 },
+
+    #[error("Failed to merge locations, conflicting values of {field}: '{left}' vs '{right}'")]
+    LocationConflict {
+        field: String,
+        left: String,
+        right: String,
+    },
+
+    #[error("Failed to read the cost config '{path}'")]
+    CostConfigReadError {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to parse the cost config '{path}'")]
+    CostConfigParseError {
+        path: std::path::PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("Failed to read the namespace groups '{path}'")]
+    NamespaceGroupsReadError {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to parse the namespace groups '{path}'")]
+    NamespaceGroupsParseError {
+        path: std::path::PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("Failed to read the color theme '{path}'")]
+    ColorThemeReadError {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to parse the color theme '{path}'")]
+    ColorThemeParseError {
+        path: std::path::PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error(
+        "{qualifier:?} of '{path}' is {ratio:.2} of allocatable, over the threshold {threshold:.2}"
+    )]
+    ThresholdExceeded {
+        qualifier: ResourceQualifier,
+        path: String,
+        ratio: f64,
+        threshold: f64,
+    },
+
+    #[error("Failed to read '{path}'")]
+    ReadFileError {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to write '{path}'")]
+    WriteFileError {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Missing {option}, required by {by}")]
+    MissingOption { option: String, by: String },
+
+    #[error("Incompatible options: {reason}")]
+    IncompatibleOptions { reason: String },
+
+    #[error("Invalid value of {option}: {reason}")]
+    InvalidOption { option: String, reason: String },
+
+    #[error("Failed to {context}")]
+    JsonError {
+        context: String,
+        source: serde_json::Error,
+    },
+
+    #[error("Failed to {context}")]
+    YamlError {
+        context: String,
+        source: serde_yaml::Error,
+    },
+
     #[error("Failed to {context}")]
     KubeError {
         context: String,
@@ -59,16 +166,183 @@ pub enum Error {
         context: String,
         source: kube::config::InferConfigError,
     },
+
+    #[error("Failed to write the output")]
+    WriteOutputError { source: std::io::Error },
+
+    #[cfg(feature = "sftp")]
+    #[error("Failed to {context}")]
+    SftpError {
+        context: String,
+        source: ssh2::Error,
+    },
+
+    #[error("Failed to {context}")]
+    SftpIoError {
+        context: String,
+        source: std::io::Error,
+    },
 }
 
-#[derive(Debug, Clone, Default)]
+impl Error {
+    /// The exit code of the process: 2 when a threshold is exceeded, 1 for other errors
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::ThresholdExceeded { .. } => 2,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+// missing fields (eg: in snapshots of older versions) take their default values
+#[serde(default)]
 pub struct Location {
+    /// the kubeconfig context of the resource (only set when collecting several contexts)
+    pub cluster_name: Option<String>,
     pub node_name: Option<String>,
     pub namespace: Option<String>,
     pub pod_name: Option<String>,
+    pub container_name: Option<String>,
+    pub is_daemonset: bool,
+    pub is_job: bool,
+    /// the controller of the pod, as `<kind>/<name>` (eg: `Deployment/nginx` when resolved)
+    pub owner_name: Option<String>,
+    /// the uid of the controller of `owner_name`, to resolve its own owner (see `OwnerCache`)
+    pub owner_uid: Option<String>,
+    pub terminating: bool,
+    /// the pod is waiting to be scheduled (see `--include-pending`)
+    pub pending: bool,
+    pub kubelet_version: Option<String>,
+    pub node_ip: Option<String>,
+    pub cpu_manager_policy: Option<String>,
+    /// the container gets exclusive CPUs from the `static` policy of the CPU Manager
+    /// (Guaranteed pod & integer CPU request)
+    pub exclusive_cpu: bool,
+    /// the size class of the limits of the container, see `limit_tier`
+    pub limit_tier: Option<String>,
+    pub restart_count: u32,
+    pub pod_ip: Option<String>,
+    pub runtime_class: Option<String>,
+    /// the tool managing the pod (eg: `Helm`), see `managed_by`
+    pub managed_by: Option<String>,
+    pub resource_version: Option<String>,
+    pub missing_requests: bool,
+    /// a container has a limit lower than its request (for a resource)
+    pub has_invalid_limits: bool,
+    pub evicting: bool,
+    pub failing_conditions: Vec<String>,
+    pub imbalance: Option<String>,
+    pub annotations: BTreeMap<String, String>,
+    pub labels: BTreeMap<String, String>,
+    pub namespace_labels: BTreeMap<String, String>,
+    pub namespace_terminating: bool,
 }
 
-#[derive(Debug, Clone)]
+impl Location {
+    /// Merge 2 locations of the same pod (eg: collected by different calls to the API).
+    /// A value set on one side only is kept, values set on both sides should be equal,
+    /// flags are or-ed and the highest restart count is kept.
+    pub fn merge(&self, other: &Location) -> Result<Location, Error> {
+        fn merge_option(
+            field: &str,
+            left: &Option<String>,
+            right: &Option<String>,
+        ) -> Result<Option<String>, Error> {
+            match (left, right) {
+                (Some(l), Some(r)) if l != r => Err(Error::LocationConflict {
+                    field: field.to_string(),
+                    left: l.clone(),
+                    right: r.clone(),
+                }),
+                _ => Ok(left.clone().or_else(|| right.clone())),
+            }
+        }
+        fn merge_map(
+            field: &str,
+            left: &BTreeMap<String, String>,
+            right: &BTreeMap<String, String>,
+        ) -> Result<BTreeMap<String, String>, Error> {
+            let mut merged = right.clone();
+            for (k, v) in left {
+                if let Some(r) = merged.insert(k.clone(), v.clone()) {
+                    if &r != v {
+                        return Err(Error::LocationConflict {
+                            field: format!("{}.{}", field, k),
+                            left: v.clone(),
+                            right: r,
+                        });
+                    }
+                }
+            }
+            Ok(merged)
+        }
+        Ok(Location {
+            cluster_name: merge_option("cluster_name", &self.cluster_name, &other.cluster_name)?,
+            node_name: merge_option("node_name", &self.node_name, &other.node_name)?,
+            namespace: merge_option("namespace", &self.namespace, &other.namespace)?,
+            pod_name: merge_option("pod_name", &self.pod_name, &other.pod_name)?,
+            container_name: merge_option(
+                "container_name",
+                &self.container_name,
+                &other.container_name,
+            )?,
+            is_daemonset: self.is_daemonset || other.is_daemonset,
+            is_job: self.is_job || other.is_job,
+            owner_name: merge_option("owner_name", &self.owner_name, &other.owner_name)?,
+            owner_uid: merge_option("owner_uid", &self.owner_uid, &other.owner_uid)?,
+            terminating: self.terminating || other.terminating,
+            pending: self.pending || other.pending,
+            namespace_terminating: self.namespace_terminating || other.namespace_terminating,
+            kubelet_version: merge_option(
+                "kubelet_version",
+                &self.kubelet_version,
+                &other.kubelet_version,
+            )?,
+            node_ip: merge_option("node_ip", &self.node_ip, &other.node_ip)?,
+            cpu_manager_policy: merge_option(
+                "cpu_manager_policy",
+                &self.cpu_manager_policy,
+                &other.cpu_manager_policy,
+            )?,
+            exclusive_cpu: self.exclusive_cpu || other.exclusive_cpu,
+            limit_tier: merge_option("limit_tier", &self.limit_tier, &other.limit_tier)?,
+            restart_count: self.restart_count.max(other.restart_count),
+            pod_ip: merge_option("pod_ip", &self.pod_ip, &other.pod_ip)?,
+            managed_by: merge_option("managed_by", &self.managed_by, &other.managed_by)?,
+            runtime_class: merge_option(
+                "runtime_class",
+                &self.runtime_class,
+                &other.runtime_class,
+            )?,
+            resource_version: merge_option(
+                "resource_version",
+                &self.resource_version,
+                &other.resource_version,
+            )?,
+            missing_requests: self.missing_requests || other.missing_requests,
+            has_invalid_limits: self.has_invalid_limits || other.has_invalid_limits,
+            evicting: self.evicting || other.evicting,
+            failing_conditions: self
+                .failing_conditions
+                .iter()
+                .chain(other.failing_conditions.iter())
+                .cloned()
+                .unique()
+                .collect(),
+            imbalance: merge_option("imbalance", &self.imbalance, &other.imbalance)?,
+            annotations: merge_map("annotations", &self.annotations, &other.annotations)?,
+            labels: merge_map("labels", &self.labels, &other.labels)?,
+            namespace_labels: merge_map(
+                "namespace_labels",
+                &self.namespace_labels,
+                &other.namespace_labels,
+            )?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Resource {
     pub kind: String,
     pub quantity: Qty,
@@ -76,26 +350,109 @@ pub struct Resource {
     pub qualifier: ResourceQualifier,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum ResourceQualifier {
     Limit,
     Requested,
     Allocatable,
     Utilization,
+    /// capacity of the node not allocatable (system-reserved + kube-reserved + eviction threshold)
+    SystemReserved,
+    /// hard limit of the ResourceQuotas of a namespace
+    QuotaHard,
+    /// part of the ResourceQuotas of a namespace used
+    QuotaUsed,
+    /// limit applied (by the LimitRanges of a namespace) to containers without limit
+    DefaultLimit,
+    /// request applied (by the LimitRanges of a namespace) to containers without request
+    DefaultRequest,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, serde::Serialize)]
 pub struct QtyByQualifier {
     pub limit: Option<Qty>,
     pub requested: Option<Qty>,
     pub allocatable: Option<Qty>,
     pub utilization: Option<Qty>,
+    pub system_reserved: Option<Qty>,
+    pub quota_hard: Option<Qty>,
+    pub quota_used: Option<Qty>,
+    pub default_limit: Option<Qty>,
+    pub default_request: Option<Qty>,
 }
 
 fn add(lhs: Option<Qty>, rhs: &Qty) -> Option<Qty> {
     lhs.map(|l| &l + rhs).or_else(|| Some(rhs.clone()))
 }
 
+fn add_option(lhs: Option<Qty>, rhs: &Option<Qty>) -> Option<Qty> {
+    match rhs {
+        Some(rhs) => add(lhs, rhs),
+        None => lhs,
+    }
+}
+
+impl std::ops::Add<&QtyByQualifier> for QtyByQualifier {
+    type Output = QtyByQualifier;
+    fn add(self, rhs: &QtyByQualifier) -> QtyByQualifier {
+        QtyByQualifier {
+            limit: add_option(self.limit, &rhs.limit),
+            requested: add_option(self.requested, &rhs.requested),
+            allocatable: add_option(self.allocatable, &rhs.allocatable),
+            utilization: add_option(self.utilization, &rhs.utilization),
+            system_reserved: add_option(self.system_reserved, &rhs.system_reserved),
+            quota_hard: add_option(self.quota_hard, &rhs.quota_hard),
+            quota_used: add_option(self.quota_used, &rhs.quota_used),
+            default_limit: add_option(self.default_limit, &rhs.default_limit),
+            default_request: add_option(self.default_request, &rhs.default_request),
+        }
+    }
+}
+
+impl From<&Resource> for QtyByQualifier {
+    fn from(v: &Resource) -> QtyByQualifier {
+        let quantity = Some(v.quantity.clone());
+        match &v.qualifier {
+            ResourceQualifier::Limit => QtyByQualifier {
+                limit: quantity,
+                ..QtyByQualifier::default()
+            },
+            ResourceQualifier::Requested => QtyByQualifier {
+                requested: quantity,
+                ..QtyByQualifier::default()
+            },
+            ResourceQualifier::Allocatable => QtyByQualifier {
+                allocatable: quantity,
+                ..QtyByQualifier::default()
+            },
+            ResourceQualifier::Utilization => QtyByQualifier {
+                utilization: quantity,
+                ..QtyByQualifier::default()
+            },
+            ResourceQualifier::SystemReserved => QtyByQualifier {
+                system_reserved: quantity,
+                ..QtyByQualifier::default()
+            },
+            ResourceQualifier::QuotaHard => QtyByQualifier {
+                quota_hard: quantity,
+                ..QtyByQualifier::default()
+            },
+            ResourceQualifier::QuotaUsed => QtyByQualifier {
+                quota_used: quantity,
+                ..QtyByQualifier::default()
+            },
+            ResourceQualifier::DefaultLimit => QtyByQualifier {
+                default_limit: quantity,
+                ..QtyByQualifier::default()
+            },
+            ResourceQualifier::DefaultRequest => QtyByQualifier {
+                default_request: quantity,
+                ..QtyByQualifier::default()
+            },
+        }
+    }
+}
+
 impl QtyByQualifier {
     pub fn calc_free(&self) -> Option<Qty> {
         let total_used = std::cmp::max(self.limit.as_ref(), self.requested.as_ref());
@@ -110,9 +467,52 @@ impl QtyByQualifier {
                 }
             })
     }
+
+    /// The utilization as a fraction of the requested (above 1.0 when using more than requested),
+    /// `None` without utilization or requested
+    pub fn calc_utilization_ratio(&self) -> Option<f64> {
+        self.utilization
+            .as_ref()
+            .zip(self.requested.as_ref().filter(|r| !r.is_zero()))
+            .map(|(utilization, requested)| f64::from(utilization) / f64::from(requested))
+    }
+
+    /// The limit as a fraction of the allocatable (above 1.0 when overcommitted),
+    /// `None` without limit or allocatable
+    pub fn calc_overcommit_ratio(&self) -> Option<f64> {
+        self.limit
+            .as_ref()
+            .zip(self.allocatable.as_ref().filter(|a| !a.is_zero()))
+            .map(|(limit, allocatable)| f64::from(limit) / f64::from(allocatable))
+    }
 }
 
+fn format_utilization_ratio(ratio: Option<f64>) -> String {
+    ratio
+        .map(|r| format!("{:.0}%", r * 100.0))
+        .unwrap_or_default()
+}
+
+fn format_overcommit_ratio(ratio: Option<f64>) -> String {
+    ratio.map(|r| format!("{:.2}", r)).unwrap_or_default()
+}
+
+/// Sum the quantities of the resources by qualifier (`None` when there is no resource).
+/// Resources of different kinds can not be summed: the sum is then empty (and a warning is logged),
+/// see `try_sum_by_qualifier` for the groups where the kinds are expected to differ.
 pub fn sum_by_qualifier(rsrcs: &[&Resource]) -> Option<QtyByQualifier> {
+    if rsrcs.iter().map(|r| &r.kind).all_equal() {
+        try_sum_by_qualifier(rsrcs)
+    } else {
+        let kinds = rsrcs.iter().map(|r| r.kind.as_str()).unique().join(", ");
+        warn!(%kinds, "can not sum resources of different kinds");
+        Some(QtyByQualifier::default())
+    }
+}
+
+/// Sum the quantities of the resources by qualifier, `None` when there is no resource
+/// or when the resources have different kinds (eg: the groups above the `resource` level)
+pub fn try_sum_by_qualifier(rsrcs: &[&Resource]) -> Option<QtyByQualifier> {
     if !rsrcs.is_empty() {
         let kind = rsrcs
             .get(0)
@@ -121,19 +521,28 @@ pub fn sum_by_qualifier(rsrcs: &[&Resource]) -> Option<QtyByQualifier> {
             .clone();
 
         if rsrcs.iter().all(|i| i.kind == kind) {
-            let sum = rsrcs.iter().fold(QtyByQualifier::default(), |mut acc, v| {
-                match &v.qualifier {
-                    ResourceQualifier::Limit => acc.limit = add(acc.limit, &v.quantity),
-                    ResourceQualifier::Requested => acc.requested = add(acc.requested, &v.quantity),
-                    ResourceQualifier::Allocatable => {
-                        acc.allocatable = add(acc.allocatable, &v.quantity)
-                    }
-                    ResourceQualifier::Utilization => {
-                        acc.utilization = add(acc.utilization, &v.quantity)
-                    }
-                };
-                acc
-            });
+            // the utilization of a node (from node metrics) includes the one of its pods,
+            // so it replaces the utilization of the pods on the rows of a single node;
+            // on the other rows (eg: by namespace, or over several nodes) the pods are summed
+            let is_node_utilization = |r: &Resource| {
+                matches!(r.qualifier, ResourceQualifier::Utilization)
+                    && r.location.pod_name.is_none()
+            };
+            let has_node_utilization = rsrcs.iter().any(|r| is_node_utilization(r));
+            let single_node = rsrcs
+                .iter()
+                .map(|r| (&r.location.cluster_name, &r.location.node_name))
+                .all_equal();
+            let sum = rsrcs
+                .iter()
+                .filter(|r| {
+                    !has_node_utilization
+                        || !matches!(r.qualifier, ResourceQualifier::Utilization)
+                        || is_node_utilization(r) == single_node
+                })
+                .fold(QtyByQualifier::default(), |acc, v| {
+                    acc + &QtyByQualifier::from(*v)
+                });
             Some(sum)
         } else {
             None
@@ -146,28 +555,194 @@ pub fn sum_by_qualifier(rsrcs: &[&Resource]) -> Option<QtyByQualifier> {
 pub fn make_qualifiers(
     rsrcs: &[Resource],
     group_by: &[GroupBy],
-    resource_names: &[String],
+    resource_filter: &ResourceFilter,
 ) -> Vec<(Vec<String>, Option<QtyByQualifier>)> {
     let group_by_fct = group_by.iter().map(GroupBy::to_fct).collect::<Vec<_>>();
-    let mut out = make_group_x_qualifier(
+    // the groups above the `resource` level mix the kinds: they have no quantities
+    let resource_depth = group_by
+        .iter()
+        .position(|g| g == &GroupBy::resource)
+        .unwrap_or(group_by.len());
+    let mut out = make_group_x(
         &(rsrcs
             .iter()
-            .filter(|a| accept_resource(&a.kind, resource_names))
+            .filter(|a| resource_filter.accept(a))
             .collect::<Vec<_>>()),
         &[],
         &group_by_fct,
         0,
+        &|group, depth| {
+            if depth < resource_depth {
+                try_sum_by_qualifier(group)
+            } else {
+                sum_by_qualifier(group)
+            }
+        },
     );
     out.sort_by_key(|i| i.0.clone());
     out
 }
 
-fn make_group_x_qualifier(
+/// Flag (`location.imbalance`) the nodes where the percentages of requested cpu and memory
+/// differ more than `threshold` (in percentage points)
+pub fn detect_imbalances(resources: &mut [Resource], threshold: f64) {
+    let requested_pcts = make_qualifiers(
+        resources,
+        &[GroupBy::node, GroupBy::resource],
+        &ResourceFilter {
+            resource_names: vec!["cpu".to_string(), "memory".to_string()],
+            ..ResourceFilter::default()
+        },
+    )
+    .into_iter()
+    .filter_map(|(k, oqtys)| {
+        let qtys = oqtys?;
+        let pct = qtys
+            .requested
+            .unwrap_or_default()
+            .calc_percentage(qtys.allocatable.as_ref()?);
+        (k.len() == 2).then(|| ((k[0].clone(), k[1].clone()), pct))
+    })
+    .collect::<HashMap<_, _>>();
+    for resource in resources.iter_mut() {
+        let location = &mut resource.location;
+        if location.pod_name.is_some() {
+            continue;
+        }
+        if let Some(node_name) = &location.node_name {
+            let pct = |kind: &str| requested_pcts.get(&(node_name.clone(), kind.to_string()));
+            if let (Some(cpu), Some(memory)) = (pct("cpu"), pct("memory")) {
+                if (cpu - memory).abs() > threshold {
+                    location.imbalance = Some(format!(
+                        "⚠ CPU/memory imbalanced (CPU: {:.0}%, Mem: {:.0}%)",
+                        cpu, memory
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Pair the rows of `before` and `after` by key (sorted), a side is `None` when the row is missing
+pub fn diff_qualifiers(
+    before: &[(Vec<String>, Option<QtyByQualifier>)],
+    after: &[(Vec<String>, Option<QtyByQualifier>)],
+) -> Vec<(Vec<String>, Option<QtyByQualifier>, Option<QtyByQualifier>)> {
+    let mut rows: BTreeMap<Vec<String>, (Option<QtyByQualifier>, Option<QtyByQualifier>)> =
+        BTreeMap::new();
+    for (k, qtys) in before {
+        rows.entry(k.clone()).or_default().0 = qtys.clone();
+    }
+    for (k, qtys) in after {
+        rows.entry(k.clone()).or_default().1 = qtys.clone();
+    }
+    rows.into_iter()
+        .filter(|(_, (b, a))| b.is_some() || a.is_some())
+        .map(|(k, (b, a))| (k, b, a))
+        .collect()
+}
+
+/// The change from `before` to `after` as a percentage of `before` (eg: `+12%`),
+/// empty when unchanged or without `before`
+pub fn format_pct_change(before: &Option<Qty>, after: &Option<Qty>) -> String {
+    let before = before.as_ref().map(f64::from).unwrap_or(0.0);
+    let after = after.as_ref().map(f64::from).unwrap_or(0.0);
+    if before == 0.0 || before == after {
+        "".to_string()
+    } else {
+        format!("{:+.0}%", (after - before) / before * 100.0)
+    }
+}
+
+/// The change from `before` to `after` (eg: `+1.5Gi`, `-250.0m`, empty when unchanged),
+/// and its direction
+pub fn format_delta(before: &Option<Qty>, after: &Option<Qty>) -> (String, std::cmp::Ordering) {
+    let zero = Qty::default();
+    let before = before.as_ref().unwrap_or(&zero);
+    let after = after.as_ref().unwrap_or(&zero);
+    match after.cmp(before) {
+        std::cmp::Ordering::Equal => ("".to_string(), std::cmp::Ordering::Equal),
+        std::cmp::Ordering::Greater => (
+            format!("+{}", (after - before).adjust_scale()),
+            std::cmp::Ordering::Greater,
+        ),
+        std::cmp::Ordering::Less => (
+            format!("-{}", (before - after).adjust_scale()),
+            std::cmp::Ordering::Less,
+        ),
+    }
+}
+
+/// Summarize the resources accepted by `filter` (eg: held by terminating pods) under a
+/// virtual group `name`, added as child of every top-level group.
+pub fn make_virtual_group_qualifiers(
+    rsrcs: &[Resource],
+    group_by: &[GroupBy],
+    resource_filter: &ResourceFilter,
+    name: &str,
+    filter: fn(&Resource) -> bool,
+) -> Vec<(Vec<String>, Option<QtyByQualifier>)> {
+    let selected = rsrcs
+        .iter()
+        .filter(|r| filter(r))
+        .cloned()
+        .collect::<Vec<_>>();
+    make_qualifiers(
+        &selected,
+        &group_by[..group_by.len().min(1)],
+        resource_filter,
+    )
+    .into_iter()
+    .map(|(mut k, qtys)| {
+        k.push(name.to_string());
+        (k, qtys)
+    })
+    .collect()
+}
+
+/// Compute the values of the extra columns for every group (same keys as `make_qualifiers`),
+/// a value is only computed for the groups at the level of the column.
+pub fn make_extra_columns(
+    rsrcs: &[Resource],
+    group_by: &[GroupBy],
+    resource_filter: &ResourceFilter,
+    columns: &[ExtraColumn],
+) -> HashMap<Vec<String>, Vec<Option<String>>> {
+    if columns.is_empty() {
+        return HashMap::new();
+    }
+    let group_by_fct = group_by.iter().map(GroupBy::to_fct).collect::<Vec<_>>();
+    make_group_x(
+        &(rsrcs
+            .iter()
+            .filter(|a| resource_filter.accept(a))
+            .collect::<Vec<_>>()),
+        &[],
+        &group_by_fct,
+        0,
+        &|group, depth| {
+            columns
+                .iter()
+                .map(|c| {
+                    group_by
+                        .get(depth)
+                        .filter(|level| c.is_filled_at(level))
+                        .and_then(|level| c.compute(group, level))
+                })
+                .collect::<Vec<_>>()
+        },
+    )
+    .into_iter()
+    .collect()
+}
+
+fn make_group_x<T, F: Fn(&Resource) -> Option<String>>(
     rsrcs: &[&Resource],
     prefix: &[String],
-    group_by_fct: &[fn(&Resource) -> Option<String>],
+    group_by_fct: &[F],
     group_by_depth: usize,
-) -> Vec<(Vec<String>, Option<QtyByQualifier>)> {
+    aggregate: &dyn Fn(&[&Resource], usize) -> T,
+) -> Vec<(Vec<String>, T)> {
     // Note: The `&` is significant here, `GroupBy` is iterable
     // only by reference. You can also call `.into_iter()` explicitly.
     let mut out = vec![];
@@ -179,9 +754,14 @@ fn make_group_x_qualifier(
         {
             let mut key_full = prefix.to_vec();
             key_full.push(key);
-            let children =
-                make_group_x_qualifier(&group, &key_full, group_by_fct, group_by_depth + 1);
-            out.push((key_full, sum_by_qualifier(&group)));
+            let children = make_group_x(
+                &group,
+                &key_full,
+                group_by_fct,
+                group_by_depth + 1,
+                aggregate,
+            );
+            out.push((key_full, aggregate(&group, group_by_depth)));
             out.extend(children);
         }
     }
@@ -190,27 +770,361 @@ fn make_group_x_qualifier(
     out
 }
 
-fn accept_resource(name: &str, resource_filter: &[String]) -> bool {
-    resource_filter.is_empty() || resource_filter.iter().any(|x| name.contains(x))
+/// The name of the resource matches the filter: equal if `exact` or if the filter is a qualified
+/// name (with a `/`, eg: `nvidia.com/gpu`), contains it otherwise (eg: `gpu`)
+fn match_resource_name(name: &str, filter: &str, exact: bool) -> bool {
+    if exact || filter.contains('/') {
+        name == filter
+    } else {
+        name.contains(filter)
+    }
+}
+
+fn accept_resource(name: &str, resource_filter: &[String], exact: bool) -> bool {
+    resource_filter.is_empty()
+        || resource_filter
+            .iter()
+            .any(|x| match_resource_name(name, x, exact))
+}
+
+fn reject_resource(name: &str, exclude_filter: &[String], exact: bool) -> bool {
+    exclude_filter
+        .iter()
+        .any(|x| match_resource_name(name, x, exact))
+}
+
+/// A pattern to select namespaces: a name, a glob (`kube-*`) or a regex (starting with `^`
+/// or ending with `$`, eg: `^prod-`)
+#[derive(Debug, Clone)]
+pub struct NamespacePattern {
+    pattern: String,
+    regex: Regex,
+}
+
+impl NamespacePattern {
+    /// The name of the namespace, if the pattern is not a glob or a regex
+    pub fn name(&self) -> Option<&str> {
+        let is_name = self
+            .pattern
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+        is_name.then_some(self.pattern.as_str())
+    }
+
+    pub fn is_match(&self, namespace: &str) -> bool {
+        self.regex.is_match(namespace)
+    }
+
+    /// The namespace, if `patterns` selects a single namespace by its name
+    /// (allowing to call the API of this namespace only)
+    pub fn single_name(patterns: &[NamespacePattern]) -> Option<&str> {
+        match patterns {
+            [pattern] => pattern.name(),
+            _ => None,
+        }
+    }
+
+    /// `patterns` is empty (no filter) or one of them matches `namespace`
+    pub fn any_match(patterns: &[NamespacePattern], namespace: &str) -> bool {
+        patterns.is_empty() || patterns.iter().any(|p| p.is_match(namespace))
+    }
+}
+
+impl FromStr for NamespacePattern {
+    type Err = regex::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let regex = if s.starts_with('^') || s.ends_with('$') {
+            Regex::new(s)?
+        } else {
+            let glob = regex::escape(s).replace("\\*", ".*").replace("\\?", ".");
+            Regex::new(&format!("^{}$", glob))?
+        };
+        Ok(NamespacePattern {
+            pattern: s.to_string(),
+            regex,
+        })
+    }
+}
+
+/// A label selector (eg: `env=production,tier in (frontend,backend),!canary`), validated
+/// before being sent to the API server, to filter pods on the server side
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelSelector(String);
+
+impl LabelSelector {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// A label key: an optional prefix (a DNS subdomain) and `/`, then a name of 63 characters max
+    fn is_valid_key(key: &str) -> bool {
+        let (prefix, name) = match key.split_once('/') {
+            Some((prefix, name)) => (Some(prefix), name),
+            None => (None, key),
+        };
+        let is_dns_subdomain = |s: &str| {
+            s.len() <= 253
+                && s.split('.').all(|part| {
+                    !part.is_empty()
+                        && part
+                            .chars()
+                            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+                        && !part.starts_with('-')
+                        && !part.ends_with('-')
+                })
+        };
+        prefix.is_none_or(is_dns_subdomain) && !name.is_empty() && Self::is_valid_value(name)
+    }
+
+    /// A label value: empty or 63 alphanumeric characters max, with `-`, `_` or `.` between
+    fn is_valid_value(value: &str) -> bool {
+        value.len() <= 63
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+            && value
+                .chars()
+                .next()
+                .is_none_or(|c| c.is_ascii_alphanumeric())
+            && value
+                .chars()
+                .last()
+                .is_none_or(|c| c.is_ascii_alphanumeric())
+    }
+
+    fn check_requirement(requirement: &str) -> Result<(), String> {
+        let re = Regex::new(
+            r"^\s*(?:!\s*(?P<absent>[^\s=!(),]+)|(?P<key>[^\s=!(),]+)(?:\s*(?:==?|!=)\s*(?P<value>[^\s=!(),]*)|\s+(?:in|notin)\s*\((?P<values>[^()]*)\))?)\s*$",
+        )
+        .expect("valid regex");
+        let is_valid = re.captures(requirement).is_some_and(|caps| {
+            let key = caps.name("key").or_else(|| caps.name("absent"));
+            let values_ok = match (caps.name("value"), caps.name("values")) {
+                (Some(value), _) => Self::is_valid_value(value.as_str()),
+                (_, Some(values)) => values
+                    .as_str()
+                    .split(',')
+                    .all(|v| !v.trim().is_empty() && Self::is_valid_value(v.trim())),
+                _ => true,
+            };
+            key.is_some_and(|k| Self::is_valid_key(k.as_str())) && values_ok
+        });
+        if is_valid {
+            Ok(())
+        } else {
+            Err(format!(
+                "invalid requirement '{}', expected `key`, `!key`, `key=value`, `key!=value`, `key in (v1,v2)` or `key notin (v1,v2)`",
+                requirement.trim()
+            ))
+        }
+    }
+}
+
+/// A field selector of pods (eg: `status.phase=Running,spec.nodeName=node-1`), made of
+/// requirements `field=value`, `field==value` or `field!=value`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSelector {
+    /// (field, operator, value)
+    requirements: Vec<(String, String, String)>,
+}
+
+impl FieldSelector {
+    /// The field selector of the pods
+    pub fn for_pods(&self) -> String {
+        self.requirements
+            .iter()
+            .map(|(field, op, value)| format!("{}{}{}", field, op, value))
+            .join(",")
+    }
+
+    /// The field selector of the nodes: the requirements on `spec.nodeName` (on `metadata.name`),
+    /// as the other fields of pods are not supported by nodes
+    pub fn for_nodes(&self) -> Option<String> {
+        let selector = self
+            .requirements
+            .iter()
+            .filter(|(field, _, _)| field == "spec.nodeName")
+            .map(|(_, op, value)| format!("metadata.name{}{}", op, value))
+            .join(",");
+        (!selector.is_empty()).then_some(selector)
+    }
+}
+
+impl FromStr for FieldSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let re = Regex::new(
+            r"^\s*(?P<field>[A-Za-z0-9_.]+)\s*(?P<op>==|!=|=)\s*(?P<value>[^\s=!,]*)\s*$",
+        )
+        .expect("valid regex");
+        let requirements = s
+            .split(',')
+            .map(|requirement| {
+                re.captures(requirement)
+                    .map(|caps| {
+                        (
+                            caps["field"].to_string(),
+                            caps["op"].to_string(),
+                            caps["value"].to_string(),
+                        )
+                    })
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid requirement '{}', expected `field=value`, `field==value` or `field!=value`",
+                            requirement.trim()
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(FieldSelector { requirements })
+    }
+}
+
+impl FromStr for LabelSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err("empty label selector".to_string());
+        }
+        // split on the commas outside of the parentheses of `in (...)`
+        let mut depth = 0;
+        let mut start = 0;
+        let mut requirements = vec![];
+        for (i, c) in s.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    requirements.push(&s[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        requirements.push(&s[start..]);
+        for requirement in requirements {
+            Self::check_requirement(requirement)?;
+        }
+        Ok(LabelSelector(s.to_string()))
+    }
+}
+
+/// The filters of the resources to display (an empty filter accepts everything).
+/// The resources of nodes (without namespace) are not filtered by namespace.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceFilter {
+    /// accept resources whose name contains one of these
+    pub resource_names: Vec<String>,
+    /// reject resources whose name contains one of these (after `resource_names`)
+    pub exclude_resource_names: Vec<String>,
+    /// the names of `resource_names` and `exclude_resource_names` are matched exactly
+    /// (always the case for the qualified names, like `nvidia.com/gpu`)
+    pub exact_resource: bool,
+    pub namespaces: Vec<NamespacePattern>,
+    pub exclude_namespaces: Vec<String>,
+    /// accept resources located on a node whose name matches
+    pub node_regex: Option<Regex>,
+    /// reject resources of namespaces being terminated
+    pub exclude_terminating_namespaces: bool,
+}
+
+impl ResourceFilter {
+    pub fn accept(&self, resource: &Resource) -> bool {
+        let location = &resource.location;
+        let accept_namespace = |ns: &String| {
+            NamespacePattern::any_match(&self.namespaces, ns)
+                && !self.exclude_namespaces.contains(ns)
+        };
+        // the name of the API or the one displayed (eg: `ephemeral-storage` or `storage`)
+        let names = [resource.kind.as_str(), display_kind(&resource.kind)];
+        names
+            .iter()
+            .any(|name| accept_resource(name, &self.resource_names, self.exact_resource))
+            && !names.iter().any(|name| {
+                reject_resource(name, &self.exclude_resource_names, self.exact_resource)
+            })
+            && location.namespace.as_ref().is_none_or(accept_namespace)
+            && !(self.exclude_terminating_namespaces && location.namespace_terminating)
+            && self.node_regex.as_ref().is_none_or(|re| {
+                location
+                    .node_name
+                    .as_ref()
+                    .is_some_and(|name| re.is_match(name))
+            })
+    }
+}
+
+impl From<&CliOpts> for ResourceFilter {
+    fn from(cli_opts: &CliOpts) -> Self {
+        ResourceFilter {
+            resource_names: cli_opts.resource_name.clone(),
+            exclude_resource_names: cli_opts.exclude_resource_name.clone(),
+            exact_resource: cli_opts.exact_resource,
+            namespaces: cli_opts.namespace.clone(),
+            exclude_namespaces: cli_opts
+                .exclude_namespace
+                .iter()
+                .cloned()
+                .unique()
+                .collect(),
+            node_regex: cli_opts.node_filter_regex.clone(),
+            exclude_terminating_namespaces: cli_opts.exclude_terminating_namespaces,
+        }
+    }
 }
 
 #[instrument(skip(client, resources))]
 pub async fn collect_from_nodes(
     client: kube::Client,
     resources: &mut Vec<Resource>,
+    node_name: &Option<String>,
+    node_filter: &Option<Regex>,
+    field_selector: &Option<FieldSelector>,
+    node_selector: &Option<LabelSelector>,
+    label_keys: &[String],
 ) -> Result<(), Error> {
     let api_nodes: Api<Node> = Api::all(client);
-    let nodes = api_nodes
-        .list(&ListParams::default())
+    let fields = node_name
+        .iter()
+        .map(|name| format!("metadata.name={}", name))
+        .chain(field_selector.iter().filter_map(|f| f.for_nodes()))
+        .join(",");
+    let mut list_params = ListParams::default();
+    if !fields.is_empty() {
+        list_params = list_params.fields(&fields);
+    }
+    if let Some(selector) = node_selector {
+        list_params = list_params.labels(selector.as_str());
+    }
+    let mut nodes = api_nodes
+        .list(&list_params)
         .await
         .map_err(|source| Error::KubeError {
             context: "list nodes".to_string(),
             source,
         })?;
+    if let Some(re) = node_filter {
+        nodes.items.retain(|node| {
+            node.metadata
+                .name
+                .as_ref()
+                .map(|name| re.is_match(name))
+                .unwrap_or(false)
+        });
+    }
+    for node in nodes.items.iter_mut() {
+        retain_labels(&mut node.metadata, label_keys);
+    }
     extract_allocatable_from_nodes(nodes, resources).await?;
     Ok(())
 }
 
+/// The annotation of nodes with the policy of the CPU Manager (`none`, `static`)
+const CPU_MANAGER_POLICY_ANNOTATION: &str = "cpu-manager-policy";
+
 #[instrument(skip(node_list, resources))]
 pub async fn extract_allocatable_from_nodes(
     node_list: ObjectList<Node>,
@@ -219,19 +1133,55 @@ pub async fn extract_allocatable_from_nodes(
     for node in node_list.items {
         let location = Location {
             node_name: node.metadata.name,
+            kubelet_version: node
+                .status
+                .as_ref()
+                .and_then(|v| v.node_info.as_ref())
+                .map(|info| info.kubelet_version.clone()),
+            node_ip: node
+                .status
+                .as_ref()
+                .and_then(|v| v.addresses.as_ref())
+                .and_then(|addresses| addresses.iter().find(|a| a.type_ == "InternalIP"))
+                .map(|a| a.address.clone()),
+            cpu_manager_policy: node
+                .metadata
+                .annotations
+                .as_ref()
+                .and_then(|a| a.get(CPU_MANAGER_POLICY_ANNOTATION).cloned()),
+            labels: node.metadata.labels.clone().unwrap_or_default(),
             ..Location::default()
         };
-        if let Some(als) = node.status.and_then(|v| v.allocatable) {
+        let status = node.status.unwrap_or_default();
+        if let Some(als) = status.allocatable {
             // add_resource(resources, &location, ResourceUsage::Allocatable, &als)?
             for (kind, value) in als.iter() {
                 let quantity =
                     Qty::from_str(&(value).0).map_err(|source| Error::ResourceQtyParseError {
-                        location: location.clone(),
+                        location: Box::new(location.clone()),
                         qualifier: ResourceQualifier::Allocatable,
                         kind: kind.to_string(),
                         input: value.0.to_string(),
                         source,
                     })?;
+                // the capacity not allocatable is reserved for the system and kubernetes (kubelet,...)
+                if let Some(capacity) = status.capacity.as_ref().and_then(|c| c.get(kind)) {
+                    let capacity = Qty::from_str(&(capacity).0).map_err(|source| {
+                        Error::ResourceQtyParseError {
+                            location: Box::new(location.clone()),
+                            qualifier: ResourceQualifier::SystemReserved,
+                            kind: kind.to_string(),
+                            input: capacity.0.to_string(),
+                            source,
+                        }
+                    })?;
+                    resources.push(Resource {
+                        kind: kind.clone(),
+                        qualifier: ResourceQualifier::SystemReserved,
+                        quantity: (&capacity - &quantity).max(Qty::default()),
+                        location: location.clone(),
+                    });
+                }
                 resources.push(Resource {
                     kind: kind.clone(),
                     qualifier: ResourceQualifier::Allocatable,
@@ -277,30 +1227,168 @@ pub fn is_scheduled(pod: &Pod) -> bool {
         .unwrap_or(false)
 }
 
-fn push_resources(
-    resources: &mut Vec<Resource>,
-    location: &Location,
-    qualifier: ResourceQualifier,
-    resource_list: &BTreeMap<String, Qty>,
-) -> Result<(), Error> {
-    for (key, quantity) in resource_list.iter() {
-        resources.push(Resource {
-            kind: key.clone(),
-            qualifier: qualifier.clone(),
-            quantity: quantity.clone(),
-            location: location.clone(),
-        });
-    }
-    // add a "pods" resource as well
-    resources.push(Resource {
-        kind: "pods".to_string(),
-        qualifier,
+/// The pod is `Pending` and not yet scheduled on a node
+pub fn is_pending(pod: &Pod) -> bool {
+    pod.status
+        .as_ref()
+        .and_then(|ps| ps.phase.as_deref())
+        .is_some_and(|phase| phase == "Pending")
+        && !is_scheduled(pod)
+}
+
+/// The pod is controlled by an object of `kind` (eg: `DaemonSet`, `Job`)
+fn is_controlled_by(pod: &Pod, kind: &str) -> bool {
+    pod.metadata
+        .owner_references
+        .as_ref()
+        .map(|refs| {
+            refs.iter()
+                .any(|r| r.kind == kind && r.controller.unwrap_or(false))
+        })
+        .unwrap_or(false)
+}
+
+pub fn is_daemonset(pod: &Pod) -> bool {
+    is_controlled_by(pod, "DaemonSet")
+}
+
+pub fn is_job(pod: &Pod) -> bool {
+    is_controlled_by(pod, "Job")
+}
+
+/// The reference to the controller of an object (or to its first owner)
+fn controller_ref_of(
+    metadata: &kube::api::ObjectMeta,
+) -> Option<&k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference> {
+    let refs = metadata.owner_references.as_ref()?;
+    refs.iter()
+        .find(|r| r.controller.unwrap_or(false))
+        .or_else(|| refs.first())
+}
+
+/// The controller of an object (or its first owner), as `<kind>/<name>`
+fn controller_of(metadata: &kube::api::ObjectMeta) -> Option<String> {
+    controller_ref_of(metadata).map(|r| format!("{}/{}", r.kind, r.name))
+}
+
+/// The pod is being deleted (it can stay `Running` until the end of its grace period)
+pub fn is_terminating(pod: &Pod) -> bool {
+    pod.metadata.deletion_timestamp.is_some()
+}
+
+/// The controller of the pod (or its first owner), as `<kind>/<name>`
+pub fn owner_of(pod: &Pod) -> Option<String> {
+    controller_of(&pod.metadata)
+}
+
+/// The uid of the controller of the pod (or of its first owner), see `owner_of`
+pub fn owner_uid_of(pod: &Pod) -> Option<String> {
+    controller_ref_of(&pod.metadata).map(|r| r.uid.clone())
+}
+
+/// The labels identifying the tool managing a pod, when `app.kubernetes.io/managed-by` is not set
+const MANAGED_BY_LABELS: [(&str, &str); 3] = [
+    ("helm.sh/chart", "Helm"),
+    ("argocd.argoproj.io/instance", "ArgoCD"),
+    ("kustomize.toolkit.fluxcd.io/name", "Flux"),
+];
+
+/// The tool managing the pod, from its labels (`None` when unknown, then the kind of its
+/// controller is used, see `GroupBy::managed_by`)
+pub fn managed_by(pod: &Pod) -> Option<String> {
+    let labels = pod.metadata.labels.as_ref()?;
+    labels
+        .get("app.kubernetes.io/managed-by")
+        .cloned()
+        .or_else(|| {
+            MANAGED_BY_LABELS
+                .iter()
+                .find(|(key, _)| labels.contains_key(*key))
+                .map(|(_, tool)| tool.to_string())
+        })
+}
+
+/// Keep only the labels of `label_keys` (eg: to group by) and the ones read by `managed_by`,
+/// as the location (with the labels) is copied for every resource
+pub fn retain_labels(metadata: &mut kube::api::ObjectMeta, label_keys: &[String]) {
+    if let Some(labels) = metadata.labels.as_mut() {
+        labels.retain(|k, _| {
+            label_keys.contains(k)
+                || k == "app.kubernetes.io/managed-by"
+                || MANAGED_BY_LABELS.iter().any(|(key, _)| key == k)
+        });
+    }
+}
+
+/// A pod is being evicted when it's deleted with an eviction condition
+pub fn is_evicting(pod: &Pod) -> bool {
+    is_terminating(pod)
+        && pod
+            .status
+            .as_ref()
+            .and_then(|s| s.conditions.as_ref())
+            .map(|conditions| {
+                conditions.iter().any(|c| {
+                    c.reason.as_deref() == Some("Evicting")
+                        || (c.type_ == "DisruptionTarget" && c.status == "True")
+                })
+            })
+            .unwrap_or(false)
+}
+
+fn push_quantities(
+    resources: &mut Vec<Resource>,
+    location: &Location,
+    qualifier: &ResourceQualifier,
+    resource_list: &BTreeMap<String, Qty>,
+) {
+    for (key, quantity) in resource_list.iter() {
+        resources.push(Resource {
+            kind: key.clone(),
+            qualifier: qualifier.clone(),
+            quantity: quantity.clone(),
+            location: location.clone(),
+        });
+    }
+}
+
+fn push_resources(
+    resources: &mut Vec<Resource>,
+    location: &Location,
+    qualifier: ResourceQualifier,
+    resource_list: &BTreeMap<String, Qty>,
+) -> Result<(), Error> {
+    push_quantities(resources, location, &qualifier, resource_list);
+    // add a "pods" resource as well
+    resources.push(Resource {
+        kind: "pods".to_string(),
+        qualifier,
         quantity: Qty::from_str("1")?,
         location: location.clone(),
     });
     Ok(())
 }
 
+/// The part of `total` not held by the `parts` (zero quantities are removed)
+fn remaining_resources<'a>(
+    total: &BTreeMap<String, Qty>,
+    parts: impl Iterator<Item = &'a BTreeMap<String, Qty>>,
+) -> BTreeMap<String, Qty> {
+    let mut remaining = total.clone();
+    for part in parts {
+        for (key, quantity) in part.iter() {
+            if let Some(current_quantity) = remaining.get_mut(key) {
+                *current_quantity = &*current_quantity - quantity;
+            }
+        }
+    }
+    remaining.retain(|_, quantity| !quantity.is_zero());
+    remaining
+}
+
+/// Merge the quantities of `resource_list` into `effective_resources` (by resource name) with `op`,
+/// any binary operation on `Qty` (eg: `Add::add` to sum containers, `cmp::max` for init containers).
+/// A resource absent of `effective_resources` is inserted as is.
 fn process_resources<F>(
     effective_resources: &mut BTreeMap<String, Qty>,
     resource_list: &BTreeMap<String, k8s_openapi::apimachinery::pkg::api::resource::Quantity>,
@@ -320,57 +1408,350 @@ where
     Ok(())
 }
 
+/// `process_resources` summing the quantities (containers running side by side)
+pub fn process_resources_additive(
+    effective_resources: &mut BTreeMap<String, Qty>,
+    resource_list: &BTreeMap<String, k8s_openapi::apimachinery::pkg::api::resource::Quantity>,
+) -> Result<(), Error> {
+    process_resources(effective_resources, resource_list, std::ops::Add::add)
+}
+
+/// `process_resources` keeping the highest quantities (init containers running one after the other)
+pub fn process_resources_max(
+    effective_resources: &mut BTreeMap<String, Qty>,
+    resource_list: &BTreeMap<String, k8s_openapi::apimachinery::pkg::api::resource::Quantity>,
+) -> Result<(), Error> {
+    process_resources(effective_resources, resource_list, std::cmp::max)
+}
+
 #[instrument(skip(client, resources))]
+#[allow(clippy::too_many_arguments)]
 pub async fn collect_from_pods(
     client: kube::Client,
     resources: &mut Vec<Resource>,
-    namespace: &Option<String>,
-) -> Result<(), Error> {
-    let api_pods: Api<Pod> = if let Some(ns) = namespace {
+    namespaces: &[NamespacePattern],
+    node_name: &Option<String>,
+    node_filter: &Option<Regex>,
+    label_selector: &Option<LabelSelector>,
+    field_selector: &Option<FieldSelector>,
+    annotation_keys: &[String],
+    label_keys: &[String],
+    include_pending: bool,
+    include_terminating: bool,
+    split_containers: bool,
+) -> Result<HashSet<(String, String)>, Error> {
+    let api_pods: Api<Pod> = if let Some(ns) = NamespacePattern::single_name(namespaces) {
         Api::namespaced(client, ns)
     } else {
         Api::all(client)
     };
-    let pods = api_pods
-        .list(&ListParams::default())
+    // filter on the server side, to reduce the data transfer on large cluster
+    let fields = node_name
+        .iter()
+        .map(|name| format!("spec.nodeName={}", name))
+        .chain(field_selector.iter().map(|f| f.for_pods()))
+        .join(",");
+    let mut list_params = ListParams::default();
+    if !fields.is_empty() {
+        list_params = list_params.fields(&fields);
+    }
+    if let Some(selector) = label_selector {
+        list_params = list_params.labels(selector.as_str());
+    }
+    let mut pods = api_pods
+        .list(&list_params)
         .await
         .map_err(|source| Error::KubeError {
             context: "list pods".to_string(),
             source,
         })?;
-    extract_allocatable_from_pods(pods, resources).await?;
+    if let Some(re) = node_filter {
+        pods.items.retain(|pod| {
+            pod.spec
+                .as_ref()
+                .and_then(|spec| spec.node_name.as_ref())
+                .map(|name| re.is_match(name))
+                .unwrap_or(false)
+        });
+    }
+    pods.items.retain(|pod| {
+        NamespacePattern::any_match(
+            namespaces,
+            pod.metadata.namespace.as_deref().unwrap_or_default(),
+        )
+    });
+    // only keep the annotations used (eg: to group by), the location is copied for every resource
+    for pod in pods.items.iter_mut() {
+        if let Some(annotations) = pod.metadata.annotations.as_mut() {
+            annotations.retain(|k, _| annotation_keys.contains(k));
+        }
+        retain_labels(&mut pod.metadata, label_keys);
+    }
+    extract_allocatable_from_pods(
+        pods,
+        resources,
+        include_pending,
+        include_terminating,
+        split_containers,
+    )
+    .await
+}
+
+/// The owners (`<kind>/<name>` and uid) of the intermediate objects (ReplicaSets, Jobs),
+/// by namespace and uid of the object (as in the `ownerReferences` of the owned objects)
+pub type OwnerCache = HashMap<(String, String), (String, String)>;
+
+/// Replace the owners of the pods by their top-level owner, through the ReplicaSets
+/// (eg: `Deployment/<name>`) and the Jobs (eg: `CronJob/<name>`), listed once each
+/// (so 2 requests whatever the number of pods). The `OwnerCache` is rebuilt on each call,
+/// so on each refresh of `--watch`.
+#[instrument(skip(client, resources))]
+pub async fn resolve_owners(
+    client: kube::Client,
+    resources: &mut [Resource],
+    namespaces: &[NamespacePattern],
+) -> Result<(), Error> {
+    let (api_replicasets, api_jobs): (Api<ReplicaSet>, Api<Job>) =
+        match NamespacePattern::single_name(namespaces) {
+            Some(ns) => (
+                Api::namespaced(client.clone(), ns),
+                Api::namespaced(client, ns),
+            ),
+            None => (Api::all(client.clone()), Api::all(client)),
+        };
+    let replicasets = api_replicasets
+        .list(&ListParams::default())
+        .await
+        .map_err(|source| Error::KubeError {
+            context: "list replicasets".to_string(),
+            source,
+        })?;
+    let jobs = api_jobs
+        .list(&ListParams::default())
+        .await
+        .map_err(|source| Error::KubeError {
+            context: "list jobs".to_string(),
+            source,
+        })?;
+    let mut owners = owners_by_object(replicasets.items.iter().map(|o| &o.metadata));
+    owners.extend(owners_by_object(jobs.items.iter().map(|o| &o.metadata)));
+    resolve_owners_with(resources, &owners);
     Ok(())
 }
 
+/// The owners of the objects
+pub fn owners_by_object<'a>(
+    metadatas: impl Iterator<Item = &'a kube::api::ObjectMeta>,
+) -> OwnerCache {
+    metadatas
+        .filter_map(|metadata| {
+            let owner = controller_ref_of(metadata)?;
+            Some((
+                (
+                    metadata.namespace.clone().unwrap_or_default(),
+                    metadata.uid.clone()?,
+                ),
+                (format!("{}/{}", owner.kind, owner.name), owner.uid.clone()),
+            ))
+        })
+        .collect()
+}
+
+/// Replace the owners of the resources by the last owner of the chain found into `owners`
+pub fn resolve_owners_with(resources: &mut [Resource], owners: &OwnerCache) {
+    for resource in resources.iter_mut() {
+        let location = &mut resource.location;
+        let namespace = location.namespace.clone().unwrap_or_default();
+        // bounded, in case of a cycle of owners
+        for _ in 0..owners.len() {
+            match location
+                .owner_uid
+                .as_ref()
+                .and_then(|uid| owners.get(&(namespace.clone(), uid.clone())))
+            {
+                Some((name, uid)) => {
+                    location.owner_name = Some(name.clone());
+                    location.owner_uid = Some(uid.clone());
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// The resources with a limit lower than the request
+fn limits_under_requests(
+    requests: &BTreeMap<String, Qty>,
+    limits: &BTreeMap<String, Qty>,
+) -> Vec<String> {
+    limits
+        .iter()
+        .filter(|(key, limit)| requests.get(*key).is_some_and(|request| *limit < request))
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+/// The CPUs pinned (requested by containers with exclusive CPUs) and shared (the rest of the allocatable)
+/// of the nodes of the group, when their CPU Manager has the `static` policy
+fn cpu_pinning(group: &[&Resource]) -> Option<String> {
+    let policy = group
+        .iter()
+        .find_map(|r| r.location.cpu_manager_policy.as_deref())?;
+    if policy != "static" {
+        return None;
+    }
+    let cpus = group
+        .iter()
+        .filter(|r| r.kind == "cpu")
+        .copied()
+        .collect::<Vec<_>>();
+    let pinned = cpus
+        .iter()
+        .filter(|r| r.location.exclusive_cpu && matches!(r.qualifier, ResourceQualifier::Requested))
+        .fold(Qty::default(), |acc, r| &acc + &r.quantity);
+    let allocatable = sum_by_qualifier(&cpus)?.allocatable?;
+    Some(format!(
+        "{} pinned / {} shared",
+        pinned.adjust_scale(),
+        (&allocatable - &pinned).adjust_scale()
+    ))
+}
+
+/// The ratio requested / allocatable of the ephemeral storage (of a node),
+/// `None` without ephemeral storage allocatable
+fn ephemeral_storage_ratio(group: &[&Resource]) -> Option<f64> {
+    let storages = group
+        .iter()
+        .filter(|r| r.kind == "ephemeral-storage")
+        .copied()
+        .collect::<Vec<_>>();
+    let qtys = sum_by_qualifier(&storages)?;
+    let allocatable = qtys.allocatable.filter(|a| !a.is_zero())?;
+    let requested = qtys.requested.unwrap_or_default();
+    Some(f64::from(&requested) / f64::from(&allocatable))
+}
+
+/// The tiers of limits (name, max cpu, max memory), from the smallest
+const LIMIT_TIERS: [(&str, &str, &str); 4] = [
+    ("nano", "100m", "128Mi"),
+    ("micro", "500m", "512Mi"),
+    ("small", "1", "1Gi"),
+    ("medium", "4", "4Gi"),
+];
+
+/// The size class of a container from its limits: the first tier of `LIMIT_TIERS` with both
+/// the cpu and the memory under its maximums, `large` above, `unlimited` without cpu or memory limit
+fn limit_tier(limits: &BTreeMap<String, Qty>) -> &'static str {
+    let (cpu, memory) = match (limits.get("cpu"), limits.get("memory")) {
+        (Some(cpu), Some(memory)) => (cpu, memory),
+        _ => return "unlimited",
+    };
+    LIMIT_TIERS
+        .iter()
+        .find(|(_, max_cpu, max_memory)| {
+            cpu <= &Qty::from_str(max_cpu).expect("valid cpu")
+                && memory <= &Qty::from_str(max_memory).expect("valid memory")
+        })
+        .map(|(name, _, _)| *name)
+        .unwrap_or("large")
+}
+
+/// The conditions of the pod with the status `False` (as `<type>=False`)
+fn failing_conditions(pod: &Pod) -> Vec<String> {
+    pod.status
+        .as_ref()
+        .and_then(|s| s.conditions.as_ref())
+        .map(|conditions| {
+            conditions
+                .iter()
+                .filter(|c| c.status == "False")
+                .map(|c| format!("{}=False", c.type_))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 #[instrument(skip(pod_list, resources))]
+/// Extract the resources of the scheduled pods (and of the pending ones if `include_pending`),
+/// except the terminating ones (unless `include_terminating`), by container if `split_containers`.
+/// Returns the (namespace, name) of the terminating pods skipped, to skip their metrics too.
 pub async fn extract_allocatable_from_pods(
     pod_list: ObjectList<Pod>,
     resources: &mut Vec<Resource>,
-) -> Result<(), Error> {
-    for pod in pod_list.items.into_iter().filter(is_scheduled) {
+    include_pending: bool,
+    include_terminating: bool,
+    split_containers: bool,
+) -> Result<HashSet<(String, String)>, Error> {
+    let (terminating, pods): (Vec<_>, Vec<_>) = pod_list
+        .items
+        .into_iter()
+        .filter(|pod| is_scheduled(pod) || (include_pending && is_pending(pod)))
+        .partition(|pod| !include_terminating && is_terminating(pod));
+    let skipped = terminating
+        .iter()
+        .map(|pod| {
+            (
+                pod.metadata.namespace.clone().unwrap_or_default(),
+                pod.metadata.name.clone().unwrap_or_default(),
+            )
+        })
+        .collect();
+    for pod in pods {
         let spec = pod.spec.as_ref();
         let node_name = spec.and_then(|s| s.node_name.clone());
         let metadata = &pod.metadata;
-        let location = Location {
+        let mut location = Location {
             node_name: node_name.clone(),
             namespace: metadata.namespace.clone(),
             pod_name: metadata.name.clone(),
+            is_daemonset: is_daemonset(&pod),
+            is_job: is_job(&pod),
+            pending: is_pending(&pod),
+            owner_name: owner_of(&pod),
+            owner_uid: owner_uid_of(&pod),
+            terminating: is_terminating(&pod),
+            restart_count: pod
+                .status
+                .as_ref()
+                .and_then(|s| s.container_statuses.as_ref())
+                .map(|statuses| {
+                    statuses
+                        .iter()
+                        .map(|cs| cs.restart_count.max(0) as u32)
+                        .sum()
+                })
+                .unwrap_or(0),
+            pod_ip: pod.status.as_ref().and_then(|s| s.pod_ip.clone()),
+            runtime_class: pod.spec.as_ref().and_then(|s| s.runtime_class_name.clone()),
+            managed_by: managed_by(&pod),
+            resource_version: metadata.resource_version.clone(),
+            evicting: is_evicting(&pod),
+            failing_conditions: failing_conditions(&pod),
+            annotations: metadata.annotations.clone().unwrap_or_default(),
+            labels: metadata.labels.clone().unwrap_or_default(),
+            ..Location::default()
         };
         // compute the effective resource qualifier
         // see https://kubernetes.io/docs/concepts/workloads/pods/init-containers/#resources
         let mut resource_requests: BTreeMap<String, Qty> = BTreeMap::new();
         let mut resource_limits: BTreeMap<String, Qty> = BTreeMap::new();
-        // handle regular containers
+        // handle regular containers (and keep the resources of each of them)
+        let mut containers_resources = vec![];
         let containers = spec.map(|s| s.containers.clone()).unwrap_or_default();
         for container in containers.into_iter() {
+            let mut container_requests: BTreeMap<String, Qty> = BTreeMap::new();
+            let mut container_limits: BTreeMap<String, Qty> = BTreeMap::new();
             if let Some(requirements) = container.resources {
                 if let Some(r) = requirements.requests {
-                    process_resources(&mut resource_requests, &r, std::ops::Add::add)?
+                    process_resources_additive(&mut resource_requests, &r)?;
+                    process_resources_additive(&mut container_requests, &r)?;
                 }
                 if let Some(r) = requirements.limits {
-                    process_resources(&mut resource_limits, &r, std::ops::Add::add)?;
+                    process_resources_additive(&mut resource_limits, &r)?;
+                    process_resources_additive(&mut container_limits, &r)?;
                 }
             }
+            containers_resources.push((container.name, container_requests, container_limits));
         }
         // handle initContainers
         let init_containers = spec
@@ -379,50 +1760,322 @@ pub async fn extract_allocatable_from_pods(
         for container in init_containers.into_iter() {
             if let Some(requirements) = container.resources {
                 if let Some(r) = requirements.requests {
-                    process_resources(&mut resource_requests, &r, std::cmp::max)?;
+                    process_resources_max(&mut resource_requests, &r)?;
                 }
                 if let Some(r) = requirements.limits {
-                    process_resources(&mut resource_limits, &r, std::cmp::max)?;
+                    process_resources_max(&mut resource_limits, &r)?;
                 }
             }
         }
         // handler overhead (add to both requests and limits)
         if let Some(ref overhead) = spec.and_then(|s| s.overhead.clone()) {
-            process_resources(&mut resource_requests, overhead, std::ops::Add::add)?;
-            process_resources(&mut resource_limits, overhead, std::ops::Add::add)?;
+            process_resources_additive(&mut resource_requests, overhead)?;
+            process_resources_additive(&mut resource_limits, overhead)?;
+        }
+        location.missing_requests = resource_requests.is_empty();
+        let invalid_limits = containers_resources
+            .iter()
+            .flat_map(|(_, requests, limits)| limits_under_requests(requests, limits))
+            .unique()
+            .collect::<Vec<_>>();
+        if !invalid_limits.is_empty() {
+            warn!(
+                namespace = location.namespace.as_deref().unwrap_or_default(),
+                pod = location.pod_name.as_deref().unwrap_or_default(),
+                resources = invalid_limits.join(",").as_str(),
+                "limit lower than request"
+            );
+            location.has_invalid_limits = true;
+        }
+        if !split_containers {
+            push_resources(
+                resources,
+                &location,
+                ResourceQualifier::Requested,
+                &resource_requests,
+            )?;
+            push_resources(
+                resources,
+                &location,
+                ResourceQualifier::Limit,
+                &resource_limits,
+            )?;
+            continue;
+        }
+        // push these onto resources: the part of every container, then the remaining part
+        // of the pod (initContainers, overhead) without container
+        let guaranteed = pod
+            .status
+            .as_ref()
+            .and_then(|s| s.qos_class.as_deref())
+            .is_some_and(|qos| qos == "Guaranteed");
+        for (container_name, container_requests, container_limits) in &containers_resources {
+            let container_location = Location {
+                container_name: Some(container_name.clone()),
+                exclusive_cpu: guaranteed
+                    && container_requests
+                        .get("cpu")
+                        .is_some_and(|cpu| !cpu.is_zero() && f64::from(cpu).fract() == 0.0),
+                limit_tier: Some(limit_tier(container_limits).to_string()),
+                ..location.clone()
+            };
+            push_quantities(
+                resources,
+                &container_location,
+                &ResourceQualifier::Requested,
+                container_requests,
+            );
+            push_quantities(
+                resources,
+                &container_location,
+                &ResourceQualifier::Limit,
+                container_limits,
+            );
         }
-        // push these onto resources
         push_resources(
             resources,
             &location,
             ResourceQualifier::Requested,
-            &resource_requests,
+            &remaining_resources(
+                &resource_requests,
+                containers_resources.iter().map(|(_, requests, _)| requests),
+            ),
         )?;
         push_resources(
             resources,
             &location,
             ResourceQualifier::Limit,
-            &resource_limits,
+            &remaining_resources(
+                &resource_limits,
+                containers_resources.iter().map(|(_, _, limits)| limits),
+            ),
         )?;
     }
+    Ok(skipped)
+}
+
+/// The kind of resource limited by a key of a ResourceQuota: `requests.cpu` and `cpu`
+/// limit the requested `cpu`, other keys (`limits.cpu`, `count/jobs.batch`,...) are kept as is
+fn quota_kind(key: &str) -> &str {
+    match key {
+        // the storage requested by the PersistentVolumeClaims: `storage` is the name displayed
+        // for `ephemeral-storage` (see `display_kind`), the quota would be shown on its rows
+        "requests.storage" => "pvc-storage",
+        _ => key.strip_prefix("requests.").unwrap_or(key),
+    }
+}
+
+#[instrument(skip(client, resources))]
+pub async fn collect_from_resource_quotas(
+    client: kube::Client,
+    resources: &mut Vec<Resource>,
+    namespaces: &[NamespacePattern],
+) -> Result<(), Error> {
+    let api_quotas: Api<ResourceQuota> = if let Some(ns) = NamespacePattern::single_name(namespaces)
+    {
+        Api::namespaced(client, ns)
+    } else {
+        Api::all(client)
+    };
+    let mut quotas = api_quotas
+        .list(&ListParams::default())
+        .await
+        .map_err(|source| Error::KubeError {
+            context: "list resourcequotas".to_string(),
+            source,
+        })?;
+    quotas.items.retain(|quota| {
+        NamespacePattern::any_match(
+            namespaces,
+            quota.metadata.namespace.as_deref().unwrap_or_default(),
+        )
+    });
+    extract_from_resource_quotas(quotas, resources)
+}
+
+pub fn extract_from_resource_quotas(
+    quota_list: ObjectList<ResourceQuota>,
+    resources: &mut Vec<Resource>,
+) -> Result<(), Error> {
+    for quota in quota_list.items {
+        let location = Location {
+            namespace: quota.metadata.namespace.clone(),
+            ..Location::default()
+        };
+        let status = quota.status.unwrap_or_default();
+        for (qualifier, values) in [
+            (ResourceQualifier::QuotaHard, status.hard),
+            (ResourceQualifier::QuotaUsed, status.used),
+        ] {
+            for (key, value) in values.unwrap_or_default() {
+                let quantity =
+                    Qty::from_str(&value.0).map_err(|source| Error::ResourceQtyParseError {
+                        location: Box::new(location.clone()),
+                        qualifier: qualifier.clone(),
+                        kind: key.clone(),
+                        input: value.0.clone(),
+                        source,
+                    })?;
+                resources.push(Resource {
+                    kind: quota_kind(&key).to_string(),
+                    qualifier: qualifier.clone(),
+                    quantity,
+                    location: location.clone(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[instrument(skip(client, resources))]
+pub async fn collect_from_limit_ranges(
+    client: kube::Client,
+    resources: &mut Vec<Resource>,
+    namespaces: &[NamespacePattern],
+) -> Result<(), Error> {
+    let api_limit_ranges: Api<LimitRange> =
+        if let Some(ns) = NamespacePattern::single_name(namespaces) {
+            Api::namespaced(client, ns)
+        } else {
+            Api::all(client)
+        };
+    let mut limit_ranges = api_limit_ranges
+        .list(&ListParams::default())
+        .await
+        .map_err(|source| Error::KubeError {
+            context: "list limitranges".to_string(),
+            source,
+        })?;
+    limit_ranges.items.retain(|limit_range| {
+        NamespacePattern::any_match(
+            namespaces,
+            limit_range
+                .metadata
+                .namespace
+                .as_deref()
+                .unwrap_or_default(),
+        )
+    });
+    extract_from_limit_ranges(limit_ranges, resources)
+}
+
+/// Push the defaults (limit & request) of the `Container` entries of the LimitRanges
+pub fn extract_from_limit_ranges(
+    limit_range_list: ObjectList<LimitRange>,
+    resources: &mut Vec<Resource>,
+) -> Result<(), Error> {
+    for limit_range in limit_range_list.items {
+        let location = Location {
+            namespace: limit_range.metadata.namespace.clone(),
+            ..Location::default()
+        };
+        let items = limit_range.spec.map(|spec| spec.limits).unwrap_or_default();
+        for item in items.into_iter().filter(|item| item.type_ == "Container") {
+            for (qualifier, values) in [
+                (ResourceQualifier::DefaultLimit, item.default),
+                (ResourceQualifier::DefaultRequest, item.default_request),
+            ] {
+                for (key, value) in values.unwrap_or_default() {
+                    let quantity =
+                        Qty::from_str(&value.0).map_err(|source| Error::ResourceQtyParseError {
+                            location: Box::new(location.clone()),
+                            qualifier: qualifier.clone(),
+                            kind: key.clone(),
+                            input: value.0.clone(),
+                            source,
+                        })?;
+                    resources.push(Resource {
+                        kind: key,
+                        qualifier: qualifier.clone(),
+                        quantity,
+                        location: location.clone(),
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The label of the namespaces with the level enforced by the Pod Security Admission
+pub const PSA_ENFORCE_LABEL: &str = "pod-security.kubernetes.io/enforce";
+
+/// Set the labels (only the ones of `label_keys`) and the phase (terminating or not)
+/// of their namespace on the resources
+#[instrument(skip(client, resources))]
+pub async fn collect_from_namespaces(
+    client: kube::Client,
+    resources: &mut [Resource],
+    namespaces: &[NamespacePattern],
+    label_keys: &[String],
+) -> Result<(), Error> {
+    let api_namespaces: Api<Namespace> = Api::all(client);
+    // get only the namespace if single (listing all namespaces could be forbidden)
+    let namespaces = match NamespacePattern::single_name(namespaces) {
+        Some(ns) => api_namespaces.get(ns).await.map(|ns| vec![ns]),
+        None => api_namespaces
+            .list(&ListParams::default())
+            .await
+            .map(|list| list.items),
+    }
+    .map_err(|source| Error::KubeError {
+        context: "list namespaces".to_string(),
+        source,
+    })?;
+    let infos_by_namespace = namespaces
+        .into_iter()
+        .filter_map(|ns| {
+            let terminating = ns
+                .status
+                .as_ref()
+                .and_then(|s| s.phase.as_deref())
+                .is_some_and(|phase| phase == "Terminating");
+            let labels = ns
+                .metadata
+                .labels
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|(k, _)| label_keys.contains(k))
+                .collect::<BTreeMap<_, _>>();
+            ns.metadata.name.map(|name| (name, (labels, terminating)))
+        })
+        .collect::<HashMap<_, _>>();
+    for resource in resources.iter_mut() {
+        let location = &mut resource.location;
+        if let Some((labels, terminating)) = location
+            .namespace
+            .as_ref()
+            .and_then(|ns| infos_by_namespace.get(ns))
+        {
+            location.namespace_labels = labels.clone();
+            location.namespace_terminating = *terminating;
+        }
+    }
     Ok(())
 }
 
 pub fn extract_locations(
     resources: &[Resource],
-) -> std::collections::HashMap<(String, String), Location> {
-    resources
-        .iter()
-        .filter_map(|resource| {
-            let loc = &resource.location;
-            loc.pod_name.as_ref().map(|n| {
-                (
-                    (loc.namespace.clone().unwrap_or_default(), n.to_owned()),
-                    loc.clone(),
-                )
-            })
-        })
-        .collect()
+) -> Result<std::collections::HashMap<(String, String), Location>, Error> {
+    let mut locations = std::collections::HashMap::new();
+    for loc in resources.iter().map(|resource| &resource.location) {
+        if let Some(n) = &loc.pod_name {
+            let key = (loc.namespace.clone().unwrap_or_default(), n.to_owned());
+            // the location of the pod, not of one of its containers
+            let loc = Location {
+                container_name: None,
+                limit_tier: None,
+                ..loc.clone()
+            };
+            let merged = match locations.get(&key) {
+                Some(existing) => loc.merge(existing)?,
+                None => loc,
+            };
+            locations.insert(key, merged);
+        }
+    }
+    Ok(locations)
 }
 
 //TODO need location of pods (aka node because its not part of metrics)
@@ -430,34 +2083,48 @@ pub fn extract_locations(
 pub async fn collect_from_metrics(
     client: kube::Client,
     resources: &mut Vec<Resource>,
+    label_selector: &Option<LabelSelector>,
+    skipped_pods: &HashSet<(String, String)>,
 ) -> Result<(), Error> {
     let api_pod_metrics: Api<metrics::PodMetrics> = Api::all(client);
-    let pod_metrics = api_pod_metrics
-        .list(&ListParams::default())
-        .await
-        .map_err(|source| Error::KubeError {
-            context: "list podmetrics, maybe Metrics API not available".to_string(),
-            source,
-        })?;
+    // the metrics of the pods filtered out (by labels) are not collected
+    let mut list_params = ListParams::default();
+    if let Some(selector) = label_selector {
+        list_params = list_params.labels(selector.as_str());
+    }
+    let pod_metrics =
+        api_pod_metrics
+            .list(&list_params)
+            .await
+            .map_err(|source| Error::KubeError {
+                context: "list podmetrics, maybe Metrics API not available".to_string(),
+                source,
+            })?;
 
-    extract_utilizations_from_pod_metrics(pod_metrics, resources).await?;
+    extract_utilizations_from_pod_metrics(pod_metrics, resources, skipped_pods).await?;
     Ok(())
 }
 
-#[instrument(skip(pod_metrics, resources))]
+/// Extract the utilizations of the pods, except of `skipped_pods` (eg: the terminating ones
+/// excluded from the requests) that would be reported as pods without node
+#[instrument(skip(pod_metrics, resources, skipped_pods))]
 pub async fn extract_utilizations_from_pod_metrics(
     pod_metrics: ObjectList<metrics::PodMetrics>,
     resources: &mut Vec<Resource>,
+    skipped_pods: &HashSet<(String, String)>,
 ) -> Result<(), Error> {
     let cpu_kind = "cpu";
     let memory_kind = "memory";
-    let locations = extract_locations(resources);
+    let locations = extract_locations(resources)?;
     for pod_metric in pod_metrics.items {
         let metadata = &pod_metric.metadata;
         let key = (
             metadata.namespace.clone().unwrap_or_default(),
             metadata.name.clone().unwrap_or_default(),
         );
+        if skipped_pods.contains(&key) {
+            continue;
+        }
         let location = locations.get(&key).cloned().unwrap_or_else(|| Location {
             // node_name: node_name.clone(),
             namespace: metadata.namespace.clone(),
@@ -469,7 +2136,7 @@ pub async fn extract_utilizations_from_pod_metrics(
         for container in pod_metric.containers.into_iter() {
             cpu_utilization += &Qty::from_str(&container.usage.cpu)
                 .map_err(|source| Error::ResourceQtyParseError {
-                    location: location.clone(),
+                    location: Box::new(location.clone()),
                     qualifier: ResourceQualifier::Utilization,
                     kind: cpu_kind.to_string(),
                     input: container.usage.cpu.clone(),
@@ -478,7 +2145,7 @@ pub async fn extract_utilizations_from_pod_metrics(
                 .max(Qty::lowest_positive());
             memory_utilization += &Qty::from_str(&container.usage.memory)
                 .map_err(|source| Error::ResourceQtyParseError {
-                    location: location.clone(),
+                    location: Box::new(location.clone()),
                     qualifier: ResourceQualifier::Utilization,
                     kind: memory_kind.to_string(),
                     input: container.usage.memory.clone(),
@@ -502,27 +2169,115 @@ pub async fn extract_utilizations_from_pod_metrics(
     Ok(())
 }
 
-#[derive(Debug, Eq, PartialEq, ArgEnum, Clone)]
-#[allow(non_camel_case_types)]
-pub enum GroupBy {
+#[instrument(skip(client, resources))]
+pub async fn collect_node_metrics(
+    client: kube::Client,
+    resources: &mut Vec<Resource>,
+) -> Result<(), Error> {
+    let api_node_metrics: Api<metrics::NodeMetrics> = Api::all(client);
+    let node_metrics = api_node_metrics
+        .list(&ListParams::default())
+        .await
+        .map_err(|source| Error::KubeError {
+            context: "list nodemetrics, maybe Metrics API not available".to_string(),
+            source,
+        })?;
+
+    extract_utilizations_from_node_metrics(node_metrics, resources).await?;
+    Ok(())
+}
+
+#[instrument(skip(node_metrics, resources))]
+pub async fn extract_utilizations_from_node_metrics(
+    node_metrics: ObjectList<metrics::NodeMetrics>,
+    resources: &mut Vec<Resource>,
+) -> Result<(), Error> {
+    for node_metric in node_metrics.items {
+        let node_name = node_metric.metadata.name.clone();
+        // reuse the location of the node (labels,...) when its resources are already collected
+        let location = resources
+            .iter()
+            .find(|r| r.location.pod_name.is_none() && r.location.node_name == node_name)
+            .map(|r| r.location.clone())
+            .unwrap_or_else(|| Location {
+                node_name: node_name.clone(),
+                ..Location::default()
+            });
+        for (kind, input) in [
+            ("cpu", &node_metric.usage.cpu),
+            ("memory", &node_metric.usage.memory),
+        ] {
+            let quantity = Qty::from_str(input).map_err(|source| Error::ResourceQtyParseError {
+                location: Box::new(location.clone()),
+                qualifier: ResourceQualifier::Utilization,
+                kind: kind.to_string(),
+                input: input.clone(),
+                source,
+            })?;
+            resources.push(Resource {
+                kind: kind.to_string(),
+                qualifier: ResourceQualifier::Utilization,
+                quantity: quantity.max(Qty::lowest_positive()),
+                location: location.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Extract the key of the group of a resource (`None` to exclude the resource)
+pub type GroupByFct<'a> = Box<dyn Fn(&Resource) -> Option<String> + 'a>;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[allow(non_camel_case_types)]
+pub enum GroupBy {
+    /// the kubeconfig context, see `--context ctx1,ctx2`
+    cluster,
     resource,
     node,
     pod,
     namespace,
+    container,
+    /// the controller of the pod, as `<kind>/<name>` (see `--resolve-owners`)
+    owner,
+    /// the name of the top-level owner of the pod (eg: the Deployment), or of the pod without owner
+    top_level_owner,
+    /// the size class of the limits of the containers (`nano`, `micro`, `small`, `medium`, `large`
+    /// or `unlimited`), see `limit_tier`
+    limit_tier,
+    /// the tool managing the pod (eg: `Helm`, `ArgoCD`), or the kind of its controller (eg: `DaemonSet`,
+    /// `Deployment` with `--resolve-owners`)
+    managed_by,
+    /// the value of an annotation (of the pod), syntax `pod_annotation:<key>`
+    pod_annotation(String),
+    /// the value of a label (of the pod or of the node), syntax `label=<key>`
+    label(String),
 }
 
 impl GroupBy {
-    pub fn to_fct(&self) -> fn(&Resource) -> Option<String> {
+    pub fn to_fct(&self) -> GroupByFct<'_> {
         match self {
-            Self::resource => Self::extract_kind,
-            Self::node => Self::extract_node_name,
-            Self::pod => Self::extract_pod_name,
-            Self::namespace => Self::extract_namespace,
+            Self::cluster => Box::new(Self::extract_cluster_name),
+            Self::resource => Box::new(Self::extract_kind),
+            Self::node => Box::new(Self::extract_node_name),
+            Self::pod => Box::new(Self::extract_pod_name),
+            Self::namespace => Box::new(Self::extract_namespace),
+            Self::container => Box::new(Self::extract_container_name),
+            Self::owner => Box::new(Self::extract_owner),
+            Self::top_level_owner => Box::new(Self::extract_top_level_owner),
+            Self::limit_tier => Box::new(Self::extract_limit_tier),
+            Self::managed_by => Box::new(Self::extract_managed_by),
+            Self::pod_annotation(key) => Box::new(move |e| Self::extract_pod_annotation(e, key)),
+            Self::label(key) => Box::new(move |e| Self::extract_label(e, key)),
         }
     }
 
+    fn extract_cluster_name(e: &Resource) -> Option<String> {
+        e.location.cluster_name.clone()
+    }
+
     fn extract_kind(e: &Resource) -> Option<String> {
-        Some(e.kind.clone())
+        Some(display_kind(&e.kind).to_string())
     }
 
     fn extract_node_name(e: &Resource) -> Option<String> {
@@ -540,322 +2295,5025 @@ impl GroupBy {
     fn extract_namespace(e: &Resource) -> Option<String> {
         e.location.namespace.clone()
     }
+
+    fn extract_container_name(e: &Resource) -> Option<String> {
+        // We do not need to display "pods" resource types when grouping by containers
+        if e.kind == "pods" {
+            return None;
+        }
+        e.location.container_name.clone()
+    }
+
+    fn extract_owner(e: &Resource) -> Option<String> {
+        e.location.owner_name.clone()
+    }
+
+    fn extract_top_level_owner(e: &Resource) -> Option<String> {
+        match &e.location.owner_name {
+            Some(owner) => owner
+                .split_once('/')
+                .map(|(_, name)| name.to_string())
+                .or_else(|| Some(owner.clone())),
+            None => e.location.pod_name.clone(),
+        }
+    }
+
+    fn extract_limit_tier(e: &Resource) -> Option<String> {
+        e.location.limit_tier.clone()
+    }
+
+    fn extract_managed_by(e: &Resource) -> Option<String> {
+        e.location.managed_by.clone().or_else(|| {
+            e.location
+                .owner_name
+                .as_ref()
+                .and_then(|owner| owner.split_once('/'))
+                .map(|(kind, _)| kind.to_string())
+        })
+    }
+
+    fn extract_label(e: &Resource, key: &str) -> Option<String> {
+        e.location.labels.get(key).cloned()
+    }
+
+    fn extract_pod_annotation(e: &Resource, key: &str) -> Option<String> {
+        // annotations' values are free text, normalize them to be displayed as names
+        e.location
+            .annotations
+            .get(key)
+            .map(|v| v.replace(|c: char| !c.is_ascii_alphanumeric(), "-"))
+    }
+}
+
+/// The name displayed for a kind of resource (eg: `storage` for `ephemeral-storage`)
+pub fn display_kind(kind: &str) -> &str {
+    match kind {
+        "ephemeral-storage" => "storage",
+        _ => kind,
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cluster" => Ok(Self::cluster),
+            "resource" => Ok(Self::resource),
+            "node" => Ok(Self::node),
+            "pod" => Ok(Self::pod),
+            "namespace" => Ok(Self::namespace),
+            "container" => Ok(Self::container),
+            "owner" => Ok(Self::owner),
+            "top_level_owner" => Ok(Self::top_level_owner),
+            "limit_tier" | "container_resource_limit_tier" => Ok(Self::limit_tier),
+            "managed_by" => Ok(Self::managed_by),
+            _ => {
+                // parametrized variants: `<name><sep><key>`
+                let key_of = |name: &str, sep: char| {
+                    s.split_once(sep)
+                        .filter(|(prefix, key)| {
+                            prefix.eq_ignore_ascii_case(name) && !key.is_empty()
+                        })
+                        .map(|(_, key)| key.to_string())
+                };
+                if let Some(key) = key_of("pod_annotation", ':') {
+                    Ok(Self::pod_annotation(key))
+                } else if let Some(key) = key_of("label", '=') {
+                    Ok(Self::label(key))
+                } else {
+                    Err(format!(
+                        "invalid value '{}' [possible values: {}]",
+                        s, "cluster, resource, node, pod, namespace, container, owner, top_level_owner, limit_tier, managed_by, pod_annotation:<key>, label=<key>"
+                    ))
+                }
+            }
+        }
+    }
 }
 
 impl std::fmt::Display for GroupBy {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            Self::pod_annotation(key) => write!(f, "pod_annotation:{}", key),
+            Self::label(key) => write!(f, "label={}", key),
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
 
-#[derive(Debug, Eq, PartialEq, ArgEnum, Clone)]
-#[allow(non_camel_case_types)]
-pub enum Output {
-    table,
-    csv,
+/// The style (prettytable's spec, eg: `rFg`) of the rows by resource kind, loaded from a toml file:
+///
+/// ```toml
+/// cpu = "rFg"
+/// memory = "rFb"
+/// "nvidia.com/gpu" = "rFm"
+/// ```
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(transparent)]
+pub struct ColorTheme {
+    styles: HashMap<String, String>,
 }
 
-#[derive(Parser, Debug)]
-#[clap(
-    // global_settings(&[AppSettings::ColoredHelp, AppSettings::VersionlessSubcommands]),
-    author = env!("CARGO_PKG_HOMEPAGE"), about, version
-)]
-pub struct CliOpts {
-    /// The name of the kubeconfig context to use
-    #[clap(long)]
-    pub context: Option<String>,
+impl ColorTheme {
+    pub fn load(path: &std::path::Path) -> Result<Self, Error> {
+        let content =
+            std::fs::read_to_string(path).map_err(|source| Error::ColorThemeReadError {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        toml::from_str(&content).map_err(|source| Error::ColorThemeParseError {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
 
-    /// Show only pods from this namespace
-    #[clap(short, long)]
-    pub namespace: Option<String>,
+    pub fn style_of(&self, kind: &str) -> Option<&str> {
+        self.styles.get(kind).map(|s| s.as_str())
+    }
+}
 
-    /// Force to retrieve utilization (for cpu and memory), require to have metrics-server https://github.com/kubernetes-sigs/metrics-server
-    #[clap(short = 'u', long)]
-    pub utilization: bool,
+/// A group of namespaces displayed as a single one
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct NamespaceGroup {
+    pub name: String,
+    pub namespaces: Vec<String>,
+}
 
-    /// Show lines with zero requested and zero limit and zero allocatable
-    #[clap(short = 'z', long)]
-    pub show_zero: bool,
+/// The groups of namespaces, loaded from a toml file, eg:
+///
+/// ```toml
+/// [[ns_group]]
+/// name = "production"
+/// namespaces = ["prod", "prod-eu", "prod-us"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+pub struct NamespaceGroups {
+    #[serde(default)]
+    ns_group: Vec<NamespaceGroup>,
+}
 
-    /// Filter resources shown by name(s), by default all resources are listed
-    #[clap(short, long)]
-    pub resource_name: Vec<String>,
+impl NamespaceGroups {
+    pub fn load(path: &std::path::Path) -> Result<Self, Error> {
+        let content =
+            std::fs::read_to_string(path).map_err(|source| Error::NamespaceGroupsReadError {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        toml::from_str(&content).map_err(|source| Error::NamespaceGroupsParseError {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
 
-    /// Group information hierarchically (default: -g resource -g node -g pod)
-    #[clap(short, long, arg_enum, ignore_case = true)]
-    pub group_by: Vec<GroupBy>,
+    /// The name of the (first) group of the namespace
+    pub fn group_of(&self, namespace: &str) -> Option<&str> {
+        self.ns_group
+            .iter()
+            .find(|g| g.namespaces.iter().any(|ns| ns == namespace))
+            .map(|g| g.name.as_str())
+    }
 
-    /// Output format
-    #[clap(short, long, arg_enum, ignore_case = true, default_value = "table")]
-    pub output: Output,
+    /// Replace the namespaces of the resources by their group, so every key (of rows,
+    /// extra columns, virtual groups,...) built from the resources uses the group
+    pub fn apply(&self, resources: &mut [Resource]) {
+        for resource in resources.iter_mut() {
+            let group = resource
+                .location
+                .namespace
+                .as_deref()
+                .and_then(|ns| self.group_of(ns));
+            if let Some(group) = group {
+                resource.location.namespace = Some(group.to_string());
+            }
+        }
+    }
+
+    /// Like `apply`, after having filtered the resources by `filter` (so the namespaces are matched
+    /// by their own names, not by the name of their group), returns the filter to use on the resources
+    /// afterwards (without the filters of namespaces)
+    pub fn apply_filtered(
+        &self,
+        resources: &mut Vec<Resource>,
+        filter: &ResourceFilter,
+    ) -> ResourceFilter {
+        if self.ns_group.is_empty() {
+            return filter.clone();
+        }
+        resources.retain(|r| filter.accept(r));
+        self.apply(resources);
+        ResourceFilter {
+            namespaces: vec![],
+            exclude_namespaces: vec![],
+            ..filter.clone()
+        }
+    }
 }
 
-pub async fn refresh_kube_config(cli_opts: &CliOpts) -> Result<(), Error> {
-    //HACK force refresh token by calling "kubectl cluster-info before loading configuration"
-    use std::process::Command;
-    let mut cmd = Command::new("kubectl");
-    cmd.arg("cluster-info");
-    if let Some(ref context) = cli_opts.context {
-        cmd.arg("--context").arg(context);
+/// The hourly cost of a unit (by default `1`, eg: a core) of a resource
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct CostRate {
+    #[serde(alias = "per_core_per_hour")]
+    pub per_unit_per_hour: f64,
+    /// the unit of the rate, as a quantity (eg: `1Gi` for a rate per GiB)
+    #[serde(default = "CostRate::default_unit")]
+    pub unit: String,
+}
+
+impl CostRate {
+    fn default_unit() -> String {
+        "1".to_string()
     }
-    let output = cmd.output().map_err(|source| Error::CmdError {
-        cmd: "kubectl cluster-info".to_owned(),
-        output: None,
-        source: Some(source),
-    })?;
-    if !output.status.success() {
-        return Err(Error::CmdError {
-            cmd: "kubectl cluster-info".to_owned(),
-            output: Some(output),
-            source: None,
-        });
+
+    pub fn cost_of(&self, qty: &Qty) -> Result<f64, Error> {
+        let unit = Qty::from_str(&self.unit)?;
+        Ok(f64::from(qty) / f64::from(&unit) * self.per_unit_per_hour)
     }
-    Ok(())
 }
 
-pub async fn new_client(cli_opts: &CliOpts) -> Result<kube::Client, Error> {
-    refresh_kube_config(cli_opts).await?;
-    let client_config = match cli_opts.context {
-        Some(ref context) => kube::Config::from_kubeconfig(&kube::config::KubeConfigOptions {
-            context: Some(context.clone()),
-            ..Default::default()
-        })
-        .await
-        .map_err(|source| Error::KubeConfigError {
-            context: "create the kube client config".to_string(),
-            source,
-        })?,
-        None => kube::Config::infer()
-            .await
-            .map_err(|source| Error::KubeInferConfigError {
-                context: "create the kube client config".to_string(),
-                source,
-            })?,
-    };
-    info!(cluster_url = client_config.cluster_url.to_string().as_str());
-    kube::Client::try_from(client_config).map_err(|source| Error::KubeError {
-        context: "create the kube client".to_string(),
-        source,
-    })
+/// The hourly costs by resource (kind), loaded from a toml file, eg:
+///
+/// ```toml
+/// [cpu]
+/// per_core_per_hour = 0.048
+///
+/// [memory]
+/// per_unit_per_hour = 0.006
+/// unit = "1Gi"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(transparent)]
+pub struct CostConfig {
+    rates: HashMap<String, CostRate>,
 }
 
-#[instrument]
-pub async fn do_main(cli_opts: &CliOpts) -> Result<(), Error> {
-    let client = new_client(cli_opts).await?;
-    let mut resources: Vec<Resource> = vec![];
-    collect_from_nodes(client.clone(), &mut resources).await?;
-    collect_from_pods(client.clone(), &mut resources, &cli_opts.namespace).await?;
-
-    let show_utilization = if cli_opts.utilization {
-        match collect_from_metrics(client.clone(), &mut resources).await {
-            Ok(_) => true,
-            Err(err) => {
-                warn!(?err);
-                false
-            }
+impl CostConfig {
+    pub fn load(path: &std::path::Path) -> Result<Self, Error> {
+        let content =
+            std::fs::read_to_string(path).map_err(|source| Error::CostConfigReadError {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        let config: CostConfig =
+            toml::from_str(&content).map_err(|source| Error::CostConfigParseError {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        // check the units once, instead of on every row
+        for rate in config.rates.values() {
+            Qty::from_str(&rate.unit)?;
         }
-    } else {
-        false
-    };
+        Ok(config)
+    }
 
-    let res = make_qualifiers(&resources, &cli_opts.group_by, &cli_opts.resource_name);
-    match &cli_opts.output {
-        Output::table => display_with_prettytable(&res, !&cli_opts.show_zero, show_utilization),
-        Output::csv => display_as_csv(&res, &cli_opts.group_by, show_utilization),
+    /// The hourly cost of the requested quantity of the resource (`None` if no rate defined)
+    pub fn cost_of(&self, kind: &str, requested: &Qty) -> Option<f64> {
+        self.rates
+            .get(kind)
+            .and_then(|rate| rate.cost_of(requested).ok())
     }
-    Ok(())
 }
 
-pub fn display_as_csv(
-    data: &[(Vec<String>, Option<QtyByQualifier>)],
-    group_by: &[GroupBy],
-    show_utilization: bool,
-) {
-    // print header
-    println!(
-        "Date,Kind,{}{},Requested,%Requested,Limit,%Limit,Allocatable,Free",
-        group_by.iter().map(|x| x.to_string()).join(","),
-        if show_utilization {
-            ",Utilization,%Utilization"
-        } else {
-            ""
+/// Additional columns, filled only on the rows of a given level of the tree
+#[derive(Debug, PartialEq, Clone)]
+pub enum ExtraColumn {
+    /// part of the node's allocatable requested by pods of DaemonSets
+    DaemonSetOverhead,
+    KubeletVersion,
+    RestartCount,
+    PodIp,
+    /// marker of pods without any resource requested
+    MissingRequests,
+    /// marker of pods being evicted
+    Evicting,
+    /// capacity of the node reserved (by system and kubernetes) vs allocatable to users
+    CapacityBreakdown,
+    /// marker of nodes with unbalanced requested cpu vs memory
+    Imbalance,
+    /// the policy of the CPU Manager of the node (`static` grants exclusive CPUs to Guaranteed pods)
+    CpuManager,
+    /// the conditions of the pod which are `False`
+    PodConditions,
+    /// the value of a label (the key) of the namespace
+    NamespaceLabel(String),
+    /// the part of the ResourceQuotas of the namespace used (used / hard)
+    Quota,
+    /// the internal IP of the node
+    NodeIp,
+    /// the required labels (the keys) missing on the pod,
+    /// or the number of pods missing some of them on node & namespace rows
+    MissingLabels(Vec<String>),
+    /// marker of namespaces being terminated
+    NamespaceTerminating,
+    /// the defaults (request / limit) applied by the LimitRanges of the namespace to containers
+    LimitRangeDefaults,
+    /// marker of pods waiting to be scheduled
+    Pending,
+    /// the estimated hourly cost of the requested resources (on every row),
+    /// highlighted when above the threshold
+    Cost(CostConfig, Option<f64>),
+    /// the RuntimeClass of the pod (eg: `kata-containers`, `gvisor`)
+    RuntimeClass,
+    /// the resourceVersion of the pod (as seen by the collect)
+    ResourceVersion,
+    /// marker of pods with a limit lower than the request (for a resource of a container)
+    InvalidLimits,
+    /// the CPUs allocated exclusively (pinned) vs shared, on nodes with the `static` policy of the CPU Manager
+    CpuPinning,
+    /// the level (`privileged`, `baseline` or `restricted`) enforced by the Pod Security Admission on the namespace
+    PsaLevel,
+    /// the part of the ephemeral storage of the node requested, red over the threshold (ratio)
+    EphemeralStoragePressure(f64),
+    /// the tool managing the pod (eg: `Helm`, `ArgoCD`), see `GroupBy::managed_by`
+    ManagedBy,
+}
+
+impl ExtraColumn {
+    pub fn title(&self) -> &str {
+        match self {
+            Self::DaemonSetOverhead => "DS Overhead",
+            Self::KubeletVersion => "Kubelet",
+            Self::RestartCount => "Restarts",
+            Self::PodIp => "Pod IP",
+            Self::MissingRequests => "",
+            Self::Evicting => "",
+            Self::CapacityBreakdown => "Capacity",
+            Self::Imbalance => "",
+            Self::CpuManager => "CPU Manager",
+            Self::PodConditions => "Conditions",
+            Self::NamespaceLabel(key) => key,
+            Self::Quota => "Quota",
+            Self::NodeIp => "IP",
+            Self::MissingLabels(_) => "Missing Labels",
+            Self::NamespaceTerminating => "",
+            Self::LimitRangeDefaults => "Defaults (req / lim)",
+            Self::Cost(..) => "Cost/hr",
+            Self::Pending => "",
+            Self::RuntimeClass => "Runtime",
+            Self::ResourceVersion => "ResourceVersion",
+            Self::InvalidLimits => "",
+            Self::CpuPinning => "Pinned CPUs",
+            Self::PsaLevel => "PSA",
+            Self::EphemeralStoragePressure(_) => "%Ephemeral",
+            Self::ManagedBy => "Managed by",
         }
-    );
+    }
 
-    // print data
-    let empty = "".to_string();
-    let datetime = Utc::now().to_rfc3339();
-    for (k, oqtys) in data {
-        if let Some(qtys) = oqtys {
-            let mut row = vec![
-                datetime.clone(),
-                group_by
-                    .get(k.len() - 1)
-                    .map(|x| x.to_string())
-                    .unwrap_or_else(|| empty.clone()),
-            ];
-            for i in 0..group_by.len() {
-                row.push(k.get(i).cloned().unwrap_or_else(|| empty.clone()));
-            }
-
-            if show_utilization {
-                add_cells_for_cvs(&mut row, &qtys.utilization, &qtys.allocatable);
-            }
-            add_cells_for_cvs(&mut row, &qtys.requested, &qtys.allocatable);
-            add_cells_for_cvs(&mut row, &qtys.limit, &qtys.allocatable);
-
-            row.push(
-                qtys.allocatable
-                    .as_ref()
-                    .map(|qty| format!("{:.2}", f64::from(qty)))
-                    .unwrap_or_else(|| empty.clone()),
-            );
-            row.push(
-                qtys.calc_free()
-                    .as_ref()
-                    .map(|qty| format!("{:.2}", f64::from(qty)))
-                    .unwrap_or_else(|| empty.clone()),
-            );
-            println!("{}", &row.join(","));
+    /// The level of the tree where the column is filled
+    pub fn level(&self) -> GroupBy {
+        match self {
+            Self::DaemonSetOverhead => GroupBy::node,
+            Self::KubeletVersion => GroupBy::node,
+            Self::RestartCount => GroupBy::pod,
+            Self::PodIp => GroupBy::pod,
+            Self::MissingRequests => GroupBy::pod,
+            Self::Evicting => GroupBy::pod,
+            Self::CapacityBreakdown => GroupBy::node,
+            Self::Imbalance => GroupBy::node,
+            Self::CpuManager => GroupBy::node,
+            Self::PodConditions => GroupBy::pod,
+            Self::NamespaceLabel(_) => GroupBy::namespace,
+            Self::Quota => GroupBy::namespace,
+            Self::NodeIp => GroupBy::node,
+            Self::MissingLabels(_) => GroupBy::pod,
+            Self::NamespaceTerminating => GroupBy::namespace,
+            Self::LimitRangeDefaults => GroupBy::namespace,
+            Self::Cost(..) => GroupBy::resource,
+            Self::Pending => GroupBy::pod,
+            Self::RuntimeClass => GroupBy::pod,
+            Self::ResourceVersion => GroupBy::pod,
+            Self::InvalidLimits => GroupBy::pod,
+            Self::CpuPinning => GroupBy::node,
+            Self::PsaLevel => GroupBy::namespace,
+            Self::EphemeralStoragePressure(_) => GroupBy::node,
+            Self::ManagedBy => GroupBy::pod,
         }
     }
-}
 
-fn add_cells_for_cvs(row: &mut Vec<String>, oqty: &Option<Qty>, o100: &Option<Qty>) {
-    match oqty {
-        None => {
-            row.push("".to_string());
-            row.push("".to_string());
-        }
-        Some(ref qty) => {
-            row.push(format!("{:.2}", f64::from(qty)));
-            row.push(match o100 {
-                None => "".to_string(),
-                Some(q100) => format!("{:.0}%", qty.calc_percentage(q100)),
-            });
+    /// Is the column filled on the rows of this level, by default only on `level()`
+    pub fn is_filled_at(&self, level: &GroupBy) -> bool {
+        match self {
+            Self::MissingLabels(_) => {
+                matches!(level, GroupBy::pod | GroupBy::node | GroupBy::namespace)
+            }
+            Self::Cost(..) => true,
+            _ => level == &self.level(),
         }
-    };
-}
+    }
 
-#[cfg(not(feature = "prettytable"))]
-pub fn display_with_prettytable(
-    _data: &[(Vec<String>, Option<QtyByQualifier>)],
-    _filter_full_zero: bool,
-    _show_utilization: bool,
-) {
-    warn!("feature 'prettytable' not enabled");
-}
+    /// The alignment of the column (for prettytable): 'r' for quantities, 'l' for texts
+    pub fn align(&self) -> &'static str {
+        match self {
+            Self::DaemonSetOverhead => "r",
+            Self::Cost(..) => "r",
+            Self::Quota => "r",
+            Self::RestartCount => "r",
+            Self::EphemeralStoragePressure(_) => "r",
+            _ => "l",
+        }
+    }
 
-#[cfg(feature = "prettytable")]
-pub fn display_with_prettytable(
-    data: &[(Vec<String>, Option<QtyByQualifier>)],
-    filter_full_zero: bool,
-    show_utilization: bool,
-) {
-    // Create the table
-    let mut table = Table::new();
-    let format = format::FormatBuilder::new()
-        // .column_separator('|')
-        // .borders('|')
-        // .separators(&[format::LinePosition::Top,
-        //               format::LinePosition::Bottom],
-        //             format::LineSeparator::new('-', '+', '+', '+'))
-        .separators(&[], format::LineSeparator::new('-', '+', '+', '+'))
-        .padding(1, 1)
-        .build();
-    table.set_format(format);
-    let mut row_titles = row![bl->"Resource", br->"Utilization", br->"Requested", br->"Limit",  br->"Allocatable", br->"Free"];
-    if !show_utilization {
-        row_titles.remove_cell(1);
-    }
-    table.set_titles(row_titles);
-    let data2 = data
-        .iter()
-        .filter(|d| {
-            !filter_full_zero
-                || !d
-                    .1
-                    .as_ref()
-                    .map(|x| {
-                        x.utilization.is_none()
-                            && is_empty(&x.requested)
-                            && is_empty(&x.limit)
-                            && is_empty(&x.allocatable)
-                    })
-                    .unwrap_or(false)
-        })
-        .collect::<Vec<_>>();
-    let prefixes = tree::provide_prefix(&data2, |parent, item| parent.0.len() + 1 == item.0.len());
+    /// A marker is displayed after the name of the row (instead of into its own column),
+    /// and its color is applied to the row
+    pub fn is_marker(&self) -> bool {
+        matches!(
+            self,
+            Self::MissingRequests
+                | Self::Evicting
+                | Self::Imbalance
+                | Self::NamespaceTerminating
+                | Self::Pending
+                | Self::InvalidLimits
+        )
+    }
 
-    for ((k, oqtys), prefix) in data2.iter().zip(prefixes.iter()) {
-        let column0 = format!(
-            "{} {}",
-            prefix,
-            k.last().map(|x| x.as_str()).unwrap_or("???")
-        );
-        if let Some(qtys) = oqtys {
-            let style = if qtys.requested > qtys.limit
-                || qtys.utilization > qtys.limit
-                || is_empty(&qtys.requested)
-                || is_empty(&qtys.limit)
-            {
-                "rFy"
-            } else {
-                "rFg"
-            };
-            let mut row = Row::new(vec![
-                Cell::new(&column0),
-                make_cell_for_prettytable(&qtys.utilization, &qtys.allocatable).style_spec(style),
-                make_cell_for_prettytable(&qtys.requested, &qtys.allocatable).style_spec(style),
-                make_cell_for_prettytable(&qtys.limit, &qtys.allocatable).style_spec(style),
-                make_cell_for_prettytable(&qtys.allocatable, &None).style_spec(style),
-                make_cell_for_prettytable(&qtys.calc_free(), &None).style_spec(style),
-            ]);
-            if !show_utilization {
-                row.remove_cell(1);
+    /// The color of a cell (for prettytable), according to its value
+    pub fn color(&self, txt: &str) -> &'static str {
+        match self {
+            Self::MissingRequests => "Fm",
+            Self::Evicting => "Fc",
+            Self::Imbalance => "Fy",
+            Self::NamespaceTerminating => "Fr",
+            Self::Pending => "Fb",
+            Self::InvalidLimits => "Fr",
+            Self::Cost(_, Some(threshold)) => match txt.trim_start_matches('$').parse::<f64>() {
+                Ok(cost) if cost > *threshold => "Fy",
+                _ => "",
+            },
+            Self::EphemeralStoragePressure(threshold) => {
+                match txt.trim_end_matches('%').parse::<f64>() {
+                    Ok(pct) if pct > threshold * 100.0 => "Fr",
+                    _ => "",
+                }
+            }
+            Self::MissingLabels(_) if !txt.is_empty() => "Fm",
+            Self::RestartCount => match txt.parse::<u32>().unwrap_or(0) {
+                n if n > 20 => "Fr",
+                n if n > 5 => "Fy",
+                _ => "",
+            },
+            _ => "",
+        }
+    }
+
+    fn compute(&self, group: &[&Resource], level: &GroupBy) -> Option<String> {
+        match self {
+            Self::DaemonSetOverhead => {
+                // above the resource level (eg: `-g node -g resource`) the kinds are mixed,
+                // the overhead is computed by kind
+                let kinds = group.iter().map(|r| r.kind.as_str()).unique().sorted();
+                let overheads = kinds
+                    .filter_map(|kind| {
+                        let of_kind = group
+                            .iter()
+                            .filter(|r| r.kind == kind)
+                            .copied()
+                            .collect::<Vec<_>>();
+                        daemonset_overhead(&of_kind).map(|overhead| (kind, overhead))
+                    })
+                    .collect::<Vec<_>>();
+                match overheads.as_slice() {
+                    [] => None,
+                    [(_, overhead)] if group.iter().map(|r| &r.kind).all_equal() => {
+                        Some(overhead.clone())
+                    }
+                    _ => Some(
+                        overheads
+                            .iter()
+                            .map(|(kind, overhead)| format!("{}: {}", kind, overhead))
+                            .join(", "),
+                    ),
+                }
+            }
+            Self::KubeletVersion => group
+                .iter()
+                .find_map(|r| r.location.kubelet_version.clone()),
+            Self::RestartCount => group.first().map(|r| r.location.restart_count.to_string()),
+            Self::PodIp => group.iter().find_map(|r| r.location.pod_ip.clone()),
+            Self::MissingRequests => group
+                .iter()
+                .any(|r| r.location.missing_requests)
+                .then(|| "⚠ NO REQUESTS".to_string()),
+            Self::Evicting => group
+                .iter()
+                .any(|r| r.location.evicting)
+                .then(|| "E".to_string()),
+            Self::CapacityBreakdown => try_sum_by_qualifier(group).and_then(|qtys| {
+                qtys.system_reserved.map(|reserved| {
+                    format!(
+                        "[sys+kube: {} | user: {}]",
+                        reserved.adjust_scale(),
+                        qtys.allocatable.unwrap_or_default().adjust_scale()
+                    )
+                })
+            }),
+            Self::Imbalance => group.iter().find_map(|r| r.location.imbalance.clone()),
+            Self::CpuManager => group
+                .iter()
+                .find_map(|r| r.location.cpu_manager_policy.clone()),
+            Self::PodConditions => group
+                .iter()
+                .map(|r| &r.location.failing_conditions)
+                .find(|c| !c.is_empty())
+                .map(|c| c.join(",")),
+            Self::Pending => group
+                .iter()
+                .any(|r| r.location.pending)
+                .then(|| "⏳ (pending)".to_string()),
+            Self::NamespaceTerminating => group
+                .iter()
+                .any(|r| r.location.namespace_terminating)
+                .then(|| "[TERMINATING]".to_string()),
+            Self::NamespaceLabel(key) => group
+                .iter()
+                .find_map(|r| r.location.namespace_labels.get(key).cloned()),
+            Self::Quota => try_sum_by_qualifier(group).and_then(|qtys| {
+                let used = qtys.quota_used.unwrap_or_default();
+                qtys.quota_hard.map(|hard| {
+                    format!(
+                        "({:.0}%) {} / {}",
+                        used.calc_percentage(&hard),
+                        used.adjust_scale(),
+                        hard.adjust_scale()
+                    )
+                })
+            }),
+            Self::NodeIp => group.iter().find_map(|r| r.location.node_ip.clone()),
+            Self::Cost(config, _) => {
+                let kind = &group.first()?.kind;
+                let requested = try_sum_by_qualifier(group)?.requested?;
+                config
+                    .cost_of(kind, &requested)
+                    .map(|cost| format!("${:.3}", cost))
+            }
+            Self::LimitRangeDefaults => {
+                let kind = &group.first()?.kind;
+                if group.iter().any(|r| &r.kind != kind) {
+                    return None;
+                }
+                // the defaults of several LimitRanges of a namespace are not cumulative,
+                // show the smallest one (the most restrictive)
+                let min_default = |is_qualifier: fn(&ResourceQualifier) -> bool| {
+                    group
+                        .iter()
+                        .filter(|r| is_qualifier(&r.qualifier))
+                        .map(|r| &r.quantity)
+                        .min()
+                        .cloned()
+                };
+                let default_request =
+                    min_default(|q| matches!(q, ResourceQualifier::DefaultRequest));
+                let default_limit = min_default(|q| matches!(q, ResourceQualifier::DefaultLimit));
+                let format_default = |oqty: Option<Qty>| {
+                    oqty.map(|qty| qty.adjust_scale().to_string())
+                        .unwrap_or_else(|| "__".to_string())
+                };
+                (default_request.is_some() || default_limit.is_some()).then(|| {
+                    format!(
+                        "{} / {}",
+                        format_default(default_request),
+                        format_default(default_limit)
+                    )
+                })
+            }
+            Self::MissingLabels(keys) => {
+                let missing_by_pod = group
+                    .iter()
+                    .filter_map(|r| {
+                        let pod = (
+                            r.location.namespace.as_ref()?,
+                            r.location.pod_name.as_ref()?,
+                        );
+                        let missing = keys
+                            .iter()
+                            .filter(|k| !r.location.labels.contains_key(*k))
+                            .collect::<Vec<_>>();
+                        (!missing.is_empty()).then_some((pod, missing))
+                    })
+                    .collect::<BTreeMap<_, _>>();
+                match level {
+                    GroupBy::pod => missing_by_pod
+                        .values()
+                        .next()
+                        .map(|keys| keys.iter().join(",")),
+                    _ => (!missing_by_pod.is_empty())
+                        .then(|| format!("{} pods", missing_by_pod.len())),
+                }
+            }
+            Self::RuntimeClass => group.iter().find_map(|r| r.location.runtime_class.clone()),
+            Self::ResourceVersion => group
+                .iter()
+                .find_map(|r| r.location.resource_version.clone()),
+            Self::InvalidLimits => group
+                .iter()
+                .any(|r| r.location.has_invalid_limits)
+                .then(|| "⚠ LIMIT < REQUEST".to_string()),
+            Self::CpuPinning => cpu_pinning(group),
+            Self::EphemeralStoragePressure(_) => {
+                ephemeral_storage_ratio(group).map(|r| format!("{:.0}%", r * 100.0))
             }
-            table.add_row(row);
+            Self::PsaLevel => group
+                .iter()
+                .find_map(|r| r.location.namespace_labels.get(PSA_ENFORCE_LABEL).cloned()),
+            Self::ManagedBy => group.iter().find_map(|r| GroupBy::extract_managed_by(r)),
         }
     }
+}
 
-    // Print the table to stdout
-    table.printstd();
+#[derive(Debug, Eq, PartialEq, ArgEnum, Clone)]
+#[allow(non_camel_case_types)]
+pub enum Output {
+    table,
+    csv,
+    csv_with_schema,
+    /// like csv, but separated by tabs (eg: for `cut` or `awk`)
+    tsv,
+    kube_score,
+    statsd,
+    terraform,
+    ansible,
+    /// one yaml document (`---`) by row, with the same structure as the json output
+    ndyaml,
+    json,
+    yaml,
+    markdown,
+    prometheus,
+    datadog_events,
+    /// TimeSeries for Google Cloud Monitoring, see `--gcp-project`
+    gcp_monitoring,
+    /// issues of the rows over `--threshold-request` / `--threshold-limit`, created on `--jira-url` if defined
+    jira,
+    /// upload (csv or json) to a file server, see `--sftp-host`
+    sftp,
 }
 
-#[cfg(feature = "prettytable")]
-fn is_empty(oqty: &Option<Qty>) -> bool {
-    match oqty {
-        Some(qty) => qty.is_zero(),
-        None => true,
+#[derive(Debug, Eq, PartialEq, ArgEnum, Clone)]
+#[allow(non_camel_case_types)]
+pub enum SortBy {
+    key,
+    requested,
+    limit,
+    allocatable,
+    utilization,
+    free,
+}
+
+impl SortBy {
+    /// The value to sort by (`0.0` when undefined)
+    fn value_of(&self, qtys: &QtyByQualifier) -> f64 {
+        let oqty = match self {
+            Self::key => None,
+            Self::requested => qtys.requested.clone(),
+            Self::limit => qtys.limit.clone(),
+            Self::allocatable => qtys.allocatable.clone(),
+            Self::utilization => qtys.utilization.clone(),
+            Self::free => qtys.calc_free(),
+        };
+        oqty.as_ref().map(f64::from).unwrap_or(0.0)
     }
 }
 
-#[cfg(feature = "prettytable")]
-fn make_cell_for_prettytable(oqty: &Option<Qty>, o100: &Option<Qty>) -> Cell {
-    let txt = match oqty {
-        None => "__".to_string(),
-        Some(ref qty) => match o100 {
-            None => format!("{}", qty.adjust_scale()),
-            Some(q100) => format!("({:.0}%) {}", qty.calc_percentage(q100), qty.adjust_scale()),
-        },
-    };
-    Cell::new(&txt)
+/// The metric driving the color of the rows (of the table)
+#[derive(Debug, Eq, PartialEq, ArgEnum, Clone)]
+#[allow(non_camel_case_types)]
+pub enum ColorBy {
+    /// yellow when the requested and limit are missing or inconsistent (utilization above limit,...)
+    requested_pct,
+    /// utilization / allocatable
+    utilization_pct,
+    /// part of the allocatable not free (requested or limit)
+    free,
+    /// part of the requested not used (over-requested)
+    efficiency,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// The columns of quantities (of the table, csv and markdown outputs)
+#[derive(Debug, Eq, PartialEq, Hash, ArgEnum, Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub enum Column {
+    utilization,
+    requested,
+    limit,
+    allocatable,
+    free,
+}
+
+impl Column {
+    const ALL: [Column; 5] = [
+        Self::utilization,
+        Self::requested,
+        Self::limit,
+        Self::allocatable,
+        Self::free,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::utilization => "Utilization",
+            Self::requested => "Requested",
+            Self::limit => "Limit",
+            Self::allocatable => "Allocatable",
+            Self::free => "Free",
+        }
+    }
+
+    fn value_of(&self, qtys: &QtyByQualifier) -> Option<Qty> {
+        match self {
+            Self::utilization => qtys.utilization.clone(),
+            Self::requested => qtys.requested.clone(),
+            Self::limit => qtys.limit.clone(),
+            Self::allocatable => qtys.allocatable.clone(),
+            Self::free => qtys.calc_free(),
+        }
+    }
+
+    /// The column is displayed with its percentage of the allocatable
+    fn with_percentage(&self) -> bool {
+        matches!(self, Self::utilization | Self::requested | Self::limit)
+    }
+
+    /// The columns to display (in order), without the `hidden` ones
+    pub fn visible(
+        show_utilization: bool,
+        hide_allocatable: bool,
+        hidden: &HashSet<Column>,
+    ) -> Vec<Column> {
+        Self::ALL
+            .into_iter()
+            .filter(|c| !hidden.contains(c))
+            .filter(|c| match c {
+                Self::utilization => show_utilization,
+                Self::allocatable | Self::free => !hide_allocatable,
+                _ => true,
+            })
+            .collect()
+    }
+}
+
+/// The options of display (built once from the `CliOpts`), shared by the display functions
+#[derive(Debug, Clone)]
+pub struct DisplayOptions {
+    /// hide the rows without requested, limit and allocatable (unless `--show-zero`)
+    pub filter_full_zero: bool,
+    /// show the utilization (when collected)
+    pub show_utilization: bool,
+    /// hide the columns allocatable & free, and the percentages relative to allocatable
+    pub hide_allocatable: bool,
+    /// the columns of quantities to hide
+    pub hidden_columns: HashSet<Column>,
+    /// the number of decimals of the quantities (the default depends on the output)
+    pub precision: Option<usize>,
+    /// display only the percentages (of allocatable) instead of the quantities
+    pub percent_only: bool,
+    /// a column per level of grouping, instead of a tree
+    pub flatten: bool,
+    pub color_by: ColorBy,
+    /// the percentages (of the `color_by` metric) from which rows are yellow, red
+    pub color_thresholds: (u8, u8),
+    /// the maximum number of characters of the names (of the table)
+    pub max_name_width: usize,
+    /// show the ratio limit / allocatable
+    pub show_overcommit: bool,
+    /// the ratio limit / allocatable from which the overcommit is red
+    pub overcommit_threshold: f64,
+    /// show the changes (of the diff table) also as percentages
+    pub show_pct_change: bool,
+    /// color the tables (eg: when written to a terminal)
+    pub colorize: bool,
+}
+
+impl Default for DisplayOptions {
+    /// The defaults of the cli
+    fn default() -> Self {
+        DisplayOptions {
+            filter_full_zero: true,
+            show_utilization: false,
+            hide_allocatable: false,
+            hidden_columns: HashSet::new(),
+            precision: None,
+            percent_only: false,
+            flatten: false,
+            color_by: ColorBy::requested_pct,
+            color_thresholds: (80, 95),
+            max_name_width: 60,
+            show_overcommit: false,
+            overcommit_threshold: 1.5,
+            show_pct_change: false,
+            colorize: false,
+        }
+    }
+}
+
+impl From<&CliOpts> for DisplayOptions {
+    fn from(cli_opts: &CliOpts) -> Self {
+        DisplayOptions {
+            filter_full_zero: !cli_opts.show_zero,
+            show_utilization: cli_opts.utilization,
+            hide_allocatable: cli_opts.hide_allocatable,
+            hidden_columns: cli_opts.hide_column.iter().copied().collect(),
+            precision: cli_opts.precision,
+            percent_only: cli_opts.percent_only,
+            flatten: cli_opts.flatten,
+            color_by: cli_opts.color_by.clone(),
+            color_thresholds: (cli_opts.color_warn, cli_opts.color_crit),
+            max_name_width: cli_opts.max_name_width,
+            show_overcommit: cli_opts.show_overcommit,
+            overcommit_threshold: cli_opts.overcommit_threshold,
+            show_pct_change: cli_opts.historical_comparison.is_some(),
+            // decided by the writer of the output, see `render_output`
+            colorize: false,
+        }
+    }
+}
+
+/// The style of rows without warning (replaced by the color theme if any)
+const OK_STYLE: &str = "rFg";
+
+/// The style (prettytable) of a row: `OK_STYLE`, yellow above the `warn` percentage,
+/// red above the `crit` one (see `ColorBy` for the percentage compared)
+pub fn compute_color(
+    qtys: &QtyByQualifier,
+    color_by: &ColorBy,
+    warn: u8,
+    crit: u8,
+) -> &'static str {
+    let pct = match color_by {
+        ColorBy::requested_pct => {
+            return if qtys.requested > qtys.limit
+                || qtys.utilization > qtys.limit
+                || is_empty(&qtys.requested)
+                || is_empty(&qtys.limit)
+            {
+                "rFy"
+            } else {
+                OK_STYLE
+            };
+        }
+        ColorBy::utilization_pct => qtys
+            .utilization
+            .as_ref()
+            .zip(qtys.allocatable.as_ref())
+            .map(|(utilization, allocatable)| utilization.calc_percentage(allocatable)),
+        ColorBy::free => qtys
+            .calc_free()
+            .zip(qtys.allocatable.as_ref())
+            .map(|(free, allocatable)| 100.0 - free.calc_percentage(allocatable)),
+        ColorBy::efficiency => qtys
+            .calc_utilization_ratio()
+            .map(|ratio| 100.0 - ratio * 100.0),
+    };
+    match pct.filter(|pct| pct.is_finite()) {
+        Some(pct) if pct >= f64::from(crit) => "rFr",
+        Some(pct) if pct >= f64::from(warn) => "rFy",
+        _ => OK_STYLE,
+    }
+}
+
+/// The requested of the daemonsets' pods, and its percentage of the allocatable,
+/// of the resources (of the same kind) of a node
+fn daemonset_overhead(group: &[&Resource]) -> Option<String> {
+    let daemonsets = group
+        .iter()
+        .filter(|r| r.location.is_daemonset)
+        .copied()
+        .collect::<Vec<_>>();
+    let requested = try_sum_by_qualifier(&daemonsets)
+        .and_then(|qtys| qtys.requested)
+        .unwrap_or_default();
+    try_sum_by_qualifier(group)
+        .and_then(|qtys| qtys.allocatable)
+        .map(|allocatable| {
+            format!(
+                "({:.0}%) {}",
+                requested.calc_percentage(&allocatable),
+                requested.adjust_scale()
+            )
+        })
+}
+
+/// A row with a ratio requested / allocatable or limit / allocatable over its threshold
+#[derive(Debug, Clone)]
+pub struct ThresholdViolation<'a> {
+    pub key: &'a [String],
+    pub qualifier: ResourceQualifier,
+    pub ratio: f64,
+    pub threshold: f64,
+}
+
+impl From<&ThresholdViolation<'_>> for Error {
+    fn from(v: &ThresholdViolation<'_>) -> Self {
+        Error::ThresholdExceeded {
+            qualifier: v.qualifier.clone(),
+            path: v.key.join("/"),
+            ratio: v.ratio,
+            threshold: v.threshold,
+        }
+    }
+}
+
+/// Check that the ratios requested / allocatable and limit / allocatable of every row
+/// are under the thresholds (if defined)
+pub fn check_thresholds(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    threshold_request: Option<f64>,
+    threshold_limit: Option<f64>,
+) -> Result<(), Error> {
+    match threshold_violations(data, threshold_request, threshold_limit).first() {
+        Some(violation) => Err(violation.into()),
+        None => Ok(()),
+    }
+}
+
+/// List the rows (and qualifiers) over the thresholds (if defined)
+pub fn threshold_violations(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    threshold_request: Option<f64>,
+    threshold_limit: Option<f64>,
+) -> Vec<ThresholdViolation<'_>> {
+    let mut violations = vec![];
+    for (k, qtys) in data
+        .iter()
+        .filter_map(|(k, oqtys)| oqtys.as_ref().map(|q| (k, q)))
+    {
+        let allocatable = match &qtys.allocatable {
+            Some(allocatable) if !allocatable.is_zero() => f64::from(allocatable),
+            _ => continue,
+        };
+        for (qualifier, oqty, othreshold) in [
+            (
+                ResourceQualifier::Requested,
+                &qtys.requested,
+                threshold_request,
+            ),
+            (ResourceQualifier::Limit, &qtys.limit, threshold_limit),
+        ] {
+            if let (Some(qty), Some(threshold)) = (oqty, othreshold) {
+                let ratio = f64::from(qty) / allocatable;
+                if ratio > threshold {
+                    violations.push(ThresholdViolation {
+                        key: k,
+                        qualifier: qualifier.clone(),
+                        ratio,
+                        threshold,
+                    });
+                }
+            }
+        }
+    }
+    violations
+}
+
+/// Sort the rows by `sort_by` (then by key) among siblings, so every row stays
+/// after its parent (as expected by the tree).
+pub fn sort_rows(data: &mut [(Vec<String>, Option<QtyByQualifier>)], sort_by: &SortBy, desc: bool) {
+    let values = data
+        .iter()
+        .map(|(k, oqtys)| {
+            let value = oqtys.as_ref().map(|qtys| sort_by.value_of(qtys));
+            (k.clone(), value.unwrap_or(0.0))
+        })
+        .collect::<HashMap<_, _>>();
+    let value_of = |k: &[String]| values.get(k).copied().unwrap_or(0.0);
+    data.sort_by(|(a, _), (b, _)| {
+        // compare the ancestors (or self) of a and b which are siblings
+        match a.iter().zip(b.iter()).position(|(x, y)| x != y) {
+            Some(i) => {
+                let ord = value_of(&a[..=i])
+                    .partial_cmp(&value_of(&b[..=i]))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| a[i].cmp(&b[i]));
+                if desc {
+                    ord.reverse()
+                } else {
+                    ord
+                }
+            }
+            None => a.len().cmp(&b.len()),
+        }
+    });
+}
+
+/// Keep only the `n` leaves with the highest value of `sort_by` (`requested` when sorted by key),
+/// for every resource, and their ancestors (recomputed, see `recompute_parents`, or without quantities
+/// above the `resource` level)
+pub fn top_rows(
+    data: Vec<(Vec<String>, Option<QtyByQualifier>)>,
+    group_by: &[GroupBy],
+    n: usize,
+    sort_by: &SortBy,
+) -> Vec<(Vec<String>, Option<QtyByQualifier>)> {
+    let sort_by = match sort_by {
+        SortBy::key => &SortBy::requested,
+        _ => sort_by,
+    };
+    let parents = data
+        .iter()
+        .filter_map(|(k, _)| k.split_last().map(|(_, parent)| parent.to_vec()))
+        .collect::<HashSet<_>>();
+    let mut leaves = data
+        .iter()
+        .filter(|(k, _)| !parents.contains(k))
+        .map(|(k, oqtys)| {
+            let value = oqtys.as_ref().map(|qtys| sort_by.value_of(qtys));
+            (k, value.unwrap_or(0.0))
+        })
+        .collect::<Vec<_>>();
+    leaves.sort_by(|(ka, a), (kb, b)| {
+        b.partial_cmp(a)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| ka.cmp(kb))
+    });
+    let resource_idx = group_by.iter().position(|g| g == &GroupBy::resource);
+    // the groups above the `resource` level mix the kinds (see `make_qualifiers`)
+    let resource_depth = resource_idx.unwrap_or(group_by.len());
+    let mut count_by_resource: HashMap<Option<&String>, usize> = HashMap::new();
+    let kept = leaves
+        .into_iter()
+        .filter(|(k, _)| {
+            let count = count_by_resource
+                .entry(resource_idx.and_then(|i| k.get(i)))
+                .or_default();
+            *count += 1;
+            *count <= n
+        })
+        .flat_map(|(k, _)| (1..=k.len()).map(|i| k[..i].to_vec()))
+        .collect::<HashSet<_>>();
+    // the ancestors of the removed rows
+    let changed = data
+        .iter()
+        .filter(|(k, _)| !kept.contains(k))
+        .flat_map(|(k, _)| (1..k.len()).map(|i| k[..i].to_vec()))
+        .collect::<HashSet<_>>();
+    let mut data = data
+        .into_iter()
+        .filter(|(k, _)| kept.contains(k))
+        .map(|(k, oqtys)| {
+            // can't be recomputed as the sums of the children (of different kinds)
+            if k.len() <= resource_depth && changed.contains(&k) {
+                (k, None)
+            } else {
+                (k, oqtys)
+            }
+        })
+        .collect::<Vec<_>>();
+    recompute_parents(
+        &mut data,
+        &changed
+            .into_iter()
+            .filter(|k| k.len() > resource_depth)
+            .collect(),
+    );
+    data
+}
+
+/// Recompute the utilization, requested and limit of the `changed` rows as the sums of their children
+/// (the allocatable, quotas,... of a row doesn't depend on its children)
+pub fn recompute_parents(
+    data: &mut [(Vec<String>, Option<QtyByQualifier>)],
+    changed: &HashSet<Vec<String>>,
+) {
+    let mut children: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+    for (i, (k, _)) in data.iter().enumerate() {
+        if let Some((_, parent)) = k.split_last() {
+            children.entry(parent.to_vec()).or_default().push(i);
+        }
+    }
+    // the deepest rows first, so the children are up to date before their parent
+    let mut indexes = (0..data.len()).collect::<Vec<_>>();
+    indexes.sort_by_key(|i| std::cmp::Reverse(data[*i].0.len()));
+    for i in indexes {
+        if !changed.contains(&data[i].0) {
+            continue;
+        }
+        let mut sum = QtyByQualifier::default();
+        for child in children.get(&data[i].0).into_iter().flatten() {
+            if let Some(qtys) = &data[*child].1 {
+                sum.utilization = add_option(sum.utilization, &qtys.utilization);
+                sum.requested = add_option(sum.requested, &qtys.requested);
+                sum.limit = add_option(sum.limit, &qtys.limit);
+            }
+        }
+        let qtys = data[i].1.get_or_insert_with(QtyByQualifier::default);
+        qtys.utilization = sum.utilization;
+        qtys.requested = sum.requested;
+        qtys.limit = sum.limit;
+    }
+}
+
+/// A bearer token (of `--token`), hidden by `Debug` (eg: when the cli options are logged)
+#[derive(Clone, PartialEq, Eq)]
+pub struct Token(pub String);
+
+impl std::fmt::Debug for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Token(<redacted>)")
+    }
+}
+
+impl FromStr for Token {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Token(s.to_string()))
+    }
+}
+
+#[derive(Parser, Debug)]
+#[clap(
+    // global_settings(&[AppSettings::ColoredHelp, AppSettings::VersionlessSubcommands]),
+    author = env!("CARGO_PKG_HOMEPAGE"), about, version
+)]
+pub struct CliOpts {
+    /// The name of the kubeconfig context to use, several ones (`ctx1,ctx2`) to aggregate their clusters
+    /// (under a first level `cluster`)
+    #[clap(long, value_delimiter = ',')]
+    pub context: Vec<String>,
+
+    /// Bearer token to authenticate to the API server (e.g. of a service account), instead of the kubeconfig
+    /// (default: the env var `KUBE_TOKEN`, prefer `--token-file` to not expose it in the arguments)
+    #[clap(long, requires = "server")]
+    pub token: Option<Token>,
+
+    /// The file of the bearer token to authenticate to the API server (re-read by the client,
+    /// e.g. the token of the service account, mounted in the pods)
+    #[clap(long, requires = "server", conflicts_with = "token")]
+    pub token_file: Option<String>,
+
+    /// The address of the API server (https://hostname:port), required by `--token`
+    #[clap(long)]
+    pub server: Option<String>,
+
+    /// The certificate authority file of `--server` (default: the one of the service account, when in a pod)
+    #[clap(long, requires = "server")]
+    pub certificate_authority: Option<String>,
+
+    /// Don't check the certificate of `--server` (insecure)
+    #[clap(long, requires = "server", conflicts_with = "certificate-authority")]
+    pub insecure_skip_tls_verify: bool,
+
+    /// Client certificate file for TLS (mTLS) authentication, replace the one of the kubeconfig's user
+    #[clap(long, requires = "key-file")]
+    pub cert_file: Option<String>,
+
+    /// Client key file for TLS (mTLS) authentication, replace the one of the kubeconfig's user
+    #[clap(long, requires = "cert-file")]
+    pub key_file: Option<String>,
+
+    /// Abort the collection of resources of the context after this number of seconds
+    #[clap(long)]
+    pub context_timeout: Option<u64>,
+
+    /// Show only pods from these namespaces: names, globs (`kube-*`) or regexes (`^prod-`)
+    #[clap(short, long)]
+    pub namespace: Vec<NamespacePattern>,
+
+    /// Hide pods from these namespaces (repeatable or comma-separated, e.g. `kube-system,kube-public`)
+    #[clap(long, alias = "ignore-namespace", use_value_delimiter = true)]
+    pub exclude_namespace: Vec<String>,
+
+    /// Show only this node and the pods scheduled on it
+    #[clap(long)]
+    pub node: Option<String>,
+
+    /// Show only the nodes (and the pods scheduled on them) whose name matches the regular expression
+    #[clap(long)]
+    pub node_filter_regex: Option<Regex>,
+
+    /// Show only the pods matching the label selector (eg: `env=production,tier in (frontend,backend)`), filtered by the API server
+    #[clap(short = 'l', long)]
+    pub label_selector: Option<LabelSelector>,
+
+    /// Show only the pods matching the field selector (eg: `status.phase=Running`), filtered by the API server; its requirements on `spec.nodeName` also select the nodes
+    #[clap(long)]
+    pub field_selector: Option<FieldSelector>,
+
+    /// Show only the nodes matching the label selector (eg: `node-role.kubernetes.io/worker=true`), and the pods scheduled on them
+    #[clap(long)]
+    pub node_selector: Option<LabelSelector>,
+
+    /// Force to retrieve utilization (for cpu and memory), require to have metrics-server https://github.com/kubernetes-sigs/metrics-server
+    #[clap(short = 'u', long)]
+    pub utilization: bool,
+
+    /// Show lines with zero requested and zero limit and zero allocatable
+    #[clap(short = 'z', long)]
+    pub show_zero: bool,
+
+    /// Filter resources shown by name(s), by default all resources are listed.
+    /// A resource matches when its name contains the filter (eg: `gpu` for `nvidia.com/gpu`),
+    /// or is equal to it with `--exact-resource` or for a qualified name (eg: `nvidia.com/gpu`)
+    #[clap(short, long)]
+    pub resource_name: Vec<String>,
+
+    /// Hide resources by name(s) (e.g. `pods`), applied after `--resource-name`
+    #[clap(long = "exclude-resource")]
+    pub exclude_resource_name: Vec<String>,
+
+    /// Match the names of `--resource-name` and `--exclude-resource` exactly (eg: `pu` does not match `cpu`)
+    #[clap(long)]
+    pub exact_resource: bool,
+
+    /// Group information hierarchically (default: -g resource -g node -g pod, or -g namespace -g resource -g pod with --namespace), resource is always added (as first level if missing)
+    /// [possible values: resource, node, pod, namespace, container, limit_tier, managed_by, pod_annotation:<key>, label=<key>]
+    #[clap(short, long)]
+    pub group_by: Vec<GroupBy>,
+
+    /// Output format
+    #[clap(short, long, arg_enum, ignore_case = true, default_value = "table")]
+    pub output: Output,
+
+    /// Show the part of the node's allocatable requested by DaemonSets (the "infrastructure tax"), on node rows
+    #[clap(long)]
+    pub show_daemonset_overhead: bool,
+
+    /// Save the collected resources as the snapshot of the day, `<cache>/view-allocations/<context>/<date>.json`
+    /// (`<cache>` is `$XDG_CACHE_HOME` or `~/.cache`), to compare with them later (see `--historical-comparison`)
+    #[clap(long)]
+    pub save: bool,
+
+    /// Compare the resources with the snapshot saved (by `--save`) N days ago, displayed as a table of changes
+    /// (with their percentages)
+    #[clap(long, value_name = "N_DAYS")]
+    pub historical_comparison: Option<u32>,
+
+    /// Show the resources still held by pods being terminated, into a `<terminating>` group
+    /// (implies `--include-terminating`)
+    #[clap(long, alias = "show-terminating-pods")]
+    pub show_terminating: bool,
+
+    /// Include the pods being terminated (with a `deletionTimestamp`), excluded by default as their resources will be freed soon
+    #[clap(long)]
+    pub include_terminating: bool,
+
+    /// Show the version of the kubelet, on node rows
+    #[clap(long)]
+    pub show_kubelet_version: bool,
+
+    /// Show the number of restarts of the containers, on pod rows (yellow over 5, red over 20)
+    #[clap(long)]
+    pub show_restart_count: bool,
+
+    /// Show the IP of the pod, on pod rows
+    #[clap(long)]
+    pub show_pod_ip: bool,
+
+    /// Show a flat table with a column per level of grouping, instead of a tree
+    #[clap(long)]
+    pub flatten: bool,
+
+    /// Show a progress indicator (on stderr) while collecting data
+    #[clap(long)]
+    pub progress: bool,
+
+    /// Mark the pods without any resource requested, on pod rows
+    #[clap(long)]
+    pub show_missing_requests: bool,
+
+    /// Multiplier applied to the requested resources to define the hard limits of the quotas (used by `--output terraform` and `--generate-resource-quota`)
+    #[clap(long, default_value = "1.2")]
+    pub quota_buffer: f64,
+
+    /// Mark the pods being evicted (with 'E') and show their resources into an `<evicting>` group
+    #[clap(long, alias = "show-pod-disruption")]
+    pub show_evicting: bool,
+
+    /// Show the capacity reserved for system and kubernetes (kubelet,...) vs allocatable to users, on node rows
+    #[clap(long, alias = "show-node-capacity-breakdown")]
+    pub show_capacity_breakdown: bool,
+
+    /// Mark the nodes where the percentages of requested cpu and memory differ more than `--imbalance-threshold`
+    #[clap(long)]
+    pub detect_imbalances: bool,
+
+    /// Threshold (in percentage points) of the difference between requested cpu and memory to detect imbalanced nodes
+    #[clap(long, default_value = "30")]
+    pub imbalance_threshold: f64,
+
+    /// Hide the columns Allocatable and Free (and the percentages relative to allocatable) of the table
+    #[clap(long)]
+    pub hide_allocatable: bool,
+
+    /// Show the policy of the CPU Manager (from the node's annotation `cpu-manager-policy`), on node rows
+    #[clap(long)]
+    pub show_cpu_manager: bool,
+
+    /// Toml file with the color (prettytable style, eg: `cpu = "rFg"`) of the table's rows by resource kind
+    #[clap(long, value_name = "FILE")]
+    pub color_theme: Option<std::path::PathBuf>,
+
+    /// Show the conditions of the pod which are `False` (e.g. `ContainersReady=False`), on pod rows
+    #[clap(long)]
+    pub show_pod_conditions: bool,
+
+    /// Sort the rows (among siblings) by a column, then by name
+    #[clap(long, arg_enum, ignore_case = true, default_value = "key")]
+    pub sort_by: SortBy,
+
+    /// Sort the rows in descending order
+    #[clap(long)]
+    pub sort_desc: bool,
+
+    /// Exit with code 2 when the ratio requested / allocatable of a row is over this threshold (0.0 - 1.0)
+    #[clap(long, value_name = "RATIO")]
+    pub threshold_request: Option<f64>,
+
+    /// Exit with code 2 when the ratio limit / allocatable of a row is over this threshold (0.0 - 1.0, or more for overcommitted limits)
+    #[clap(long, value_name = "RATIO")]
+    pub threshold_limit: Option<f64>,
+
+    /// Show the value of these labels of the namespaces (comma separated keys, e.g. `team,env`), on namespace rows
+    #[clap(long, value_name = "KEY", value_delimiter = ',')]
+    pub show_namespace_labels: Vec<String>,
+
+    /// Refresh the output continuously (every `--watch-interval`), until Ctrl-C
+    /// (the table replaces the previous one on a terminal, the other outputs are appended)
+    #[clap(short, long)]
+    pub watch: bool,
+
+    /// Interval (in seconds) between the refreshes of `--watch`
+    #[clap(long, value_name = "SECONDS", default_value = "30")]
+    pub watch_interval: u64,
+
+    /// Show the usage of the ResourceQuotas (used / hard), on namespace rows (e.g. with `-g resource -g namespace`)
+    #[clap(long)]
+    pub show_quotas: bool,
+
+    /// Summarize the resources of the (running) pods of Jobs, under a virtual group `<jobs>`
+    #[clap(long)]
+    pub include_jobs: bool,
+
+    /// Generate the manifests of a ResourceQuota per namespace (with the hard limits set to the requested resources plus a buffer) into FILE (`-` for stdout), instead of the regular output
+    #[clap(long, value_name = "FILE")]
+    pub generate_resource_quota: Option<std::path::PathBuf>,
+
+    /// Buffer (in percent) added to the requested resources to define the hard limits of the quotas, overrides `--quota-buffer` (20 is the same as `--quota-buffer 1.2`)
+    #[clap(long, value_name = "PCT")]
+    pub quota_buffer_pct: Option<f64>,
+
+    /// Show the internal IP of the node, on node rows
+    #[clap(long, alias = "show-node-addresses")]
+    pub show_node_ip: bool,
+
+    /// Flag the pods missing any of these labels (repeatable), with the number of such pods on node & namespace rows
+    #[clap(long, value_name = "KEY")]
+    pub require_label: Vec<String>,
+
+    /// Mark the namespaces being terminated with `[TERMINATING]`, on namespace rows
+    #[clap(long)]
+    pub show_namespace_phase: bool,
+
+    /// Hide the resources of the namespaces being terminated (their pods are being cleaned up)
+    #[clap(long)]
+    pub exclude_terminating_namespaces: bool,
+
+    /// Resolve the owners of the pods through their ReplicaSet or Job (eg: `Deployment/<name>`), for `--group-by owner` (lists the ReplicaSets & Jobs, implied by `--group-by top_level_owner`)
+    #[clap(long)]
+    pub resolve_owners: bool,
+
+    /// Collect the LimitRanges and show their defaults (request / limit) applied to containers, on namespace rows
+    #[clap(long)]
+    pub show_limit_ranges: bool,
+
+    /// Expand the pods into their containers (shortcut to add `container` under `pod` into `--group-by`)
+    #[clap(long)]
+    pub show_containers: bool,
+
+    /// Show the estimated hourly cost of the requested resources ("Cost/hr" column), with the rates by resource of this toml file
+    #[clap(long, value_name = "FILE")]
+    pub cost_config: Option<std::path::PathBuf>,
+
+    /// Highlight the costs (see `--cost-config`) above this hourly amount
+    #[clap(long, value_name = "DOLLARS")]
+    pub cost_threshold: Option<f64>,
+
+    /// Show the RuntimeClass of the pods (eg: `runc`, `kata-containers`, `gvisor`), on pod rows
+    #[clap(long)]
+    pub show_runtime_class: bool,
+
+    /// Include the pods waiting to be scheduled (Pending), marked on pod rows and summarized under a virtual group `<pending>`
+    #[clap(long, alias = "pending")]
+    pub include_pending: bool,
+
+    /// The metric driving the color of the rows (`requested-pct` keeps the check of requested vs limit)
+    #[clap(long, arg_enum, ignore_case = true, default_value = "requested-pct")]
+    pub color_by: ColorBy,
+
+    /// Percentage (of the `--color-by` metric) from which rows are yellow
+    #[clap(long, value_name = "PCT", default_value = "80")]
+    pub color_warn: u8,
+
+    /// Percentage (of the `--color-by` metric) from which rows are red
+    #[clap(long, value_name = "PCT", default_value = "95")]
+    pub color_crit: u8,
+
+    /// Save the collected resources as json into this file (displayed as usual), to load them later with `--snapshot-read`
+    #[clap(long, value_name = "PATH")]
+    pub snapshot_write: Option<std::path::PathBuf>,
+
+    /// Load the resources from this file (saved with `--snapshot-write`) instead of connecting to the cluster
+    #[clap(long, value_name = "PATH", conflicts_with = "snapshot-write")]
+    pub snapshot_read: Option<std::path::PathBuf>,
+
+    /// Compare the resources with the ones of this snapshot (saved with `--snapshot-write`), displayed as a table of changes
+    #[clap(
+        long,
+        value_name = "SNAPSHOT_PATH",
+        conflicts_with = "historical-comparison"
+    )]
+    pub diff: Option<std::path::PathBuf>,
+
+    /// Truncate the names (first column of the table) longer than this number of characters, with `…`
+    #[clap(long, value_name = "N", default_value = "60")]
+    pub max_name_width: usize,
+
+    /// Show the resourceVersion of the pods, on pod rows (eg: to check that 2 snapshots of --diff come from the same state)
+    #[clap(long)]
+    pub show_resource_version: bool,
+
+    /// The number of decimals of the quantities (default: 1 for table and markdown, 2 for csv)
+    #[clap(long, value_name = "N")]
+    pub precision: Option<usize>,
+
+    /// Mark the pods with a limit lower than the request (of a resource of a container), on pod rows
+    #[clap(long)]
+    pub show_limits_ratio_warning: bool,
+
+    /// Show only the N leaves (eg: pods) with the highest value of `--sort-by` (requested by default), by resource,
+    /// their ancestors being recomputed from them
+    #[clap(long, value_name = "N")]
+    pub top: Option<usize>,
+
+    /// The host (`host`, `host:port` or `[ipv6]:port`) of the file server to upload to, for `--output sftp`
+    #[clap(long)]
+    pub sftp_host: Option<String>,
+
+    /// The user to connect to `--sftp-host` (default: $USER)
+    #[clap(long)]
+    pub sftp_user: Option<String>,
+
+    /// The private key to connect to `--sftp-host` (default: the keys of the ssh agent)
+    #[clap(long)]
+    pub sftp_key: Option<std::path::PathBuf>,
+
+    /// The path of the file to write on `--sftp-host` (json when it ends with `.json`, csv otherwise)
+    #[clap(long)]
+    pub sftp_path: Option<String>,
+
+    /// Hide a column of quantities (of table, csv and markdown outputs), can be repeated
+    #[clap(long, arg_enum, ignore_case = true)]
+    pub hide_column: Vec<Column>,
+
+    /// Toml file defining groups of namespaces (`[[ns_group]]` with `name` and `namespaces`),
+    /// displayed as a single namespace (eg: `production` for `prod`, `prod-eu`, `prod-us`)
+    #[clap(long, value_name = "FILE")]
+    pub namespace_resource_group: Option<std::path::PathBuf>,
+
+    /// Show the CPUs allocated exclusively to containers (Guaranteed pods with integer CPU requests) vs shared, on node rows with the `static` policy of the CPU Manager
+    #[clap(long)]
+    pub show_cpu_pinning: bool,
+
+    /// Display only the percentages (of allocatable) of utilization, requested and limit, instead of the quantities
+    #[clap(long)]
+    pub percent_only: bool,
+
+    /// The Google Cloud project of the cluster, for `--output gcp-monitoring`
+    #[clap(long)]
+    pub gcp_project: Option<String>,
+
+    /// The location (zone or region) of the cluster, for `--output gcp-monitoring`
+    #[clap(long)]
+    pub gcp_location: Option<String>,
+
+    /// The name of the GKE cluster, for `--output gcp-monitoring` (default: the value of `--group-by cluster`)
+    #[clap(long)]
+    pub gcp_cluster: Option<String>,
+
+    /// Log (at DEBUG level) the url of each request to the API server and the beginning of its response,
+    /// eg: to diagnose the resources forbidden (403) by RBAC
+    #[clap(long)]
+    pub debug_api_calls: bool,
+
+    /// Show the level enforced by the Pod Security Admission (label `pod-security.kubernetes.io/enforce`), on namespace rows (empty when not set: the default of the cluster applies)
+    #[clap(long)]
+    pub show_psa_level: bool,
+
+    /// Show the ratio limit / allocatable (Overcommit column of table, csv and markdown outputs)
+    #[clap(long)]
+    pub show_overcommit: bool,
+
+    /// The ratio limit / allocatable from which the Overcommit column is red
+    #[clap(long, value_name = "RATIO", default_value = "1.5")]
+    pub overcommit_threshold: f64,
+
+    /// Show the part of the ephemeral storage of the nodes requested by their pods (pods are evicted when the node runs out of it), on node rows
+    #[clap(long, alias = "show-ephemeral-pressure")]
+    pub show_ephemeral_storage_pressure: bool,
+
+    /// The ratio requested / allocatable of the ephemeral storage from which `--show-ephemeral-storage-pressure` is red
+    #[clap(long, value_name = "RATIO", default_value = "0.85")]
+    pub ephemeral_storage_threshold: f64,
+
+    /// The base url of the JIRA server (eg: `https://example.atlassian.net`) where to create the issues of `--output jira`
+    /// (the issues are only printed when not defined)
+    #[clap(long)]
+    pub jira_url: Option<String>,
+
+    /// The file of the (personal access) token to create the issues on `--jira-url`
+    /// (default: the env var `JIRA_TOKEN`, the token is not read from the arguments to not expose it)
+    #[clap(long, value_name = "PATH")]
+    pub jira_token_file: Option<std::path::PathBuf>,
+
+    /// The key of the JIRA project of the issues of `--output jira`
+    #[clap(long, default_value = "OPS")]
+    pub jira_project: String,
+
+    /// Show the tool managing the pods (label `app.kubernetes.io/managed-by`, eg: `Helm`, `ArgoCD`, or the kind of their controller), on pod rows
+    #[clap(long)]
+    pub show_managed_by: bool,
+}
+
+impl CliOpts {
+    /// Check the combinations of options not rejected by the parser (also for `CliOpts`
+    /// built without it, when used as a library)
+    pub fn validate(&self) -> Result<(), Error> {
+        let with_metrics = self.utilization || self.snapshot_read.is_some();
+        if !with_metrics && self.sort_by == SortBy::utilization {
+            return Err(Error::MissingOption {
+                option: "--utilization (or --snapshot-read)".to_string(),
+                by: "--sort-by utilization".to_string(),
+            });
+        }
+        if !with_metrics && self.color_by == ColorBy::utilization_pct {
+            return Err(Error::MissingOption {
+                option: "--utilization (or --snapshot-read)".to_string(),
+                by: "--color-by utilization-pct".to_string(),
+            });
+        }
+        if self.cost_threshold.is_some() && self.cost_config.is_none() {
+            return Err(Error::MissingOption {
+                option: "--cost-config".to_string(),
+                by: "--cost-threshold".to_string(),
+            });
+        }
+        if self.snapshot_read.is_some() && self.snapshot_write.is_some() {
+            return Err(Error::IncompatibleOptions {
+                reason: "--snapshot-read and --snapshot-write cannot be combined".to_string(),
+            });
+        }
+        if self.server.is_some() && !self.context.is_empty() {
+            return Err(Error::IncompatibleOptions {
+                reason: "--server and --context are mutually exclusive (--server ignores the kubeconfig)"
+                    .to_string(),
+            });
+        }
+        // the issues would be created again on every iteration
+        if self.watch && self.output == Output::jira && self.jira_url.is_some() {
+            return Err(Error::IncompatibleOptions {
+                reason: "--watch cannot create issues with --output jira (on --jira-url)"
+                    .to_string(),
+            });
+        }
+        // the requests can not be over the allocatable (the limits can be overcommitted)
+        let thresholds = [
+            ("--threshold-request", self.threshold_request, Some(1.0)),
+            ("--threshold-limit", self.threshold_limit, None),
+        ];
+        for (option, threshold, max) in thresholds
+            .into_iter()
+            .filter_map(|(option, threshold, max)| threshold.map(|t| (option, t, max)))
+        {
+            if threshold.is_nan() || threshold < 0.0 {
+                return Err(Error::InvalidOption {
+                    option: option.to_string(),
+                    reason: format!("{} is not a positive ratio", threshold),
+                });
+            }
+            if let Some(max) = max.filter(|max| threshold > *max) {
+                return Err(Error::InvalidOption {
+                    option: option.to_string(),
+                    reason: format!("{} is over {:.1}", threshold, max),
+                });
+            }
+        }
+        if self.color_warn > self.color_crit {
+            return Err(Error::IncompatibleOptions {
+                reason: format!(
+                    "--color-warn ({}) is over --color-crit ({})",
+                    self.color_warn, self.color_crit
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+pub async fn refresh_kube_config(context: &Option<String>) -> Result<(), Error> {
+    //HACK force refresh token by calling "kubectl cluster-info before loading configuration"
+    use std::process::Command;
+    let mut cmd = Command::new("kubectl");
+    cmd.arg("cluster-info");
+    if let Some(context) = context {
+        cmd.arg("--context").arg(context);
+    }
+    let output = cmd.output().map_err(|source| Error::CmdError {
+        cmd: "kubectl cluster-info".to_owned(),
+        output: None,
+        source: Some(source),
+    })?;
+    if !output.status.success() {
+        return Err(Error::CmdError {
+            cmd: "kubectl cluster-info".to_owned(),
+            output: Some(output),
+            source: None,
+        });
+    }
+    Ok(())
+}
+
+async fn new_client_config_from_kubeconfig(
+    context: &Option<String>,
+) -> Result<kube::Config, Error> {
+    refresh_kube_config(context).await?;
+    let client_config = match context {
+        Some(context) => kube::Config::from_kubeconfig(&kube::config::KubeConfigOptions {
+            context: Some(context.clone()),
+            ..Default::default()
+        })
+        .await
+        .map_err(|source| Error::KubeConfigError {
+            context: "create the kube client config".to_string(),
+            source,
+        })?,
+        None => kube::Config::infer()
+            .await
+            .map_err(|source| Error::KubeInferConfigError {
+                context: "create the kube client config".to_string(),
+                source,
+            })?,
+    };
+    Ok(client_config)
+}
+
+/// The credentials defined by the cli (bearer token of `--token`, `--token-file` or else of the
+/// env var `KUBE_TOKEN`, client certificate)
+fn make_auth_info(cli_opts: &CliOpts) -> Result<kube::config::AuthInfo, Error> {
+    let mut auth_info = kube::config::AuthInfo {
+        token_file: cli_opts.token_file.clone(),
+        client_certificate: cli_opts.cert_file.clone(),
+        client_key: cli_opts.key_file.clone(),
+        ..kube::config::AuthInfo::default()
+    };
+    let token = match (&cli_opts.token, &cli_opts.token_file) {
+        (Some(token), _) => Some(token.0.clone()),
+        (None, None) => std::env::var("KUBE_TOKEN").ok().filter(|t| !t.is_empty()),
+        (None, Some(_)) => None,
+    };
+    if let Some(token) = token {
+        // deserialized like the user of a kubeconfig, kube keeps the token as a secret
+        let token_only: kube::config::AuthInfo =
+            serde_json::from_value(serde_json::json!({ "token": token })).map_err(|source| {
+                Error::JsonError {
+                    context: "read the bearer token".to_string(),
+                    source,
+                }
+            })?;
+        auth_info.token = token_only.token;
+    }
+    Ok(auth_info)
+}
+
+/// The certificate authority of the service account (mounted in the pods)
+const SERVICE_ACCOUNT_CA: &str = "/var/run/secrets/kubernetes.io/serviceaccount/ca.crt";
+
+/// The certificate authority of `--server`: `--certificate-authority`, or else the one of
+/// the service account when present (eg: when running in a pod with `--token`)
+fn server_certificate_authority(cli_opts: &CliOpts) -> Option<String> {
+    if cli_opts.insecure_skip_tls_verify {
+        return None;
+    }
+    cli_opts.certificate_authority.clone().or_else(|| {
+        std::path::Path::new(SERVICE_ACCOUNT_CA)
+            .exists()
+            .then(|| SERVICE_ACCOUNT_CA.to_string())
+    })
+}
+
+/// A kubeconfig with a single context, to connect to `server` (trusting `certificate_authority`,
+/// or any certificate if `insecure_skip_tls_verify`) with `auth_info`
+fn make_kubeconfig(
+    server: &str,
+    certificate_authority: Option<String>,
+    insecure_skip_tls_verify: bool,
+    auth_info: kube::config::AuthInfo,
+) -> kube::config::Kubeconfig {
+    use kube::config::{Cluster, Context, Kubeconfig, NamedAuthInfo, NamedCluster, NamedContext};
+    let name = "view-allocations".to_string();
+    Kubeconfig {
+        clusters: vec![NamedCluster {
+            name: name.clone(),
+            cluster: Cluster {
+                server: server.to_string(),
+                insecure_skip_tls_verify: insecure_skip_tls_verify.then_some(true),
+                certificate_authority,
+                certificate_authority_data: None,
+                proxy_url: None,
+                extensions: None,
+            },
+        }],
+        auth_infos: vec![NamedAuthInfo {
+            name: name.clone(),
+            auth_info,
+        }],
+        contexts: vec![NamedContext {
+            name: name.clone(),
+            context: Context {
+                cluster: name.clone(),
+                user: name.clone(),
+                namespace: None,
+                extensions: None,
+            },
+        }],
+        current_context: Some(name),
+        ..Kubeconfig::default()
+    }
+}
+
+/// Replace the client certificate of the user of the context (the current one by default)
+fn set_client_certificate(
+    kubeconfig: &mut kube::config::Kubeconfig,
+    context: &Option<String>,
+    cert_file: &str,
+    key_file: &str,
+) {
+    let context_name = context
+        .clone()
+        .or_else(|| kubeconfig.current_context.clone());
+    let user = kubeconfig
+        .contexts
+        .iter()
+        .find(|c| Some(&c.name) == context_name.as_ref())
+        .map(|c| c.context.user.clone());
+    for named in kubeconfig.auth_infos.iter_mut() {
+        if Some(&named.name) == user.as_ref() {
+            named.auth_info.client_certificate = Some(cert_file.to_string());
+            named.auth_info.client_certificate_data = None;
+            named.auth_info.client_key = Some(key_file.to_string());
+            named.auth_info.client_key_data = None;
+        }
+    }
+}
+
+/// A client for the `context` of the kubeconfig (the current one by default),
+/// or for `--server` when defined
+pub async fn new_client(
+    cli_opts: &CliOpts,
+    context: &Option<String>,
+) -> Result<kube::Client, Error> {
+    let client_config = match (&cli_opts.server, &cli_opts.cert_file, &cli_opts.key_file) {
+        (Some(server), _, _) => kube::Config::from_custom_kubeconfig(
+            make_kubeconfig(
+                server,
+                server_certificate_authority(cli_opts),
+                cli_opts.insecure_skip_tls_verify,
+                make_auth_info(cli_opts)?,
+            ),
+            &kube::config::KubeConfigOptions::default(),
+        )
+        .await
+        .map_err(|source| Error::KubeConfigError {
+            context: "create the kube client config".to_string(),
+            source,
+        })?,
+        (None, Some(cert_file), Some(key_file)) => {
+            refresh_kube_config(context).await?;
+            let mut kubeconfig =
+                kube::config::Kubeconfig::read().map_err(|source| Error::KubeConfigError {
+                    context: "read the kubeconfig".to_string(),
+                    source,
+                })?;
+            set_client_certificate(&mut kubeconfig, context, cert_file, key_file);
+            kube::Config::from_custom_kubeconfig(
+                kubeconfig,
+                &kube::config::KubeConfigOptions {
+                    context: context.clone(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|source| Error::KubeConfigError {
+                context: "create the kube client config".to_string(),
+                source,
+            })?
+        }
+        _ => new_client_config_from_kubeconfig(context).await?,
+    };
+    info!(cluster_url = client_config.cluster_url.to_string().as_str());
+    if cli_opts.debug_api_calls {
+        return new_client_with_debug_api_calls(client_config);
+    }
+    kube::Client::try_from(client_config).map_err(|source| Error::KubeError {
+        context: "create the kube client".to_string(),
+        source,
+    })
+}
+
+/// Save the collected resources as json, to display them later (see `read_snapshot`)
+pub fn write_snapshot(path: &std::path::Path, resources: &[Resource]) -> Result<(), Error> {
+    let json = serde_json::to_string(resources).map_err(|source| Error::JsonError {
+        context: "serialize the snapshot".to_string(),
+        source,
+    })?;
+    std::fs::write(path, json).map_err(|source| Error::WriteFileError {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Load the resources saved by `write_snapshot`, returns the resources and if utilization is available
+pub fn read_snapshot(path: &std::path::Path) -> Result<(Vec<Resource>, bool), Error> {
+    let content = std::fs::read_to_string(path).map_err(|source| Error::ReadFileError {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let resources: Vec<Resource> =
+        serde_json::from_str(&content).map_err(|source| Error::JsonError {
+            context: format!("parse the snapshot '{}'", path.display()),
+            source,
+        })?;
+    let show_utilization = resources
+        .iter()
+        .any(|r| matches!(r.qualifier, ResourceQualifier::Utilization));
+    Ok((resources, show_utilization))
+}
+
+/// The directory of the daily snapshots (of `--save`): `$XDG_CACHE_HOME/view-allocations`
+/// or `~/.cache/view-allocations`
+pub fn history_dir() -> Result<std::path::PathBuf, Error> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(std::path::PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".cache")))
+        .map(|cache| cache.join("view-allocations"))
+        .ok_or_else(|| Error::MissingOption {
+            option: "$HOME (or $XDG_CACHE_HOME)".to_string(),
+            by: "--save or --historical-comparison".to_string(),
+        })
+}
+
+/// The path of the snapshot of `context` at `date`, into `dir` (see `history_dir`)
+pub fn history_path(dir: &std::path::Path, context: &str, date: NaiveDate) -> std::path::PathBuf {
+    dir.join(context)
+        .join(format!("{}.json", date.format("%Y-%m-%d")))
+}
+
+/// The name of the context of the resources without `cluster_name` (collected from a single context):
+/// the one of `--context`, or the current one of the kubeconfig
+fn default_context_name(cli_opts: &CliOpts) -> String {
+    cli_opts
+        .context
+        .first()
+        .cloned()
+        .or_else(|| {
+            kube::config::Kubeconfig::read()
+                .ok()
+                .and_then(|config| config.current_context)
+        })
+        .unwrap_or_else(|| "default".to_string())
+}
+
+/// Save the resources as the snapshots of `date`, one by context (`cluster_name`, or `default_context`)
+pub fn save_history(
+    dir: &std::path::Path,
+    resources: &[Resource],
+    default_context: &str,
+    date: NaiveDate,
+) -> Result<(), Error> {
+    let by_context = resources
+        .iter()
+        .cloned()
+        .into_group_map_by(|r| r.location.cluster_name.clone());
+    for (context, rsrcs) in by_context {
+        let path = history_path(dir, context.as_deref().unwrap_or(default_context), date);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|source| Error::WriteFileError {
+                path: parent.to_path_buf(),
+                source,
+            })?;
+        }
+        write_snapshot(&path, &rsrcs)?;
+    }
+    Ok(())
+}
+
+/// Load the snapshots of `contexts` at `date` (saved by `save_history`)
+pub fn load_history(
+    dir: &std::path::Path,
+    contexts: &[String],
+    date: NaiveDate,
+) -> Result<Vec<Resource>, Error> {
+    let mut resources = vec![];
+    for context in contexts {
+        resources.extend(read_snapshot(&history_path(dir, context, date))?.0);
+    }
+    Ok(resources)
+}
+
+/// The target of the logs of `--debug-api-calls`
+pub const DEBUG_API_CALLS_TARGET: &str = "kubectl_view_allocations::api_calls";
+
+/// The maximum number of bytes of a response body logged by `--debug-api-calls`
+#[cfg(feature = "debug-api-calls")]
+const DEBUG_API_CALLS_MAX_BODY: usize = 1000;
+
+#[cfg(not(feature = "debug-api-calls"))]
+fn new_client_with_debug_api_calls(client_config: kube::Config) -> Result<kube::Client, Error> {
+    warn!("feature 'debug-api-calls' not enabled");
+    kube::Client::try_from(client_config).map_err(|source| Error::KubeError {
+        context: "create the kube client".to_string(),
+        source,
+    })
+}
+
+/// Create a client (like `kube::Client::try_from`, without gzip) that logs the url of each request
+/// and the first bytes of each response (the body is read fully to be logged, then replayed).
+/// The connect and read timeouts are the ones of the config, as for `kube::Client::try_from`.
+#[cfg(feature = "debug-api-calls")]
+fn new_client_with_debug_api_calls(client_config: kube::Config) -> Result<kube::Client, Error> {
+    use kube::client::ConfigExt;
+    use tower::ServiceBuilder;
+    let to_error = |source| Error::KubeError {
+        context: "create the kube client".to_string(),
+        source,
+    };
+    let https = client_config
+        .native_tls_https_connector()
+        .map_err(to_error)?;
+    let mut connector = hyper_timeout::TimeoutConnector::new(https);
+    connector.set_connect_timeout(client_config.timeout);
+    connector.set_read_timeout(client_config.timeout);
+    let service = ServiceBuilder::new()
+        .layer(client_config.base_uri_layer())
+        .option_layer(client_config.auth_layer().map_err(to_error)?)
+        .layer(client_config.extra_headers_layer().map_err(to_error)?)
+        .map_request(|req: hyper::Request<hyper::Body>| {
+            tracing::debug!(target: DEBUG_API_CALLS_TARGET, method = %req.method(), url = %req.uri(), "request");
+            req
+        })
+        .and_then(|res: hyper::Response<hyper::Body>| async move {
+            let (parts, body) = res.into_parts();
+            let bytes = hyper::body::to_bytes(body).await?;
+            let shown = &bytes[..bytes.len().min(DEBUG_API_CALLS_MAX_BODY)];
+            tracing::debug!(
+                target: DEBUG_API_CALLS_TARGET,
+                status = parts.status.as_u16(),
+                body = %String::from_utf8_lossy(shown),
+                "response"
+            );
+            Ok::<_, hyper::Error>(hyper::Response::from_parts(parts, hyper::Body::from(bytes)))
+        })
+        .service(hyper::Client::builder().build(connector));
+    Ok(kube::Client::new(service, client_config.default_namespace))
+}
+
+/// Spinner displayed on stderr while collecting data, only if enabled and stdout is a terminal
+struct Progress {
+    #[cfg(feature = "indicatif")]
+    bar: Option<indicatif::ProgressBar>,
+}
+
+impl Progress {
+    fn new(enabled: bool) -> Progress {
+        #[cfg(feature = "indicatif")]
+        {
+            use std::io::IsTerminal;
+            let bar = if enabled && std::io::stdout().is_terminal() {
+                let bar = indicatif::ProgressBar::new_spinner();
+                bar.enable_steady_tick(std::time::Duration::from_millis(100));
+                Some(bar)
+            } else {
+                None
+            };
+            Progress { bar }
+        }
+        #[cfg(not(feature = "indicatif"))]
+        {
+            let _ = enabled;
+            Progress {}
+        }
+    }
+
+    fn set_message(&self, _msg: &'static str) {
+        #[cfg(feature = "indicatif")]
+        if let Some(bar) = &self.bar {
+            bar.set_message(_msg);
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        #[cfg(feature = "indicatif")]
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+/// The hierarchy of groups to display when none is defined: `resource`, `node`, `pod`,
+/// or `namespace`, `resource`, `pod` when `namespaced` (eg: `--namespace` is defined).
+/// `resource` is always part of it (as first level when not defined)
+pub fn effective_group_by(group_by: &[GroupBy], namespaced: bool) -> Vec<GroupBy> {
+    let mut group_by = group_by.to_vec();
+    if group_by.is_empty() {
+        group_by = if namespaced {
+            vec![GroupBy::namespace, GroupBy::resource, GroupBy::pod]
+        } else {
+            vec![GroupBy::resource, GroupBy::node, GroupBy::pod]
+        };
+    }
+    if !group_by.contains(&GroupBy::resource) {
+        group_by.insert(0, GroupBy::resource)
+    }
+    group_by.dedup();
+    group_by
+}
+
+/// Add the `container` level just under the `pod` one (or as last level without `pod`)
+pub fn with_containers(mut group_by: Vec<GroupBy>) -> Vec<GroupBy> {
+    if !group_by.contains(&GroupBy::container) {
+        match group_by.iter().position(|g| g == &GroupBy::pod) {
+            Some(i) => group_by.insert(i + 1, GroupBy::container),
+            None => group_by.push(GroupBy::container),
+        }
+    }
+    group_by
+}
+
+/// Collect the resources of nodes, pods (and metrics if requested),
+/// returns the resources and if utilization is available
+pub async fn collect_from_cluster(
+    client: kube::Client,
+    cli_opts: &CliOpts,
+) -> Result<(Vec<Resource>, bool), Error> {
+    let mut resources: Vec<Resource> = vec![];
+    let progress = Progress::new(cli_opts.progress);
+    // the labels to group by, or required on the pods
+    let label_keys = cli_opts
+        .group_by
+        .iter()
+        .filter_map(|g| match g {
+            GroupBy::label(key) => Some(key.clone()),
+            _ => None,
+        })
+        .chain(cli_opts.require_label.iter().cloned())
+        .collect::<Vec<_>>();
+    progress.set_message("collecting nodes...");
+    collect_from_nodes(
+        client.clone(),
+        &mut resources,
+        &cli_opts.node,
+        &cli_opts.node_filter_regex,
+        &cli_opts.field_selector,
+        &cli_opts.node_selector,
+        &label_keys,
+    )
+    .await?;
+    // the pods (and metrics) are listed on every nodes, see `retain_on_nodes`
+    let selected_nodes = cli_opts.node_selector.as_ref().map(|_| {
+        resources
+            .iter()
+            .filter_map(|r| r.location.node_name.clone())
+            .collect::<HashSet<_>>()
+    });
+    progress.set_message("collecting pods...");
+    let skipped_pods = collect_from_pods(
+        client.clone(),
+        &mut resources,
+        &cli_opts.namespace,
+        &cli_opts.node,
+        &cli_opts.node_filter_regex,
+        &cli_opts.label_selector,
+        &cli_opts.field_selector,
+        &cli_opts
+            .group_by
+            .iter()
+            .filter_map(|g| match g {
+                GroupBy::pod_annotation(key) => Some(key.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+        &label_keys,
+        cli_opts.include_pending,
+        // the evicting pods are terminating
+        cli_opts.include_terminating || cli_opts.show_terminating || cli_opts.show_evicting,
+        // only when displayed (or saved to be displayed later): the location of the pod is copied
+        // for each resource of each container
+        cli_opts.show_containers
+            || cli_opts.show_cpu_pinning
+            || cli_opts.snapshot_write.is_some()
+            || cli_opts
+                .group_by
+                .iter()
+                .any(|g| matches!(g, GroupBy::container | GroupBy::limit_tier)),
+    )
+    .await?;
+    if cli_opts.resolve_owners || cli_opts.group_by.contains(&GroupBy::top_level_owner) {
+        progress.set_message("collecting replicasets & jobs...");
+        resolve_owners(client.clone(), &mut resources, &cli_opts.namespace).await?;
+    }
+    if cli_opts.show_quotas {
+        progress.set_message("collecting resourcequotas...");
+        collect_from_resource_quotas(client.clone(), &mut resources, &cli_opts.namespace).await?;
+    }
+    if cli_opts.show_limit_ranges {
+        progress.set_message("collecting limitranges...");
+        collect_from_limit_ranges(client.clone(), &mut resources, &cli_opts.namespace).await?;
+    }
+    if !cli_opts.show_namespace_labels.is_empty()
+        || cli_opts.show_namespace_phase
+        || cli_opts.exclude_terminating_namespaces
+        || cli_opts.show_psa_level
+    {
+        progress.set_message("collecting namespaces...");
+        let mut label_keys = cli_opts.show_namespace_labels.clone();
+        if cli_opts.show_psa_level {
+            label_keys.push(PSA_ENFORCE_LABEL.to_string());
+        }
+        collect_from_namespaces(
+            client.clone(),
+            &mut resources,
+            &cli_opts.namespace,
+            &label_keys,
+        )
+        .await?;
+    }
+
+    let show_utilization = if cli_opts.utilization {
+        progress.set_message("collecting metrics...");
+        match collect_from_metrics(
+            client.clone(),
+            &mut resources,
+            &cli_opts.label_selector,
+            &skipped_pods,
+        )
+        .await
+        {
+            Ok(_) => {
+                // the usage of a node is the one of all its pods, not only of the pods selected
+                let pods_filtered = !cli_opts.namespace.is_empty()
+                    || !cli_opts.exclude_namespace.is_empty()
+                    || cli_opts.label_selector.is_some()
+                    || cli_opts.field_selector.is_some();
+                if !pods_filtered {
+                    if let Err(err) = collect_node_metrics(client.clone(), &mut resources).await {
+                        warn!(?err);
+                    }
+                }
+                true
+            }
+            Err(err) => {
+                warn!(?err);
+                false
+            }
+        }
+    } else {
+        false
+    };
+    if let Some(nodes) = &selected_nodes {
+        retain_on_nodes(&mut resources, nodes);
+    }
+    Ok((resources, show_utilization))
+}
+
+/// Keep only the resources of `nodes` and of the pods scheduled on them
+/// (the resources of namespaces, like quotas, are kept)
+pub fn retain_on_nodes(resources: &mut Vec<Resource>, nodes: &HashSet<String>) {
+    resources.retain(|r| match (&r.location.node_name, &r.location.pod_name) {
+        (Some(node_name), _) => nodes.contains(node_name),
+        // a pending pod, or a pod known only by its metrics
+        (None, Some(_)) => false,
+        (None, None) => true,
+    });
+}
+
+#[instrument]
+pub async fn do_main(cli_opts: &CliOpts) -> Result<(), Error> {
+    cli_opts.validate()?;
+    if !cli_opts.watch {
+        return run_once(cli_opts).await;
+    }
+    watch(cli_opts).await
+}
+
+#[cfg(not(feature = "cli"))]
+async fn watch(cli_opts: &CliOpts) -> Result<(), Error> {
+    warn!("feature 'cli' not enabled, --watch is ignored");
+    run_once(cli_opts).await
+}
+
+/// Refresh the output every `--watch-interval`, until Ctrl-C
+#[cfg(feature = "cli")]
+async fn watch(cli_opts: &CliOpts) -> Result<(), Error> {
+    use std::io::IsTerminal;
+    let interval = std::time::Duration::from_secs(cli_opts.watch_interval);
+    // the other outputs are appended (eg: to be piped)
+    let terminal = std::io::stdout().is_terminal();
+    let clear = cli_opts.output == Output::table && terminal;
+    let mut first = true;
+    // only Ctrl-C stops watching: on error, the previous screen is kept until the next iteration
+    loop {
+        let iteration = async {
+            match collect_resources(cli_opts).await {
+                Ok((resources, show_utilization)) => {
+                    if clear && !first {
+                        // clear the terminal (and move the cursor to the top left)
+                        print!("\x1B[2J\x1B[H");
+                    }
+                    first = false;
+                    let rendered = render_output(
+                        &resources,
+                        show_utilization,
+                        cli_opts,
+                        &mut std::io::stdout(),
+                        terminal,
+                    );
+                    if let Err(err) = rendered {
+                        warn!(?err);
+                    }
+                }
+                Err(err) => warn!(
+                    ?err,
+                    "failed to collect the resources, retry after the interval"
+                ),
+            }
+            tokio::time::sleep(interval).await;
+        };
+        tokio::select! {
+            _ = iteration => {},
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    Ok(())
+}
+
+/// Collect the resources of the `context` (the current one by default), see `collect_from_cluster`,
+/// in `--context-timeout`
+pub async fn collect_from_context(
+    cli_opts: &CliOpts,
+    context: Option<String>,
+) -> Result<(Vec<Resource>, bool), Error> {
+    let client = new_client(cli_opts, &context).await?;
+    let collect = collect_from_cluster(client, cli_opts);
+    match cli_opts.context_timeout {
+        #[cfg(feature = "cli")]
+        Some(secs) => tokio::time::timeout(std::time::Duration::from_secs(secs), collect)
+            .await
+            .map_err(|_| Error::ContextTimeout {
+                context: context.unwrap_or_else(|| "current".to_string()),
+                secs,
+            })?,
+        #[cfg(not(feature = "cli"))]
+        Some(_) => {
+            warn!("feature 'cli' not enabled, --context-timeout is ignored");
+            collect.await
+        }
+        None => collect.await,
+    }
+}
+
+/// Collect the resources of every context of `--context` concurrently,
+/// each resource tagged with its context as `cluster_name`.
+/// The contexts that failed (eg: timed out) are skipped with a warning, unless all of them failed.
+pub async fn collect_from_contexts(cli_opts: &CliOpts) -> Result<(Vec<Resource>, bool), Error> {
+    let collects = cli_opts.context.iter().map(|context| async move {
+        let (mut resources, show_utilization) =
+            collect_from_context(cli_opts, Some(context.clone())).await?;
+        for resource in resources.iter_mut() {
+            resource.location.cluster_name = Some(context.clone());
+        }
+        Ok::<_, Error>((resources, show_utilization))
+    });
+    merge_contexts(&cli_opts.context, futures::future::join_all(collects).await)
+}
+
+/// Merge the resources collected from `contexts` (in the same order), skipping (with a warning)
+/// the contexts that failed, an error only if all of them failed
+fn merge_contexts(
+    contexts: &[String],
+    results: Vec<Result<(Vec<Resource>, bool), Error>>,
+) -> Result<(Vec<Resource>, bool), Error> {
+    let mut resources = vec![];
+    let mut show_utilization = false;
+    let mut last_error = None;
+    let all_failed = results.iter().all(|r| r.is_err());
+    for (context, result) in contexts.iter().zip(results) {
+        match result {
+            Ok((rsrcs, utilization)) => {
+                resources.extend(rsrcs);
+                show_utilization |= utilization;
+            }
+            Err(err) => {
+                warn!(%context, error = %err, "skip the context");
+                last_error = Some(err);
+            }
+        }
+    }
+    match last_error {
+        Some(err) if all_failed => Err(err),
+        _ => Ok((resources, show_utilization)),
+    }
+}
+
+/// Add the `cluster` level as first level, when the resources come from several clusters
+pub fn with_clusters(mut group_by: Vec<GroupBy>, resources: &[Resource]) -> Vec<GroupBy> {
+    if !group_by.contains(&GroupBy::cluster)
+        && resources.iter().any(|r| r.location.cluster_name.is_some())
+    {
+        group_by.insert(0, GroupBy::cluster);
+    }
+    group_by
+}
+
+/// Collect the resources and display them (once)
+pub async fn run_once(cli_opts: &CliOpts) -> Result<(), Error> {
+    use std::io::IsTerminal;
+    let (resources, show_utilization) = collect_resources(cli_opts).await?;
+    render_output(
+        &resources,
+        show_utilization,
+        cli_opts,
+        &mut std::io::stdout(),
+        std::io::stdout().is_terminal(),
+    )
+}
+
+/// Collect the resources (from the clusters or from `--snapshot-read`), saved to `--snapshot-write`
+/// if defined (and as the snapshot of the day with `--save`), returns the resources and if utilization is available
+pub async fn collect_resources(cli_opts: &CliOpts) -> Result<(Vec<Resource>, bool), Error> {
+    let (resources, show_utilization) = match &cli_opts.snapshot_read {
+        Some(path) => read_snapshot(path)?,
+        None if cli_opts.context.len() > 1 => collect_from_contexts(cli_opts).await?,
+        None => collect_from_context(cli_opts, cli_opts.context.first().cloned()).await?,
+    };
+    if let Some(path) = &cli_opts.snapshot_write {
+        write_snapshot(path, &resources)?;
+    }
+    if cli_opts.save && cli_opts.snapshot_read.is_none() {
+        save_history(
+            &history_dir()?,
+            &resources,
+            &default_context_name(cli_opts),
+            Local::now().date_naive(),
+        )?;
+    }
+    Ok((resources, show_utilization))
+}
+
+/// Display the resources (collected by `collect_resources`) as defined by `cli_opts` into `out`
+/// (with colors if `colorize`, eg: when `out` is a terminal), then check the thresholds
+pub fn render_output(
+    resources: &[Resource],
+    show_utilization: bool,
+    cli_opts: &CliOpts,
+    out: &mut dyn std::io::Write,
+    colorize: bool,
+) -> Result<(), Error> {
+    // updated by the namespace groups and the imbalances
+    let mut resources = resources.to_vec();
+    if cli_opts.detect_imbalances {
+        detect_imbalances(&mut resources, cli_opts.imbalance_threshold);
+    }
+    let mut group_by = with_clusters(
+        effective_group_by(&cli_opts.group_by, !cli_opts.namespace.is_empty()),
+        &resources,
+    );
+    if cli_opts.show_containers {
+        group_by = with_containers(group_by);
+    }
+
+    let display_options = DisplayOptions {
+        // only when collected
+        show_utilization,
+        colorize,
+        ..DisplayOptions::from(cli_opts)
+    };
+    let namespace_groups = match &cli_opts.namespace_resource_group {
+        Some(path) => NamespaceGroups::load(path)?,
+        None => NamespaceGroups::default(),
+    };
+    let resource_filter =
+        namespace_groups.apply_filtered(&mut resources, &ResourceFilter::from(cli_opts));
+    let before = match (&cli_opts.diff, cli_opts.historical_comparison) {
+        (Some(path), _) => Some(read_snapshot(path)?.0),
+        (None, Some(days)) => {
+            let contexts = if cli_opts.context.len() > 1 {
+                cli_opts.context.clone()
+            } else {
+                vec![default_context_name(cli_opts)]
+            };
+            let date = Local::now().date_naive() - chrono::Duration::days(i64::from(days));
+            Some(load_history(&history_dir()?, &contexts, date)?)
+        }
+        (None, None) => None,
+    };
+    if let Some(mut before) = before {
+        namespace_groups.apply_filtered(&mut before, &ResourceFilter::from(cli_opts));
+        table::display_diff_table(
+            &diff_qualifiers(
+                &make_qualifiers(&before, &group_by, &resource_filter),
+                &make_qualifiers(&resources, &group_by, &resource_filter),
+            ),
+            &display_options,
+            out,
+        )?;
+        return Ok(());
+    }
+    let mut res = make_qualifiers(&resources, &group_by, &resource_filter);
+    if cli_opts.show_terminating {
+        res.extend(make_virtual_group_qualifiers(
+            &resources,
+            &group_by,
+            &resource_filter,
+            "<terminating>",
+            |r| r.location.terminating,
+        ));
+    }
+    if cli_opts.show_evicting {
+        res.extend(make_virtual_group_qualifiers(
+            &resources,
+            &group_by,
+            &resource_filter,
+            "<evicting>",
+            |r| r.location.evicting,
+        ));
+    }
+    if cli_opts.include_pending {
+        res.extend(make_virtual_group_qualifiers(
+            &resources,
+            &group_by,
+            &resource_filter,
+            "<pending>",
+            |r| r.location.pending,
+        ));
+    }
+    if cli_opts.include_jobs {
+        res.extend(make_virtual_group_qualifiers(
+            &resources,
+            &group_by,
+            &resource_filter,
+            "<jobs>",
+            |r| r.location.is_job,
+        ));
+    }
+    if let Some(n) = cli_opts.top {
+        res = top_rows(res, &group_by, n, &cli_opts.sort_by);
+    }
+    sort_rows(&mut res, &cli_opts.sort_by, cli_opts.sort_desc);
+    let mut extra_columns = vec![];
+    if cli_opts.show_daemonset_overhead {
+        extra_columns.push(ExtraColumn::DaemonSetOverhead);
+    }
+    if cli_opts.show_kubelet_version {
+        extra_columns.push(ExtraColumn::KubeletVersion);
+    }
+    if cli_opts.show_restart_count {
+        extra_columns.push(ExtraColumn::RestartCount);
+    }
+    if cli_opts.show_pod_ip {
+        extra_columns.push(ExtraColumn::PodIp);
+    }
+    if cli_opts.show_missing_requests {
+        extra_columns.push(ExtraColumn::MissingRequests);
+    }
+    if cli_opts.show_evicting {
+        extra_columns.push(ExtraColumn::Evicting);
+    }
+    if cli_opts.show_capacity_breakdown {
+        extra_columns.push(ExtraColumn::CapacityBreakdown);
+    }
+    if cli_opts.detect_imbalances {
+        extra_columns.push(ExtraColumn::Imbalance);
+    }
+    if cli_opts.show_cpu_manager {
+        extra_columns.push(ExtraColumn::CpuManager);
+    }
+    if cli_opts.show_pod_conditions {
+        extra_columns.push(ExtraColumn::PodConditions);
+    }
+    for key in &cli_opts.show_namespace_labels {
+        extra_columns.push(ExtraColumn::NamespaceLabel(key.clone()));
+    }
+    if cli_opts.show_namespace_phase {
+        extra_columns.push(ExtraColumn::NamespaceTerminating);
+    }
+    if cli_opts.show_limit_ranges {
+        extra_columns.push(ExtraColumn::LimitRangeDefaults);
+    }
+    if cli_opts.include_pending {
+        extra_columns.push(ExtraColumn::Pending);
+    }
+    if let Some(path) = &cli_opts.cost_config {
+        extra_columns.push(ExtraColumn::Cost(
+            CostConfig::load(path)?,
+            cli_opts.cost_threshold,
+        ));
+    }
+    if cli_opts.show_quotas {
+        extra_columns.push(ExtraColumn::Quota);
+    }
+    if cli_opts.show_node_ip {
+        extra_columns.push(ExtraColumn::NodeIp);
+    }
+    if !cli_opts.require_label.is_empty() {
+        extra_columns.push(ExtraColumn::MissingLabels(cli_opts.require_label.clone()));
+    }
+    if cli_opts.show_runtime_class {
+        extra_columns.push(ExtraColumn::RuntimeClass);
+    }
+    if cli_opts.show_resource_version {
+        extra_columns.push(ExtraColumn::ResourceVersion);
+    }
+    if cli_opts.show_limits_ratio_warning {
+        extra_columns.push(ExtraColumn::InvalidLimits);
+    }
+    if cli_opts.show_cpu_pinning {
+        extra_columns.push(ExtraColumn::CpuPinning);
+    }
+    if cli_opts.show_psa_level {
+        extra_columns.push(ExtraColumn::PsaLevel);
+    }
+    if cli_opts.show_ephemeral_storage_pressure {
+        extra_columns.push(ExtraColumn::EphemeralStoragePressure(
+            cli_opts.ephemeral_storage_threshold,
+        ));
+    }
+    if cli_opts.show_managed_by {
+        extra_columns.push(ExtraColumn::ManagedBy);
+    }
+    let extra_values = make_extra_columns(&resources, &group_by, &resource_filter, &extra_columns);
+    let color_theme = match &cli_opts.color_theme {
+        Some(path) => ColorTheme::load(path)?,
+        None => ColorTheme::default(),
+    };
+    let quota_buffer = cli_opts
+        .quota_buffer_pct
+        .map(|pct| 1.0 + pct / 100.0)
+        .unwrap_or(cli_opts.quota_buffer);
+    if let Some(path) = &cli_opts.generate_resource_quota {
+        let manifests = resource_quota::format_as_resource_quotas(
+            &make_qualifiers(
+                &resources,
+                &[GroupBy::namespace, GroupBy::resource],
+                &resource_filter,
+            ),
+            quota_buffer,
+        );
+        if path.as_os_str() == "-" {
+            write_output(out, &manifests)?;
+        } else {
+            std::fs::write(path, manifests).map_err(|source| Error::WriteFileError {
+                path: path.clone(),
+                source,
+            })?;
+        }
+        return Ok(());
+    }
+    match &cli_opts.output {
+        Output::table => table::display_with_prettytable(
+            &res,
+            &color_theme,
+            &extra_columns,
+            &extra_values,
+            &group_by,
+            &display_options,
+            out,
+        )?,
+        Output::csv => dsv::display_as_csv(&res, &group_by, false, &display_options, out)?,
+        Output::csv_with_schema => {
+            dsv::display_as_csv(&res, &group_by, true, &display_options, out)?
+        }
+        Output::tsv => dsv::display_as_dsv(&res, &group_by, false, &display_options, '\t', out)?,
+        Output::json => json::display_as_json(&res, out)?,
+        Output::sftp => sftp::upload_with_sftp(&res, &group_by, cli_opts, &display_options)?,
+        Output::yaml => yaml::display_as_yaml(&res, out)?,
+        Output::ndyaml => yaml::display_as_ndyaml(&res, out)?,
+        Output::markdown => markdown::display_as_markdown(
+            &res,
+            &extra_columns,
+            &extra_values,
+            &display_options,
+            out,
+        )?,
+        Output::statsd => statsd::display_as_statsd(&res, &group_by, out)?,
+        Output::prometheus => prometheus::display_as_prometheus_metrics(&res, &group_by, out)?,
+        Output::datadog_events => datadog::display_as_datadog_events(
+            &res,
+            &group_by,
+            cli_opts.threshold_request,
+            cli_opts.threshold_limit,
+            out,
+        )?,
+        Output::gcp_monitoring => gcp::display_as_gcp_monitoring(&res, &group_by, cli_opts, out)?,
+        Output::jira => jira::create_jira_issues(&res, &group_by, cli_opts, out)?,
+        Output::terraform => resource_quota::display_as_terraform(
+            &make_qualifiers(
+                &resources,
+                &[GroupBy::namespace, GroupBy::resource],
+                &resource_filter,
+            ),
+            quota_buffer,
+            out,
+        )?,
+        Output::ansible => ansible::display_as_ansible(
+            &make_qualifiers(
+                &resources,
+                &[GroupBy::node, GroupBy::resource],
+                &resource_filter,
+            ),
+            out,
+        )?,
+        Output::kube_score => kube_score::display_as_kube_score(
+            &make_qualifiers(
+                &resources,
+                &[GroupBy::namespace, GroupBy::pod, GroupBy::resource],
+                &resource_filter,
+            ),
+            out,
+        )?,
+    }
+    check_thresholds(&res, cli_opts.threshold_request, cli_opts.threshold_limit)
+}
+
+/// Write (the formatted rows) to the output
+fn write_output(out: &mut dyn std::io::Write, content: &str) -> Result<(), Error> {
+    out.write_all(content.as_bytes())
+        .map_err(|source| Error::WriteOutputError { source })
+}
+
+/// Truncate `name` to `max_width` characters, the last one replaced by `…`
+pub fn truncate_name(name: &str, max_width: usize) -> String {
+    if name.chars().count() > max_width {
+        let mut truncated = name
+            .chars()
+            .take(max_width.saturating_sub(1))
+            .collect::<String>();
+        truncated.push('…');
+        truncated
+    } else {
+        name.to_string()
+    }
+}
+
+fn is_empty(oqty: &Option<Qty>) -> bool {
+    match oqty {
+        Some(qty) => qty.is_zero(),
+        None => true,
+    }
+}
+
+/// true when the row has no utilization, and zero requested, limit (and allocatable)
+fn is_full_zero(oqtys: &Option<QtyByQualifier>, hide_allocatable: bool) -> bool {
+    oqtys
+        .as_ref()
+        .map(|x| {
+            x.utilization.is_none()
+                && is_empty(&x.requested)
+                && is_empty(&x.limit)
+                && (hide_allocatable || is_empty(&x.allocatable))
+        })
+        .unwrap_or(false)
+}
+
+/// The text of a quantity, with its percentage of `o100` if defined (alone if `percent_only`)
+fn format_cell_text(
+    oqty: &Option<Qty>,
+    o100: &Option<Qty>,
+    precision: usize,
+    percent_only: bool,
+) -> String {
+    match oqty {
+        None => "__".to_string(),
+        Some(ref qty) => match o100 {
+            None => format!("{:.*}", precision, qty.adjust_scale()),
+            Some(q100) if percent_only => format!("({:.0}%)", qty.calc_percentage(q100)),
+            Some(q100) => format!(
+                "({:.0}%) {:.*}",
+                qty.calc_percentage(q100),
+                precision,
+                qty.adjust_scale()
+            ),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn test_accept_resource() {
+        assert_eq!(accept_resource("cpu", &vec![], false), true);
+        assert_eq!(accept_resource("cpu", &vec!["c".to_string()], false), true);
+        assert_eq!(
+            accept_resource("cpu", &vec!["cpu".to_string()], false),
+            true
+        );
+        assert_eq!(
+            accept_resource("cpu", &vec!["cpu3".to_string()], false),
+            false
+        );
+        assert_eq!(
+            accept_resource("gpu", &vec!["gpu".to_string()], false),
+            true
+        );
+        assert_eq!(
+            accept_resource("nvidia.com/gpu", &vec!["gpu".to_string()], false),
+            true
+        );
+    }
+
+    #[test]
+    fn test_accept_resource_exact() {
+        assert!(accept_resource("cpu", &[], true));
+        assert!(!accept_resource("cpu", &["pu".to_string()], true));
+        assert!(accept_resource("cpu", &["cpu".to_string()], true));
+        assert!(!accept_resource(
+            "nvidia.com/gpu",
+            &["gpu".to_string()],
+            true
+        ));
+        // a qualified name is always matched exactly
+        assert!(accept_resource(
+            "nvidia.com/gpu",
+            &["nvidia.com/gpu".to_string()],
+            false
+        ));
+        assert!(!accept_resource(
+            "nvidia.com/gpu-shared",
+            &["nvidia.com/gpu".to_string()],
+            false
+        ));
+        assert!(reject_resource("pods", &["pods".to_string()], true));
+        assert!(!reject_resource(
+            "hugepages-2Mi",
+            &["pages".to_string()],
+            true
+        ));
+        assert!(reject_resource(
+            "hugepages-2Mi",
+            &["pages".to_string()],
+            false
+        ));
+    }
+
+    #[test]
+    fn test_history() {
+        let dir = std::env::temp_dir().join(format!("history-{}", std::process::id()));
+        let date = NaiveDate::from_ymd_opt(2022, 3, 1).unwrap();
+        assert_eq!(
+            history_path(&dir, "ctx1", date),
+            dir.join("ctx1").join("2022-03-01.json")
+        );
+        let resource = |cluster_name: Option<&str>| {
+            new_resource(
+                "cpu",
+                ResourceQualifier::Requested,
+                "1",
+                Location {
+                    cluster_name: cluster_name.map(|x| x.to_string()),
+                    ..Location::default()
+                },
+            )
+        };
+        save_history(&dir, &[resource(None)], "ctx1", date).unwrap();
+        save_history(
+            &dir,
+            &[resource(Some("ctx2")), resource(Some("ctx3"))],
+            "ctx1",
+            date,
+        )
+        .unwrap();
+        assert_eq!(
+            load_history(&dir, &["ctx1".to_string()], date)
+                .unwrap()
+                .len(),
+            1
+        );
+        let loaded = load_history(&dir, &["ctx2".to_string(), "ctx3".to_string()], date).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert!(load_history(&dir, &["ctx1".to_string()], date).is_err());
+    }
+
+    #[test]
+    fn test_diff_qualifiers() {
+        let before = vec![row(&["cpu"], "2", None), row(&["cpu", "pod1"], "2", None)];
+        let after = vec![
+            row(&["cpu"], "1500m", None),
+            row(&["cpu", "pod2"], "1500m", None),
+        ];
+        let diff = diff_qualifiers(&before, &after);
+        let keys = diff.iter().map(|(k, _, _)| k.join("/")).collect::<Vec<_>>();
+        assert_eq!(keys, vec!["cpu", "cpu/pod1", "cpu/pod2"]);
+        assert!(diff[1].1.is_some() && diff[1].2.is_none());
+        assert!(diff[2].1.is_none() && diff[2].2.is_some());
+
+        let requested = |q: &Option<QtyByQualifier>| q.as_ref().and_then(|q| q.requested.clone());
+        assert_eq!(
+            format_delta(&requested(&diff[0].1), &requested(&diff[0].2)),
+            ("-500.0m".to_string(), std::cmp::Ordering::Less)
+        );
+        assert_eq!(
+            format_delta(&requested(&diff[2].1), &requested(&diff[2].2)),
+            ("+1.5".to_string(), std::cmp::Ordering::Greater)
+        );
+        assert_eq!(
+            format_delta(&qty("1"), &qty("1000m")),
+            ("".to_string(), std::cmp::Ordering::Equal)
+        );
+    }
+
+    #[test]
+    fn test_format_pct_change() {
+        assert_eq!(format_pct_change(&qty("1Gi"), &qty("1536Mi")), "+50%");
+        assert_eq!(format_pct_change(&qty("4"), &qty("3")), "-25%");
+        assert_eq!(format_pct_change(&qty("4"), &qty("4")), "");
+        assert_eq!(format_pct_change(&None, &qty("4")), "");
+    }
+
+    #[test]
+    fn test_daemonset_overhead_on_node_rows() {
+        let node = node_location("node1");
+        let ds_pod = Location {
+            pod_name: Some("ds-pod".to_string()),
+            is_daemonset: true,
+            ..node.clone()
+        };
+        let app_pod = Location {
+            pod_name: Some("app-pod".to_string()),
+            ..node.clone()
+        };
+        let mut resources = vec![
+            new_resource("cpu", ResourceQualifier::Allocatable, "4", node.clone()),
+            new_resource("cpu", ResourceQualifier::Requested, "1", ds_pod.clone()),
+            new_resource("cpu", ResourceQualifier::Requested, "2", app_pod),
+        ];
+        let group_by = vec![GroupBy::resource, GroupBy::node, GroupBy::pod];
+        let values = make_extra_columns(
+            &resources,
+            &group_by,
+            &ResourceFilter::default(),
+            &[ExtraColumn::DaemonSetOverhead],
+        );
+        let node_key = key(&["cpu", "node1"]);
+        assert_eq!(values[&node_key], vec![Some("(25%) 1.0".to_string())]);
+        let resource_key = key(&["cpu"]);
+        assert_eq!(values[&resource_key], vec![None]);
+
+        // above the resource level, by kind
+        resources.push(new_resource(
+            "memory",
+            ResourceQualifier::Allocatable,
+            "8Gi",
+            node,
+        ));
+        resources.push(new_resource(
+            "memory",
+            ResourceQualifier::Requested,
+            "2Gi",
+            ds_pod,
+        ));
+        let values = make_extra_columns(
+            &resources,
+            &[GroupBy::node, GroupBy::resource],
+            &ResourceFilter::default(),
+            &[ExtraColumn::DaemonSetOverhead],
+        );
+        assert_eq!(
+            values[&vec!["node1".to_string()]],
+            vec![Some("cpu: (25%) 1.0, memory: (25%) 2.0Gi".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_calc_free_uses_requested_when_over_limit() {
+        let qtys = QtyByQualifier {
+            requested: qty("3"),
+            limit: qty("2"),
+            allocatable: qty("10"),
+            ..QtyByQualifier::default()
+        };
+        assert_eq!(qtys.calc_free(), qty("7"));
+    }
+
+    #[test]
+    fn test_calc_free_uses_limit_when_over_requested() {
+        let qtys = QtyByQualifier {
+            requested: qty("2"),
+            limit: qty("4"),
+            allocatable: qty("10"),
+            ..QtyByQualifier::default()
+        };
+        assert_eq!(qtys.calc_free(), qty("6"));
+    }
+
+    #[test]
+    fn test_calc_free_is_not_negative() {
+        let qtys = QtyByQualifier {
+            requested: qty("2"),
+            limit: qty("12"),
+            allocatable: qty("10"),
+            ..QtyByQualifier::default()
+        };
+        assert_eq!(qtys.calc_free(), Some(Qty::default()));
+    }
+
+    #[test]
+    fn test_calc_free_without_allocatable() {
+        let qtys = QtyByQualifier {
+            requested: qty("2"),
+            limit: qty("4"),
+            ..QtyByQualifier::default()
+        };
+        assert_eq!(qtys.calc_free(), None);
+    }
+
+    #[test]
+    fn test_make_virtual_group_qualifiers() {
+        let terminating_pod = Location {
+            node_name: Some("node1".to_string()),
+            pod_name: Some("pod1".to_string()),
+            terminating: true,
+            ..Location::default()
+        };
+        let running_pod = Location {
+            pod_name: Some("pod2".to_string()),
+            terminating: false,
+            ..terminating_pod.clone()
+        };
+        let resources = vec![
+            new_resource("cpu", ResourceQualifier::Requested, "1", terminating_pod),
+            new_resource("cpu", ResourceQualifier::Requested, "2", running_pod),
+        ];
+        let group_by = vec![GroupBy::resource, GroupBy::node, GroupBy::pod];
+        let rows = make_virtual_group_qualifiers(
+            &resources,
+            &group_by,
+            &ResourceFilter::default(),
+            "<terminating>",
+            |r| r.location.terminating,
+        );
+        assert_eq!(rows.len(), 1);
+        assert_eq!(
+            rows[0].0,
+            vec!["cpu".to_string(), "<terminating>".to_string()]
+        );
+        assert_eq!(
+            rows[0].1.as_ref().and_then(|q| q.requested.clone()),
+            qty("1")
+        );
+    }
+
+    #[test]
+    fn test_add_qty_by_qualifier() {
+        let lhs = QtyByQualifier {
+            requested: qty("1"),
+            limit: qty("2"),
+            ..QtyByQualifier::default()
+        };
+        let rhs = QtyByQualifier {
+            requested: qty("500m"),
+            allocatable: qty("4"),
+            ..QtyByQualifier::default()
+        };
+        let sum = lhs + &rhs;
+        assert_eq!(sum.requested, qty("1500m"));
+        assert_eq!(sum.limit, qty("2"));
+        assert_eq!(sum.allocatable, qty("4"));
+        assert_eq!(sum.utilization, None);
+    }
+
+    #[test]
+    fn test_effective_group_by() {
+        assert_eq!(
+            effective_group_by(&[], false),
+            vec![GroupBy::resource, GroupBy::node, GroupBy::pod]
+        );
+        assert_eq!(
+            effective_group_by(&[GroupBy::namespace, GroupBy::pod], false),
+            vec![GroupBy::resource, GroupBy::namespace, GroupBy::pod]
+        );
+        assert_eq!(
+            effective_group_by(
+                &[GroupBy::node, GroupBy::resource, GroupBy::resource],
+                false
+            ),
+            vec![GroupBy::node, GroupBy::resource]
+        );
+        // with `--namespace`
+        assert_eq!(
+            effective_group_by(&[], true),
+            vec![GroupBy::namespace, GroupBy::resource, GroupBy::pod]
+        );
+        assert_eq!(
+            effective_group_by(&[GroupBy::node, GroupBy::pod], true),
+            vec![GroupBy::resource, GroupBy::node, GroupBy::pod]
+        );
+    }
+
+    #[test]
+    fn test_with_containers() {
+        assert_eq!(
+            with_containers(effective_group_by(&[], false)),
+            vec![
+                GroupBy::resource,
+                GroupBy::node,
+                GroupBy::pod,
+                GroupBy::container
+            ]
+        );
+        assert_eq!(
+            with_containers(vec![GroupBy::resource, GroupBy::pod, GroupBy::node]),
+            vec![
+                GroupBy::resource,
+                GroupBy::pod,
+                GroupBy::container,
+                GroupBy::node
+            ]
+        );
+        assert_eq!(
+            with_containers(vec![GroupBy::container, GroupBy::resource]),
+            vec![GroupBy::container, GroupBy::resource]
+        );
+    }
+
+    #[test]
+    fn test_with_clusters() {
+        let node = |cluster: Option<&str>| {
+            new_resource(
+                "cpu",
+                ResourceQualifier::Allocatable,
+                "1",
+                Location {
+                    cluster_name: cluster.map(|x| x.to_string()),
+                    node_name: Some("node1".to_string()),
+                    ..Location::default()
+                },
+            )
+        };
+        let single = vec![node(None)];
+        assert_eq!(
+            with_clusters(effective_group_by(&[], false), &single),
+            effective_group_by(&[], false)
+        );
+        let multi = vec![node(Some("ctx1")), node(Some("ctx2"))];
+        let group_by = with_clusters(effective_group_by(&[], false), &multi);
+        assert_eq!(
+            group_by,
+            vec![
+                GroupBy::cluster,
+                GroupBy::resource,
+                GroupBy::node,
+                GroupBy::pod
+            ]
+        );
+        let keys = make_qualifiers(&multi, &group_by, &ResourceFilter::default())
+            .into_iter()
+            .map(|(k, _)| k.join("/"))
+            .collect::<Vec<_>>();
+        assert!(keys.contains(&"ctx1/cpu/node1".to_string()));
+        assert!(keys.contains(&"ctx2/cpu/node1".to_string()));
+    }
+
+    #[test]
+    fn test_multiple_contexts_opts() {
+        use clap::Parser;
+        let cli_opts = CliOpts::parse_from(["view-allocations", "--context", "ctx1,ctx2"]);
+        assert_eq!(cli_opts.context, vec!["ctx1", "ctx2"]);
+        let cli_opts = CliOpts::parse_from(["view-allocations"]);
+        assert!(cli_opts.context.is_empty());
+    }
+
+    #[test]
+    fn test_cli_opts_validate() {
+        use clap::Parser;
+        let validate = |args: &[&str]| {
+            CliOpts::parse_from(std::iter::once("view-allocations").chain(args.iter().copied()))
+                .validate()
+        };
+        assert!(validate(&[]).is_ok());
+        assert!(validate(&["-u", "--sort-by", "utilization"]).is_ok());
+        assert!(matches!(
+            validate(&["--sort-by", "utilization"]),
+            Err(Error::MissingOption { .. })
+        ));
+        assert!(matches!(
+            validate(&["--color-by", "utilization-pct"]),
+            Err(Error::MissingOption { .. })
+        ));
+        assert!(validate(&["--color-by", "utilization-pct", "--snapshot-read", "s.json"]).is_ok());
+        assert!(matches!(
+            validate(&["--cost-threshold", "2"]),
+            Err(Error::MissingOption { .. })
+        ));
+        assert!(matches!(
+            validate(&["--server", "https://localhost:6443", "--context", "ctx1"]),
+            Err(Error::IncompatibleOptions { .. })
+        ));
+        assert_eq!(
+            validate(&["--color-warn", "90", "--color-crit", "80"])
+                .unwrap_err()
+                .to_string(),
+            "Incompatible options: --color-warn (90) is over --color-crit (80)"
+        );
+        assert!(validate(&["--threshold-request", "0.8", "--threshold-limit", "1.5"]).is_ok());
+        assert_eq!(
+            validate(&["--threshold-request", "1.2"])
+                .unwrap_err()
+                .to_string(),
+            "Invalid value of --threshold-request: 1.2 is over 1.0"
+        );
+        assert!(matches!(
+            validate(&["--threshold-limit=-0.5"]),
+            Err(Error::InvalidOption { .. })
+        ));
+        assert!(validate(&["--watch", "-o", "jira"]).is_ok());
+        assert!(matches!(
+            validate(&["--watch", "-o", "jira", "--jira-url", "https://example.net"]),
+            Err(Error::IncompatibleOptions { .. })
+        ));
+    }
+
+    #[test]
+    fn test_display_options_from_cli_opts() {
+        use clap::Parser;
+        let options = DisplayOptions::from(&CliOpts::parse_from(["view-allocations"]));
+        let defaults = DisplayOptions::default();
+        assert_eq!(options.filter_full_zero, defaults.filter_full_zero);
+        assert_eq!(options.color_thresholds, defaults.color_thresholds);
+        assert_eq!(options.max_name_width, defaults.max_name_width);
+        let options = DisplayOptions::from(&CliOpts::parse_from([
+            "view-allocations",
+            "--show-zero",
+            "--color-warn",
+            "70",
+            "--hide-column",
+            "free",
+        ]));
+        assert!(!options.filter_full_zero);
+        assert_eq!(options.color_thresholds, (70, 95));
+        assert!(options.hidden_columns.contains(&Column::free));
+    }
+
+    #[test]
+    fn test_set_client_certificate_on_user_of_current_context() {
+        let mut kubeconfig =
+            make_kubeconfig("https://localhost:6443", None, false, Default::default());
+        set_client_certificate(&mut kubeconfig, &None, "/tmp/cert.pem", "/tmp/key.pem");
+        let auth_info = &kubeconfig.auth_infos[0].auth_info;
+        assert_eq!(
+            auth_info.client_certificate.as_deref(),
+            Some("/tmp/cert.pem")
+        );
+        assert_eq!(auth_info.client_key.as_deref(), Some("/tmp/key.pem"));
+    }
+
+    #[test]
+    fn test_server_certificate_authority() {
+        use clap::Parser;
+        let cli_opts = CliOpts::parse_from([
+            "view-allocations",
+            "--server",
+            "https://localhost:6443",
+            "--certificate-authority",
+            "/tmp/ca.crt",
+        ]);
+        let ca = server_certificate_authority(&cli_opts);
+        assert_eq!(ca.as_deref(), Some("/tmp/ca.crt"));
+        let kubeconfig = make_kubeconfig("https://localhost:6443", ca, false, Default::default());
+        let cluster = &kubeconfig.clusters[0].cluster;
+        assert_eq!(
+            cluster.certificate_authority.as_deref(),
+            Some("/tmp/ca.crt")
+        );
+        assert_eq!(cluster.insecure_skip_tls_verify, None);
+
+        let cli_opts = CliOpts::parse_from([
+            "view-allocations",
+            "--server",
+            "https://localhost:6443",
+            "--insecure-skip-tls-verify",
+        ]);
+        assert_eq!(server_certificate_authority(&cli_opts), None);
+        assert!(CliOpts::try_parse_from([
+            "view-allocations",
+            "--server",
+            "https://localhost:6443",
+            "--insecure-skip-tls-verify",
+            "--certificate-authority",
+            "/tmp/ca.crt",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_detect_imbalances() {
+        let node = node_location("node1");
+        let pod = Location {
+            pod_name: Some("pod1".to_string()),
+            ..node.clone()
+        };
+        let mut resources = vec![
+            new_resource("cpu", ResourceQualifier::Allocatable, "10", node.clone()),
+            new_resource("memory", ResourceQualifier::Allocatable, "10Gi", node),
+            new_resource("cpu", ResourceQualifier::Requested, "9", pod.clone()),
+            new_resource("memory", ResourceQualifier::Requested, "2Gi", pod),
+        ];
+        detect_imbalances(&mut resources, 80.0);
+        assert!(resources.iter().all(|r| r.location.imbalance.is_none()));
+        detect_imbalances(&mut resources, 30.0);
+        assert_eq!(
+            resources[0].location.imbalance.as_deref(),
+            Some("⚠ CPU/memory imbalanced (CPU: 90%, Mem: 20%)")
+        );
+        assert!(resources[2].location.imbalance.is_none());
+    }
+
+    #[test]
+    fn test_location_merge() {
+        let pod = pod_location("ns1", "pod1");
+        let scheduled = Location {
+            node_name: Some("node1".to_string()),
+            restart_count: 3,
+            terminating: true,
+            ..pod.clone()
+        };
+        let merged = pod.merge(&scheduled).unwrap();
+        assert_eq!(merged.node_name.as_deref(), Some("node1"));
+        assert_eq!(merged.pod_name.as_deref(), Some("pod1"));
+        assert_eq!(merged.restart_count, 3);
+        assert!(merged.terminating);
+
+        let elsewhere = Location {
+            node_name: Some("node2".to_string()),
+            ..pod
+        };
+        assert!(matches!(
+            scheduled.merge(&elsewhere),
+            Err(Error::LocationConflict { field, .. }) if field == "node_name"
+        ));
+    }
+
+    #[test]
+    fn test_group_by_from_str() {
+        assert_eq!(GroupBy::from_str("Node"), Ok(GroupBy::node));
+        let g = GroupBy::from_str("pod_annotation:cost-center").unwrap();
+        assert_eq!(g, GroupBy::pod_annotation("cost-center".to_string()));
+        assert_eq!(g.to_string(), "pod_annotation:cost-center");
+        assert!(GroupBy::from_str("pod_annotation:").is_err());
+        let g = GroupBy::from_str("label=team").unwrap();
+        assert_eq!(g, GroupBy::label("team".to_string()));
+        assert_eq!(g.to_string(), "label=team");
+        assert!(GroupBy::from_str("label=").is_err());
+        assert_eq!(GroupBy::from_str("owner"), Ok(GroupBy::owner));
+        assert_eq!(
+            GroupBy::from_str("container_resource_limit_tier"),
+            Ok(GroupBy::limit_tier)
+        );
+        assert!(GroupBy::from_str("controller").is_err());
+    }
+
+    #[test]
+    fn test_resolve_owners() {
+        let pod: Pod = serde_json::from_value(serde_json::json!({
+            "metadata": {
+                "name": "web-5d8f-x2x", "namespace": "ns1",
+                "ownerReferences": [{
+                    "apiVersion": "apps/v1", "kind": "ReplicaSet", "name": "web-5d8f",
+                    "uid": "1", "controller": true
+                }]
+            }
+        }))
+        .unwrap();
+        assert_eq!(owner_of(&pod), Some("ReplicaSet/web-5d8f".to_string()));
+        let replicaset = |uid: &str, deployment: &str| -> ReplicaSet {
+            serde_json::from_value(serde_json::json!({
+                "metadata": {
+                    "name": "web-5d8f", "namespace": "ns1", "uid": uid,
+                    "ownerReferences": [{
+                        "apiVersion": "apps/v1", "kind": "Deployment", "name": deployment,
+                        "uid": format!("{}-owner", uid), "controller": true
+                    }]
+                }
+            }))
+            .unwrap()
+        };
+        // the same name (eg: recreated), another uid
+        let replicasets = [replicaset("1", "web"), replicaset("3", "other")];
+        let location = |namespace: &str| Location {
+            namespace: Some(namespace.to_string()),
+            pod_name: Some("web-5d8f-x2x".to_string()),
+            owner_name: owner_of(&pod),
+            owner_uid: owner_uid_of(&pod),
+            ..Location::default()
+        };
+        let mut resources = vec![
+            new_resource("cpu", ResourceQualifier::Requested, "1", location("ns1")),
+            new_resource("cpu", ResourceQualifier::Requested, "1", location("ns2")),
+        ];
+        resolve_owners_with(
+            &mut resources,
+            &owners_by_object(replicasets.iter().map(|rs| &rs.metadata)),
+        );
+        assert_eq!(
+            resources[0].location.owner_name.as_deref(),
+            Some("Deployment/web")
+        );
+        assert_eq!(resources[0].location.owner_uid.as_deref(), Some("1-owner"));
+        // a ReplicaSet with the same name in another namespace
+        assert_eq!(
+            resources[1].location.owner_name.as_deref(),
+            Some("ReplicaSet/web-5d8f")
+        );
+        assert_eq!(
+            GroupBy::top_level_owner.to_fct()(&resources[0]).as_deref(),
+            Some("web")
+        );
+        let mut naked_pod = resources[0].clone();
+        naked_pod.location.owner_name = None;
+        assert_eq!(
+            GroupBy::top_level_owner.to_fct()(&naked_pod).as_deref(),
+            Some("web-5d8f-x2x")
+        );
+    }
+
+    #[test]
+    fn test_resolve_owners_chain() {
+        let owner = |name: &str, uid: &str| (name.to_string(), uid.to_string());
+        let owners = HashMap::from([
+            (
+                ("ns1".to_string(), "uid-job".to_string()),
+                owner("CronJob/backup", "uid-cronjob"),
+            ),
+            (
+                ("ns1".to_string(), "uid-cronjob".to_string()),
+                owner("Backup/nightly", "uid-backup"),
+            ),
+        ]);
+        let mut resources = vec![new_resource(
+            "cpu",
+            ResourceQualifier::Requested,
+            "1",
+            Location {
+                owner_name: Some("Job/backup-123".to_string()),
+                owner_uid: Some("uid-job".to_string()),
+                ..pod_location("ns1", "backup-123-abc")
+            },
+        )];
+        resolve_owners_with(&mut resources, &owners);
+        assert_eq!(
+            resources[0].location.owner_name.as_deref(),
+            Some("Backup/nightly")
+        );
+    }
+
+    #[test]
+    fn test_group_by_pod_annotation() {
+        let mut location = Location {
+            pod_name: Some("pod1".to_string()),
+            ..Location::default()
+        };
+        location
+            .annotations
+            .insert("cost-center".to_string(), "R&D / team 1".to_string());
+        let resource = new_resource("cpu", ResourceQualifier::Requested, "1", location);
+        let fct = GroupBy::pod_annotation("cost-center".to_string());
+        assert_eq!(fct.to_fct()(&resource).as_deref(), Some("R-D---team-1"));
+        let fct = GroupBy::pod_annotation("team".to_string());
+        assert_eq!(fct.to_fct()(&resource), None);
+        let mut node = node_location("node1");
+        node.labels.insert(
+            "topology.kubernetes.io/zone".to_string(),
+            "eu-west-1a".to_string(),
+        );
+        let resource = new_resource("cpu", ResourceQualifier::Allocatable, "4", node);
+        let fct = GroupBy::label("topology.kubernetes.io/zone".to_string());
+        assert_eq!(fct.to_fct()(&resource).as_deref(), Some("eu-west-1a"));
+    }
+
+    #[tokio::test]
+    async fn test_extract_allocatable_from_nodes_max_pods() {
+        let nodes: ObjectList<Node> = serde_json::from_value(serde_json::json!({
+            "metadata": {},
+            "items": [{
+                "metadata": { "name": "node1" },
+                "status": {
+                    "capacity": { "pods": "110", "memory": "8Gi" },
+                    "allocatable": { "pods": "110", "memory": "7Gi" }
+                }
+            }]
+        }))
+        .unwrap();
+        let mut resources = vec![];
+        extract_allocatable_from_nodes(nodes, &mut resources)
+            .await
+            .unwrap();
+        let pods = resources
+            .iter()
+            .find(|r| r.kind == "pods" && matches!(r.qualifier, ResourceQualifier::Allocatable))
+            .unwrap();
+        // a count of pods, not 110 bytes (or a cpu quantity)
+        assert_eq!(pods.quantity, Qty::from_str("110").unwrap());
+        assert_eq!(f64::from(&pods.quantity), 110.0);
+        assert_eq!(pods.quantity.adjust_scale().to_string(), "110.0");
+        let reserved = resources
+            .iter()
+            .find(|r| r.kind == "pods" && matches!(r.qualifier, ResourceQualifier::SystemReserved))
+            .unwrap();
+        assert!(reserved.quantity.is_zero());
+    }
+
+    #[tokio::test]
+    async fn test_extract_utilizations_from_node_metrics() {
+        let node_metrics: ObjectList<metrics::NodeMetrics> =
+            serde_json::from_value(serde_json::json!({
+                "metadata": {},
+                "items": [{
+                    "metadata": { "name": "node1" },
+                    "usage": { "cpu": "1500m", "memory": "2Gi" },
+                    "timestamp": "2021-01-01T00:00:00Z",
+                    "window": "30s"
+                }]
+            }))
+            .unwrap();
+        let pod = Location {
+            node_name: Some("node1".to_string()),
+            ..pod_location("ns1", "pod1")
+        };
+        let mut resources = vec![new_resource(
+            "cpu",
+            ResourceQualifier::Utilization,
+            "500m",
+            pod,
+        )];
+        extract_utilizations_from_node_metrics(node_metrics, &mut resources)
+            .await
+            .unwrap();
+        assert_eq!(resources.len(), 3);
+        assert!(resources[1..]
+            .iter()
+            .all(|r| r.location.node_name.as_deref() == Some("node1")
+                && r.location.pod_name.is_none()
+                && r.location.namespace.is_none()));
+
+        let cpu_utilization = |resources: &[Resource], group_by: &[GroupBy], key: &[&str]| {
+            make_qualifiers(resources, group_by, &ResourceFilter::default())
+                .into_iter()
+                .find(|(k, _)| k == key)
+                .and_then(|(_, qtys)| qtys)
+                .and_then(|qtys| qtys.utilization)
+                .map(|qty| f64::from(&qty))
+        };
+        let group_by = [GroupBy::resource, GroupBy::node, GroupBy::pod];
+        // the node metrics replace the sum of the pods on the node row
+        assert_eq!(
+            cpu_utilization(&resources, &group_by, &["cpu", "node1"]),
+            Some(1.5)
+        );
+        assert_eq!(
+            cpu_utilization(&resources, &group_by, &["cpu", "node1", "pod1"]),
+            Some(0.5)
+        );
+
+        // over several nodes, the pods are summed (the nodes without pods are not counted)
+        resources.push(new_resource(
+            "cpu",
+            ResourceQualifier::Utilization,
+            "4",
+            node_location("node2"),
+        ));
+        assert_eq!(cpu_utilization(&resources, &group_by, &["cpu"]), Some(0.5));
+        assert_eq!(
+            cpu_utilization(&resources, &group_by, &["cpu", "node2"]),
+            Some(4.0)
+        );
+        let group_by = [GroupBy::resource, GroupBy::namespace];
+        assert_eq!(
+            cpu_utilization(&resources, &group_by, &["cpu", "ns1"]),
+            Some(0.5)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extract_allocatable_from_pods_by_container() {
+        let pods = || -> ObjectList<Pod> {
+            serde_json::from_value(serde_json::json!({
+                "metadata": {},
+                "items": [{
+                    "metadata": { "name": "pod1", "namespace": "ns1" },
+                    "spec": {
+                        "nodeName": "node1",
+                        "containers": [
+                            { "name": "app", "resources": { "requests": { "cpu": "1" } } },
+                            { "name": "sidecar", "resources": { "requests": { "cpu": "500m" } } }
+                        ],
+                        "initContainers": [
+                            { "name": "init", "resources": { "requests": { "cpu": "2" } } }
+                        ]
+                    },
+                    "status": { "phase": "Running" }
+                }]
+            }))
+            .unwrap()
+        };
+        let mut resources = vec![];
+        extract_allocatable_from_pods(pods(), &mut resources, false, false, true)
+            .await
+            .unwrap();
+        let by_container = make_qualifiers(
+            &resources,
+            &[GroupBy::namespace, GroupBy::pod, GroupBy::container],
+            &ResourceFilter {
+                resource_names: vec!["cpu".to_string()],
+                ..ResourceFilter::default()
+            },
+        );
+        let requested = |k: &[&str]| {
+            by_container
+                .iter()
+                .find(|(key, _)| key == k)
+                .and_then(|(_, qtys)| qtys.as_ref())
+                .and_then(|qtys| qtys.requested.as_ref())
+                .map(f64::from)
+        };
+        // the pod requests the max of (sum of containers, initContainers)
+        assert_eq!(requested(&["ns1", "pod1"]), Some(2.0));
+        assert_eq!(requested(&["ns1", "pod1", "app"]), Some(1.0));
+        assert_eq!(requested(&["ns1", "pod1", "sidecar"]), Some(0.5));
+        assert_eq!(by_container.len(), 4);
+
+        // not split: a single resource per pod (and per kind, qualifier)
+        let mut resources = vec![];
+        extract_allocatable_from_pods(pods(), &mut resources, false, false, false)
+            .await
+            .unwrap();
+        let cpus = resources
+            .iter()
+            .filter(|r| r.kind == "cpu")
+            .collect::<Vec<_>>();
+        assert_eq!(cpus.len(), 1);
+        assert_eq!(f64::from(&cpus[0].quantity), 2.0);
+        assert!(cpus[0].location.container_name.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_storage_displayed_as_storage() {
+        let nodes: ObjectList<Node> = serde_json::from_value(serde_json::json!({
+            "metadata": {},
+            "items": [{
+                "metadata": { "name": "node1" },
+                "status": {
+                    "capacity": { "ephemeral-storage": "107374182400" },
+                    "allocatable": { "ephemeral-storage": "100Gi" }
+                }
+            }]
+        }))
+        .unwrap();
+        let pods: ObjectList<Pod> = serde_json::from_value(serde_json::json!({
+            "metadata": {},
+            "items": [{
+                "metadata": { "name": "pod1", "namespace": "ns1" },
+                "spec": {
+                    "nodeName": "node1",
+                    "containers": [{
+                        "name": "app",
+                        "resources": {
+                            "requests": { "ephemeral-storage": "1Gi" },
+                            "limits": { "ephemeral-storage": "2147483648" }
+                        }
+                    }]
+                },
+                "status": { "phase": "Running" }
+            }]
+        }))
+        .unwrap();
+        let mut resources = vec![];
+        extract_allocatable_from_nodes(nodes, &mut resources)
+            .await
+            .unwrap();
+        extract_allocatable_from_pods(pods, &mut resources, false, false, false)
+            .await
+            .unwrap();
+        let data = make_qualifiers(
+            &resources,
+            &[GroupBy::resource, GroupBy::node],
+            &ResourceFilter::default(),
+        );
+        let (_, qtys) = data.iter().find(|(k, _)| k == &["storage"]).unwrap();
+        let qtys = qtys.as_ref().unwrap();
+        let bytes = |qty: &Option<Qty>| qty.as_ref().map(f64::from);
+        assert_eq!(bytes(&qtys.requested), Some(1073741824.0));
+        // bare bytes
+        assert_eq!(bytes(&qtys.limit), Some(2147483648.0));
+        assert_eq!(bytes(&qtys.allocatable), Some(107374182400.0));
+        assert!(data.iter().all(|(k, _)| k[0] != "ephemeral-storage"));
+        assert_eq!(quota_kind("requests.storage"), "pvc-storage");
+        assert_eq!(
+            quota_kind("requests.ephemeral-storage"),
+            "ephemeral-storage"
+        );
+    }
+
+    /// Count the events logged at WARN level
+    struct WarnCounter(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+    impl tracing::Subscriber for WarnCounter {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            tracing::span::Id::from_u64(1)
+        }
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+        fn event(&self, event: &tracing::Event<'_>) {
+            if *event.metadata().level() == tracing::Level::WARN {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+        fn enter(&self, _: &tracing::span::Id) {}
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[test]
+    fn test_sum_by_qualifier_of_different_kinds() {
+        let cpu = new_resource(
+            "cpu",
+            ResourceQualifier::Requested,
+            "1",
+            Location::default(),
+        );
+        let memory = new_resource(
+            "memory",
+            ResourceQualifier::Requested,
+            "1Gi",
+            Location::default(),
+        );
+        let warnings = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let subscriber = WarnCounter(warnings.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(sum_by_qualifier(&[]).is_none());
+            let sum = sum_by_qualifier(&[&cpu, &cpu]).unwrap();
+            assert_eq!(sum.requested.as_ref().map(f64::from), Some(2.0));
+            assert_eq!(warnings.load(std::sync::atomic::Ordering::SeqCst), 0);
+            let sum = sum_by_qualifier(&[&cpu, &memory]).unwrap();
+            assert!(sum.requested.is_none() && sum.limit.is_none() && sum.allocatable.is_none());
+            assert_eq!(warnings.load(std::sync::atomic::Ordering::SeqCst), 1);
+        });
+    }
+
+    #[test]
+    fn test_render_output() {
+        let node = node_location("node1");
+        let pod = Location {
+            namespace: Some("ns1".to_string()),
+            pod_name: Some("pod1".to_string()),
+            ..node.clone()
+        };
+        let resources = vec![
+            new_resource("cpu", ResourceQualifier::Allocatable, "4", node),
+            new_resource("cpu", ResourceQualifier::Requested, "3", pod),
+        ];
+        let cli_opts = |args: &[&str]| {
+            CliOpts::parse_from(std::iter::once("view-allocations").chain(args.iter().copied()))
+        };
+        let mut out = vec![];
+        render_output(
+            &resources,
+            false,
+            &cli_opts(&["-o", "csv"]),
+            &mut out,
+            false,
+        )
+        .unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        assert!(csv.starts_with("Date,Kind,resource,node,pod,"));
+        assert!(csv.contains(",resource,cpu,,,3.00,75%,,,4.00,1.00\n"));
+        assert!(csv.contains(",pod,cpu,node1,pod1,3.00,,,,,\n"));
+
+        let mut out = vec![];
+        render_output(
+            &resources,
+            false,
+            &cli_opts(&["-o", "markdown"]),
+            &mut out,
+            false,
+        )
+        .unwrap();
+        let markdown = String::from_utf8(out).unwrap();
+        assert!(markdown.contains("node1"));
+        assert!(markdown.contains("pod1"));
+
+        let mut out = vec![];
+        assert!(matches!(
+            render_output(
+                &resources,
+                false,
+                &cli_opts(&["-o", "csv", "--threshold-request", "0.5"]),
+                &mut out,
+                false
+            ),
+            Err(Error::ThresholdExceeded { .. })
+        ));
+        assert!(!out.is_empty());
+    }
+
+    #[test]
+    fn test_make_qualifiers_above_resource_level() {
+        let pod = pod_location("ns1", "pod1");
+        let resources = vec![
+            new_resource("cpu", ResourceQualifier::Requested, "1", pod.clone()),
+            new_resource("memory", ResourceQualifier::Requested, "1Gi", pod),
+        ];
+        let group_by = vec![GroupBy::namespace, GroupBy::resource, GroupBy::pod];
+        let warnings = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let subscriber = WarnCounter(warnings.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let rows = make_qualifiers(&resources, &group_by, &ResourceFilter::default());
+            let ns_row = rows.iter().find(|(k, _)| k == &vec!["ns1".to_string()]);
+            assert!(ns_row.unwrap().1.is_none());
+            // the namespace row (without quantities) is not a row of the csv
+            let csv = dsv::format_as_csv(&rows, &group_by, false, &DisplayOptions::default());
+            assert_eq!(csv.lines().count(), 1 + 4);
+            assert!(csv
+                .lines()
+                .all(|l| l.split(',').nth(1) != Some("namespace")));
+            assert!(!is_full_zero(&ns_row.unwrap().1, false));
+            assert_eq!(warnings.load(std::sync::atomic::Ordering::SeqCst), 0);
+        });
+    }
+
+    #[test]
+    fn test_retain_on_nodes() {
+        let on_node = |node: Option<&str>, pod: Option<&str>| Location {
+            node_name: node.map(|x| x.to_string()),
+            pod_name: pod.map(|x| x.to_string()),
+            namespace: pod.map(|_| "ns1".to_string()),
+            ..Location::default()
+        };
+        let mut resources = vec![
+            new_resource(
+                "cpu",
+                ResourceQualifier::Allocatable,
+                "4",
+                on_node(Some("worker1"), None),
+            ),
+            new_resource(
+                "cpu",
+                ResourceQualifier::Allocatable,
+                "4",
+                on_node(Some("master1"), None),
+            ),
+            new_resource(
+                "cpu",
+                ResourceQualifier::Requested,
+                "1",
+                on_node(Some("worker1"), Some("pod1")),
+            ),
+            new_resource(
+                "cpu",
+                ResourceQualifier::Requested,
+                "1",
+                on_node(Some("master1"), Some("pod2")),
+            ),
+            new_resource(
+                "cpu",
+                ResourceQualifier::Utilization,
+                "1",
+                on_node(None, Some("pod2")),
+            ),
+            new_resource(
+                "cpu",
+                ResourceQualifier::QuotaHard,
+                "8",
+                Location {
+                    namespace: Some("ns1".to_string()),
+                    ..Location::default()
+                },
+            ),
+        ];
+        retain_on_nodes(&mut resources, &HashSet::from(["worker1".to_string()]));
+        assert_eq!(resources.len(), 3);
+        assert!(resources
+            .iter()
+            .all(|r| r.location.node_name.as_deref() != Some("master1")
+                && r.location.pod_name.as_deref() != Some("pod2")));
+    }
+
+    #[test]
+    fn test_resource_filter() {
+        let node = node_location("node1");
+        let pod = Location {
+            namespace: Some("ns1".to_string()),
+            pod_name: Some("pod1".to_string()),
+            ..node.clone()
+        };
+        let node_cpu = new_resource("cpu", ResourceQualifier::Allocatable, "4", node);
+        let pod_cpu = new_resource("cpu", ResourceQualifier::Requested, "1", pod.clone());
+        let pod_memory = new_resource("memory", ResourceQualifier::Requested, "1Gi", pod);
+
+        let filter = ResourceFilter::default();
+        assert!(filter.accept(&node_cpu) && filter.accept(&pod_cpu) && filter.accept(&pod_memory));
+
+        let filter = ResourceFilter {
+            resource_names: vec!["cpu".to_string()],
+            namespaces: vec![NamespacePattern::from_str("ns1").unwrap()],
+            ..ResourceFilter::default()
+        };
+        assert!(filter.accept(&node_cpu) && filter.accept(&pod_cpu));
+        assert!(!filter.accept(&pod_memory));
+
+        let filter = ResourceFilter {
+            resource_names: vec!["cpu".to_string(), "mem".to_string()],
+            exclude_resource_names: vec!["memory".to_string()],
+            ..ResourceFilter::default()
+        };
+        assert!(filter.accept(&pod_cpu));
+        assert!(!filter.accept(&pod_memory));
+
+        let filter = ResourceFilter {
+            exclude_namespaces: vec!["ns1".to_string()],
+            ..ResourceFilter::default()
+        };
+        assert!(filter.accept(&node_cpu));
+        assert!(!filter.accept(&pod_cpu));
+
+        let filter = ResourceFilter {
+            node_regex: Some(Regex::new("^node[2-9]$").unwrap()),
+            ..ResourceFilter::default()
+        };
+        assert!(!filter.accept(&node_cpu) && !filter.accept(&pod_cpu));
+
+        let filter = ResourceFilter {
+            exclude_terminating_namespaces: true,
+            ..ResourceFilter::default()
+        };
+        let mut terminating_pod_cpu = pod_cpu.clone();
+        terminating_pod_cpu.location.namespace_terminating = true;
+        assert!(filter.accept(&node_cpu) && filter.accept(&pod_cpu));
+        assert!(!filter.accept(&terminating_pod_cpu));
+
+        let pod_storage = new_resource(
+            "ephemeral-storage",
+            ResourceQualifier::Requested,
+            "1Gi",
+            Location::default(),
+        );
+        for name in ["storage", "ephemeral-storage"] {
+            let filter = ResourceFilter {
+                resource_names: vec![name.to_string()],
+                exact_resource: true,
+                ..ResourceFilter::default()
+            };
+            assert!(filter.accept(&pod_storage));
+            let filter = ResourceFilter {
+                exclude_resource_names: vec![name.to_string()],
+                exact_resource: true,
+                ..ResourceFilter::default()
+            };
+            assert!(!filter.accept(&pod_storage));
+        }
+    }
+
+    #[test]
+    fn test_color_theme() {
+        let theme: ColorTheme = toml::from_str(
+            r#"
+            cpu = "rFg"
+            "nvidia.com/gpu" = "rFm"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(theme.style_of("cpu"), Some("rFg"));
+        assert_eq!(theme.style_of("nvidia.com/gpu"), Some("rFm"));
+        assert_eq!(theme.style_of("memory"), None);
+    }
+
+    #[test]
+    fn test_namespace_groups_with_namespace_filters() {
+        let groups: NamespaceGroups = toml::from_str(
+            r#"
+            [[ns_group]]
+            name = "production"
+            namespaces = ["prod", "prod-eu"]
+            "#,
+        )
+        .unwrap();
+        let pod = |namespace: &str| {
+            new_resource(
+                "cpu",
+                ResourceQualifier::Requested,
+                "1",
+                Location {
+                    namespace: Some(namespace.to_string()),
+                    pod_name: Some(format!("pod-{}", namespace)),
+                    ..Location::default()
+                },
+            )
+        };
+        let pods = || vec![pod("prod"), pod("prod-eu"), pod("dev")];
+        let pods_of = |resources: &[Resource], filter: &ResourceFilter| {
+            resources
+                .iter()
+                .filter(|r| filter.accept(r))
+                .filter_map(|r| r.location.pod_name.clone())
+                .collect::<Vec<_>>()
+        };
+        let cli_opts = CliOpts::parse_from([
+            "view-allocations",
+            "-n",
+            "prod",
+            "--namespace-resource-group",
+            "groups.toml",
+        ]);
+        let mut resources = pods();
+        let filter = groups.apply_filtered(&mut resources, &ResourceFilter::from(&cli_opts));
+        assert_eq!(pods_of(&resources, &filter), vec!["pod-prod"]);
+        assert_eq!(
+            resources[0].location.namespace.as_deref(),
+            Some("production")
+        );
+
+        let filter = ResourceFilter {
+            exclude_namespaces: vec!["prod".to_string()],
+            ..ResourceFilter::default()
+        };
+        let mut resources = pods();
+        let filter = groups.apply_filtered(&mut resources, &filter);
+        assert_eq!(pods_of(&resources, &filter), vec!["pod-prod-eu", "pod-dev"]);
+    }
 
     #[test]
-    fn test_accept_resource() {
-        assert_eq!(accept_resource("cpu", &vec![]), true);
-        assert_eq!(accept_resource("cpu", &vec!["c".to_string()]), true);
-        assert_eq!(accept_resource("cpu", &vec!["cpu".to_string()]), true);
-        assert_eq!(accept_resource("cpu", &vec!["cpu3".to_string()]), false);
-        assert_eq!(accept_resource("gpu", &vec!["gpu".to_string()]), true);
+    fn test_namespace_groups() {
+        let groups: NamespaceGroups = toml::from_str(
+            r#"
+            [[ns_group]]
+            name = "production"
+            namespaces = ["prod", "prod-eu"]
+            "#,
+        )
+        .unwrap();
+        let pod = |namespace: &str, requested: &str| {
+            new_resource(
+                "cpu",
+                ResourceQualifier::Requested,
+                requested,
+                pod_location(namespace, &format!("pod-{}", namespace)),
+            )
+        };
+        let mut resources = vec![pod("prod", "1"), pod("prod-eu", "2"), pod("dev", "3")];
+        groups.apply(&mut resources);
+        let rows = make_qualifiers(
+            &resources,
+            &[GroupBy::namespace, GroupBy::resource],
+            &ResourceFilter::default(),
+        )
+        .into_iter()
+        .filter(|(k, _)| k.len() == 2)
+        .map(|(k, oqtys)| {
+            (
+                k.join("/"),
+                oqtys.and_then(|q| q.requested).map(|q| q.to_string()),
+            )
+        })
+        .collect::<Vec<_>>();
         assert_eq!(
-            accept_resource("nvidia.com/gpu", &vec!["gpu".to_string()]),
-            true
+            rows,
+            vec![
+                ("dev/cpu".to_string(), Some("3.0".to_string())),
+                ("production/cpu".to_string(), Some("3.0".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cost_column() {
+        let config: CostConfig = toml::from_str(
+            r#"
+            [cpu]
+            per_core_per_hour = 0.048
+
+            [memory]
+            per_unit_per_hour = 0.006
+            unit = "1Gi"
+            "#,
+        )
+        .unwrap();
+        let pod = Location {
+            node_name: Some("node1".to_string()),
+            pod_name: Some("pod1".to_string()),
+            ..Location::default()
+        };
+        let resources = vec![
+            new_resource("cpu", ResourceQualifier::Requested, "2", pod.clone()),
+            new_resource("memory", ResourceQualifier::Requested, "4Gi", pod.clone()),
+            new_resource("nvidia.com/gpu", ResourceQualifier::Requested, "1", pod),
+        ];
+        let column = ExtraColumn::Cost(config, Some(0.05));
+        let values = make_extra_columns(
+            &resources,
+            &[GroupBy::resource, GroupBy::node],
+            &ResourceFilter::default(),
+            std::slice::from_ref(&column),
+        );
+        assert_eq!(values[&key(&["cpu"])], vec![Some("$0.096".to_string())]);
+        assert_eq!(
+            values[&key(&["cpu", "node1"])],
+            vec![Some("$0.096".to_string())]
+        );
+        assert_eq!(
+            values[&key(&["memory", "node1"])],
+            vec![Some("$0.024".to_string())]
+        );
+        assert_eq!(values[&key(&["nvidia.com/gpu"])], vec![None]);
+        assert_eq!(column.color("$0.096"), "Fy");
+        assert_eq!(column.color("$0.024"), "");
+    }
+
+    #[test]
+    fn test_calc_utilization_ratio() {
+        let qtys = |utilization: Option<&str>, requested: Option<&str>| QtyByQualifier {
+            utilization: utilization.map(|v| Qty::from_str(v).unwrap()),
+            requested: requested.map(|v| Qty::from_str(v).unwrap()),
+            ..QtyByQualifier::default()
+        };
+        assert_eq!(
+            qtys(Some("250m"), Some("500m")).calc_utilization_ratio(),
+            Some(0.5)
+        );
+        assert_eq!(
+            qtys(Some("2"), Some("1")).calc_utilization_ratio(),
+            Some(2.0)
+        );
+        assert_eq!(qtys(Some("1"), Some("0")).calc_utilization_ratio(), None);
+        assert_eq!(qtys(None, Some("1")).calc_utilization_ratio(), None);
+        assert_eq!(qtys(Some("1"), None).calc_utilization_ratio(), None);
+        assert_eq!(format_utilization_ratio(Some(1.25)), "125%");
+        assert_eq!(format_utilization_ratio(None), "");
+    }
+
+    #[test]
+    fn test_calc_overcommit_ratio() {
+        let qtys = |limit: Option<&str>, allocatable: Option<&str>| QtyByQualifier {
+            limit: limit.map(|v| Qty::from_str(v).unwrap()),
+            allocatable: allocatable.map(|v| Qty::from_str(v).unwrap()),
+            ..QtyByQualifier::default()
+        };
+        // limit > allocatable
+        assert_eq!(
+            qtys(Some("12Gi"), Some("8Gi")).calc_overcommit_ratio(),
+            Some(1.5)
+        );
+        assert_eq!(
+            qtys(Some("2"), Some("4")).calc_overcommit_ratio(),
+            Some(0.5)
+        );
+        assert_eq!(qtys(None, Some("4")).calc_overcommit_ratio(), None);
+        assert_eq!(qtys(Some("2"), None).calc_overcommit_ratio(), None);
+        assert_eq!(qtys(Some("2"), Some("0")).calc_overcommit_ratio(), None);
+        assert_eq!(format_overcommit_ratio(Some(1.5)), "1.50");
+        assert_eq!(format_overcommit_ratio(None), "");
+    }
+
+    #[test]
+    fn test_compute_color() {
+        let qtys = QtyByQualifier {
+            requested: qty("2"),
+            limit: qty("4"),
+            allocatable: qty("4"),
+            utilization: qty("3.5"),
+            ..QtyByQualifier::default()
+        };
+        assert_eq!(
+            compute_color(&qtys, &ColorBy::requested_pct, 80, 95),
+            OK_STYLE
+        );
+        let no_limit = QtyByQualifier {
+            limit: None,
+            ..qtys.clone()
+        };
+        assert_eq!(
+            compute_color(&no_limit, &ColorBy::requested_pct, 80, 95),
+            "rFy"
+        );
+        // 87.5% of allocatable used
+        assert_eq!(
+            compute_color(&qtys, &ColorBy::utilization_pct, 80, 95),
+            "rFy"
+        );
+        assert_eq!(
+            compute_color(&qtys, &ColorBy::utilization_pct, 50, 85),
+            "rFr"
+        );
+        // the limit (4) takes the whole allocatable
+        assert_eq!(compute_color(&qtys, &ColorBy::free, 80, 95), "rFr");
+        // using more than requested, nothing wasted
+        assert_eq!(compute_color(&qtys, &ColorBy::efficiency, 80, 95), OK_STYLE);
+        let idle = QtyByQualifier {
+            utilization: qty("100m"),
+            ..qtys
+        };
+        assert_eq!(compute_color(&idle, &ColorBy::efficiency, 80, 95), "rFr");
+    }
+
+    #[test]
+    fn test_merge_contexts() {
+        let contexts = vec!["ctx1".to_string(), "ctx2".to_string()];
+        let timeout = |context: &str| Error::ContextTimeout {
+            context: context.to_string(),
+            secs: 5,
+        };
+        let cpu = new_resource(
+            "cpu",
+            ResourceQualifier::Requested,
+            "1",
+            Location::default(),
+        );
+        let (resources, show_utilization) =
+            merge_contexts(&contexts, vec![Ok((vec![cpu], true)), Err(timeout("ctx2"))]).unwrap();
+        assert_eq!(resources.len(), 1);
+        assert!(show_utilization);
+        assert!(matches!(
+            merge_contexts(&contexts, vec![Err(timeout("ctx1")), Err(timeout("ctx2"))]),
+            Err(Error::ContextTimeout { .. })
+        ));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let resources = vec![
+            new_resource(
+                "memory",
+                ResourceQualifier::Requested,
+                "1536Mi",
+                Location {
+                    node_name: Some("node1".to_string()),
+                    labels: BTreeMap::from([("app".to_string(), "web".to_string())]),
+                    ..pod_location("ns1", "pod1")
+                },
+            ),
+            new_resource(
+                "cpu",
+                ResourceQualifier::Utilization,
+                "250m",
+                Location::default(),
+            ),
+        ];
+        let path = std::env::temp_dir().join(format!("snapshot-{}.json", std::process::id()));
+        write_snapshot(&path, &resources).unwrap();
+        let (loaded, show_utilization) = read_snapshot(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(show_utilization);
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].kind, "memory");
+        assert_eq!(loaded[0].quantity.value, resources[0].quantity.value);
+        assert_eq!(loaded[0].location.labels["app"], "web");
+        assert!(matches!(
+            loaded[1].qualifier,
+            ResourceQualifier::Utilization
+        ));
+        assert!(matches!(
+            read_snapshot(&path),
+            Err(Error::ReadFileError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_truncate_name() {
+        assert_eq!(truncate_name("nginx", 60), "nginx");
+        assert_eq!(truncate_name("nginx", 5), "nginx");
+        assert_eq!(truncate_name("nginx-7c5ddbdf54-2xj4k", 10), "nginx-7c5…");
+        assert_eq!(
+            truncate_name("nginx-7c5ddbdf54-2xj4k", 10).chars().count(),
+            10
+        );
+        assert_eq!(truncate_name("éàü", 2), "é…");
+    }
+
+    #[test]
+    fn test_limits_under_requests() {
+        let qtys = |items: &[(&str, &str)]| {
+            items
+                .iter()
+                .map(|(k, v)| (k.to_string(), Qty::from_str(v).unwrap()))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let requests = qtys(&[("cpu", "500m"), ("memory", "1Gi")]);
+        assert!(limits_under_requests(&requests, &qtys(&[])).is_empty());
+        assert!(limits_under_requests(&requests, &qtys(&[("cpu", "500m")])).is_empty());
+        assert_eq!(
+            limits_under_requests(&requests, &qtys(&[("cpu", "1"), ("memory", "512Mi")])),
+            vec!["memory"]
+        );
+        // a limit without request is valid (the request defaults to the limit)
+        assert!(limits_under_requests(&qtys(&[]), &qtys(&[("cpu", "1m")])).is_empty());
+    }
+
+    #[test]
+    fn test_cpu_pinning() {
+        let node = Location {
+            node_name: Some("node1".to_string()),
+            cpu_manager_policy: Some("static".to_string()),
+            ..Location::default()
+        };
+        let container = |exclusive_cpu: bool| Location {
+            node_name: Some("node1".to_string()),
+            pod_name: Some("pod".to_string()),
+            exclusive_cpu,
+            ..Location::default()
+        };
+        let resources = vec![
+            new_resource("cpu", ResourceQualifier::Allocatable, "8", node.clone()),
+            new_resource("cpu", ResourceQualifier::Requested, "2", container(true)),
+            new_resource(
+                "cpu",
+                ResourceQualifier::Requested,
+                "500m",
+                container(false),
+            ),
+            new_resource(
+                "memory",
+                ResourceQualifier::Requested,
+                "2Gi",
+                container(true),
+            ),
+        ];
+        let group = resources.iter().collect::<Vec<_>>();
+        assert_eq!(
+            cpu_pinning(&group).as_deref(),
+            Some("2.0 pinned / 6.0 shared")
+        );
+        let mut resources = resources;
+        resources[0].location.cpu_manager_policy = Some("none".to_string());
+        let group = resources.iter().collect::<Vec<_>>();
+        assert_eq!(cpu_pinning(&group), None);
+    }
+
+    #[test]
+    fn test_retain_labels() {
+        let mut pod: Pod = serde_json::from_value(serde_json::json!({
+            "metadata": {
+                "name": "pod1",
+                "labels": {
+                    "team": "a",
+                    "pod-template-hash": "7c5ddbdf54",
+                    "helm.sh/chart": "app-1.0.0"
+                }
+            }
+        }))
+        .unwrap();
+        retain_labels(&mut pod.metadata, &["team".to_string()]);
+        let labels = pod.metadata.labels.as_ref().unwrap();
+        assert_eq!(
+            labels.keys().collect::<Vec<_>>(),
+            vec!["helm.sh/chart", "team"]
+        );
+        assert_eq!(managed_by(&pod), Some("Helm".to_string()));
+    }
+
+    #[test]
+    fn test_managed_by() {
+        let pod = |labels: serde_json::Value| -> Pod {
+            serde_json::from_value(serde_json::json!({
+                "metadata": { "name": "pod1", "labels": labels }
+            }))
+            .unwrap()
+        };
+        assert_eq!(
+            managed_by(&pod(
+                serde_json::json!({ "app.kubernetes.io/managed-by": "Helm" })
+            )),
+            Some("Helm".to_string())
+        );
+        assert_eq!(
+            managed_by(&pod(
+                serde_json::json!({ "argocd.argoproj.io/instance": "app1" })
+            )),
+            Some("ArgoCD".to_string())
+        );
+        assert_eq!(managed_by(&pod(serde_json::json!({ "app": "app1" }))), None);
+        assert_eq!(GroupBy::from_str("managed_by"), Ok(GroupBy::managed_by));
+        let resource = |managed_by: Option<&str>, owner_name: Option<&str>| {
+            new_resource(
+                "cpu",
+                ResourceQualifier::Requested,
+                "1",
+                Location {
+                    managed_by: managed_by.map(|x| x.to_string()),
+                    owner_name: owner_name.map(|x| x.to_string()),
+                    ..Location::default()
+                },
+            )
+        };
+        let extract = GroupBy::managed_by.to_fct();
+        assert_eq!(
+            extract(&resource(Some("Helm"), Some("Deployment/app1"))),
+            Some("Helm".to_string())
+        );
+        assert_eq!(
+            extract(&resource(None, Some("DaemonSet/agent"))),
+            Some("DaemonSet".to_string())
+        );
+        assert_eq!(extract(&resource(None, None)), None);
+    }
+
+    #[test]
+    fn test_limit_tier() {
+        let limits = |cpu: Option<&str>, memory: Option<&str>| {
+            cpu.map(|v| ("cpu".to_string(), Qty::from_str(v).unwrap()))
+                .into_iter()
+                .chain(memory.map(|v| ("memory".to_string(), Qty::from_str(v).unwrap())))
+                .collect::<BTreeMap<_, _>>()
+        };
+        assert_eq!(limit_tier(&limits(Some("100m"), Some("128Mi"))), "nano");
+        // the largest of cpu & memory decides
+        assert_eq!(limit_tier(&limits(Some("100m"), Some("1Gi"))), "small");
+        assert_eq!(limit_tier(&limits(Some("2"), Some("256Mi"))), "medium");
+        assert_eq!(limit_tier(&limits(Some("8"), Some("4Gi"))), "large");
+        assert_eq!(limit_tier(&limits(Some("1"), None)), "unlimited");
+        assert_eq!(limit_tier(&limits(None, None)), "unlimited");
+    }
+
+    #[test]
+    fn test_ephemeral_storage_pressure() {
+        let node = node_location("node1");
+        let pod = Location {
+            pod_name: Some("pod".to_string()),
+            ..node.clone()
+        };
+        let resources = [
+            new_resource(
+                "ephemeral-storage",
+                ResourceQualifier::Allocatable,
+                "100Gi",
+                node.clone(),
+            ),
+            new_resource(
+                "ephemeral-storage",
+                ResourceQualifier::Requested,
+                "90Gi",
+                pod.clone(),
+            ),
+            new_resource("cpu", ResourceQualifier::Allocatable, "4", node),
+        ];
+        let group = resources.iter().collect::<Vec<_>>();
+        let column = ExtraColumn::EphemeralStoragePressure(0.85);
+        let txt = column.compute(&group, &GroupBy::node).unwrap();
+        assert_eq!(txt, "90%");
+        assert_eq!(column.color(&txt), "Fr");
+        assert_eq!(column.color("80%"), "");
+        // without ephemeral storage allocatable
+        assert_eq!(column.compute(&group[2..], &GroupBy::node), None);
+    }
+
+    #[test]
+    fn test_psa_level() {
+        let location = |level: Option<&str>| Location {
+            namespace: Some("default".to_string()),
+            pod_name: Some("pod".to_string()),
+            namespace_labels: level
+                .map(|l| BTreeMap::from([(PSA_ENFORCE_LABEL.to_string(), l.to_string())]))
+                .unwrap_or_default(),
+            ..Location::default()
+        };
+        let resource = new_resource(
+            "cpu",
+            ResourceQualifier::Requested,
+            "1",
+            location(Some("restricted")),
+        );
+        assert_eq!(
+            ExtraColumn::PsaLevel
+                .compute(&[&resource], &GroupBy::namespace)
+                .as_deref(),
+            Some("restricted")
+        );
+        let resource = new_resource("cpu", ResourceQualifier::Requested, "1", location(None));
+        assert_eq!(
+            ExtraColumn::PsaLevel.compute(&[&resource], &GroupBy::namespace),
+            None
+        );
+    }
+
+    #[test]
+    fn test_failing_conditions() {
+        let pod: Pod = serde_json::from_value(serde_json::json!({
+            "metadata": { "name": "pod1" },
+            "status": {
+                "conditions": [
+                    { "type": "PodScheduled", "status": "True" },
+                    { "type": "Ready", "status": "False" },
+                    { "type": "ContainersReady", "status": "False" }
+                ]
+            }
+        }))
+        .unwrap();
+        assert_eq!(
+            failing_conditions(&pod),
+            vec![
+                "Ready=False".to_string(),
+                "ContainersReady=False".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_cell_text() {
+        assert_eq!(
+            format_cell_text(&qty("3Gi"), &qty("4Gi"), 1, false),
+            "(75%) 3.0Gi"
+        );
+        assert_eq!(format_cell_text(&qty("3Gi"), &qty("4Gi"), 1, true), "(75%)");
+        // without base, the quantity is kept
+        assert_eq!(format_cell_text(&qty("3Gi"), &None, 2, true), "3.00Gi");
+        assert_eq!(format_cell_text(&None, &qty("4Gi"), 1, true), "__");
+    }
+
+    #[test]
+    fn test_top_rows() {
+        let rows = vec![
+            row(&["cpu"], "6", Some("8")),
+            row(&["cpu", "node1"], "1", Some("4")),
+            row(&["cpu", "node1", "pod1"], "1", None),
+            row(&["cpu", "node2"], "5", Some("4")),
+            row(&["cpu", "node2", "pod2"], "2", None),
+            row(&["cpu", "node2", "pod3"], "3", None),
+            row(&["memory"], "1Gi", None),
+            row(&["memory", "node1"], "1Gi", None),
+            row(&["memory", "node1", "pod1"], "1Gi", None),
+        ];
+        let group_by = vec![GroupBy::resource, GroupBy::node, GroupBy::pod];
+        let top = top_rows(rows, &group_by, 1, &SortBy::key);
+        let summary = top
+            .iter()
+            .map(|(k, oqtys)| {
+                let qtys = oqtys.as_ref().unwrap();
+                (
+                    k.join("/"),
+                    qtys.requested
+                        .as_ref()
+                        .map(|q| q.adjust_scale().to_string()),
+                    qtys.allocatable
+                        .as_ref()
+                        .map(|q| q.adjust_scale().to_string()),
+                )
+            })
+            .collect::<Vec<_>>();
+        let s = |x: &str| Some(x.to_string());
+        assert_eq!(
+            summary,
+            vec![
+                ("cpu".to_string(), s("3.0"), s("8.0")),
+                ("cpu/node2".to_string(), s("3.0"), s("4.0")),
+                ("cpu/node2/pod3".to_string(), s("3.0"), None),
+                // unchanged (no child removed)
+                ("memory".to_string(), s("1.0Gi"), None),
+                ("memory/node1".to_string(), s("1.0Gi"), None),
+                ("memory/node1/pod1".to_string(), s("1.0Gi"), None),
+            ]
+        );
+
+        // the rows above the resource level are not recomputed (mixed kinds)
+        let rows = vec![
+            (key(&["node1"]), None),
+            row(&["node1", "cpu"], "3", Some("4")),
+            row(&["node1", "cpu", "pod1"], "1", None),
+            row(&["node1", "cpu", "pod2"], "2", None),
+            row(&["node1", "memory"], "2Gi", Some("8Gi")),
+            row(&["node1", "memory", "pod1"], "2Gi", None),
+        ];
+        let group_by = vec![GroupBy::node, GroupBy::resource, GroupBy::pod];
+        let top = top_rows(rows, &group_by, 1, &SortBy::key);
+        let summary = top
+            .iter()
+            .map(|(k, oqtys)| {
+                (
+                    k.join("/"),
+                    oqtys
+                        .as_ref()
+                        .and_then(|qtys| qtys.requested.as_ref())
+                        .map(|q| q.adjust_scale().to_string()),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("node1".to_string(), None),
+                ("node1/cpu".to_string(), s("2.0")),
+                ("node1/cpu/pod2".to_string(), s("2.0")),
+                ("node1/memory".to_string(), s("2.0Gi")),
+                ("node1/memory/pod1".to_string(), s("2.0Gi")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_rows() {
+        let mut rows = vec![
+            row(&["cpu"], "6", None),
+            row(&["cpu", "node1"], "1", None),
+            row(&["cpu", "node1", "pod1"], "1", None),
+            row(&["cpu", "node2"], "5", None),
+            row(&["cpu", "node2", "pod2"], "2", None),
+            row(&["cpu", "node2", "pod3"], "3", None),
+        ];
+        let keys = |rows: &[(Vec<String>, Option<QtyByQualifier>)]| {
+            rows.iter().map(|(k, _)| k.join("/")).collect::<Vec<_>>()
+        };
+        sort_rows(&mut rows, &SortBy::requested, true);
+        assert_eq!(
+            keys(&rows),
+            vec![
+                "cpu",
+                "cpu/node2",
+                "cpu/node2/pod3",
+                "cpu/node2/pod2",
+                "cpu/node1",
+                "cpu/node1/pod1"
+            ]
+        );
+        sort_rows(&mut rows, &SortBy::key, false);
+        assert_eq!(
+            keys(&rows),
+            vec![
+                "cpu",
+                "cpu/node1",
+                "cpu/node1/pod1",
+                "cpu/node2",
+                "cpu/node2/pod2",
+                "cpu/node2/pod3"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_thresholds() {
+        let rows = vec![(
+            key(&["cpu", "node1"]),
+            Some(QtyByQualifier {
+                requested: qty("3"),
+                limit: qty("6"),
+                allocatable: qty("4"),
+                ..QtyByQualifier::default()
+            }),
+        )];
+        assert!(check_thresholds(&rows, None, None).is_ok());
+        assert!(check_thresholds(&rows, Some(0.8), None).is_ok());
+        let err = check_thresholds(&rows, Some(0.7), None).unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::ThresholdExceeded { path, .. } if path == "cpu/node1"
+        ));
+        assert_eq!(err.exit_code(), 2);
+        assert!(check_thresholds(&rows, None, Some(1.4)).is_err());
+    }
+
+    #[test]
+    fn test_extract_from_resource_quotas() {
+        let quotas: ObjectList<ResourceQuota> = serde_json::from_value(serde_json::json!({
+            "metadata": {},
+            "items": [{
+                "metadata": { "name": "quota1", "namespace": "ns1" },
+                "status": {
+                    "hard": { "requests.cpu": "10", "limits.cpu": "20" },
+                    "used": { "requests.cpu": "4", "limits.cpu": "8" }
+                }
+            }]
+        }))
+        .unwrap();
+        let mut resources = vec![new_resource(
+            "cpu",
+            ResourceQualifier::Requested,
+            "4",
+            pod_location("ns1", "pod1"),
+        )];
+        extract_from_resource_quotas(quotas, &mut resources).unwrap();
+        assert_eq!(resources.len(), 5);
+        let group_by = vec![GroupBy::resource, GroupBy::namespace];
+        let values = make_extra_columns(
+            &resources,
+            &group_by,
+            &ResourceFilter::default(),
+            &[ExtraColumn::Quota],
+        );
+        assert_eq!(
+            values[&key(&["cpu", "ns1"])],
+            vec![Some("(40%) 4.0 / 10.0".to_string())]
+        );
+        assert_eq!(
+            values[&key(&["limits.cpu", "ns1"])],
+            vec![Some("(40%) 8.0 / 20.0".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_extract_from_limit_ranges() {
+        let limit_ranges: ObjectList<LimitRange> = serde_json::from_value(serde_json::json!({
+            "metadata": {},
+            "items": [{
+                "metadata": { "name": "defaults", "namespace": "ns1" },
+                "spec": {
+                    "limits": [{
+                        "type": "Container",
+                        "default": { "cpu": "500m", "memory": "512Mi" },
+                        "defaultRequest": { "cpu": "100m" }
+                    }, {
+                        "type": "Pod",
+                        "max": { "cpu": "4" }
+                    }]
+                }
+            }, {
+                "metadata": { "name": "team-defaults", "namespace": "ns1" },
+                "spec": {
+                    "limits": [{
+                        "type": "Container",
+                        "default": { "cpu": "2" },
+                        "defaultRequest": { "cpu": "50m" }
+                    }]
+                }
+            }]
+        }))
+        .unwrap();
+        let mut resources = vec![];
+        extract_from_limit_ranges(limit_ranges, &mut resources).unwrap();
+        assert_eq!(resources.len(), 5);
+        let group_by = vec![GroupBy::resource, GroupBy::namespace];
+        let values = make_extra_columns(
+            &resources,
+            &group_by,
+            &ResourceFilter::default(),
+            &[ExtraColumn::LimitRangeDefaults],
+        );
+        assert_eq!(
+            values[&key(&["cpu", "ns1"])],
+            vec![Some("50.0m / 500.0m".to_string())]
+        );
+        assert_eq!(
+            values[&key(&["memory", "ns1"])],
+            vec![Some("__ / 512.0Mi".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extract_allocatable_from_pending_pods() {
+        let pods = || -> ObjectList<Pod> {
+            serde_json::from_value(serde_json::json!({
+                "metadata": {},
+                "items": [{
+                    "metadata": { "name": "pod1", "namespace": "ns1" },
+                    "spec": {
+                        "containers": [
+                            { "name": "app", "resources": { "requests": { "cpu": "1" } } }
+                        ]
+                    },
+                    "status": {
+                        "phase": "Pending",
+                        "conditions": [{ "type": "PodScheduled", "status": "False" }]
+                    }
+                }]
+            }))
+            .unwrap()
+        };
+        assert!(is_pending(&pods().items[0]));
+        let mut resources = vec![];
+        extract_allocatable_from_pods(pods(), &mut resources, false, false, false)
+            .await
+            .unwrap();
+        assert!(resources.is_empty());
+        extract_allocatable_from_pods(pods(), &mut resources, true, false, false)
+            .await
+            .unwrap();
+        assert!(!resources.is_empty());
+        assert!(resources
+            .iter()
+            .all(|r| r.location.pending && r.location.node_name.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_extract_allocatable_from_terminating_pods() {
+        let pods = || -> ObjectList<Pod> {
+            serde_json::from_value(serde_json::json!({
+                "metadata": {},
+                "items": [{
+                    "metadata": {
+                        "name": "pod1",
+                        "namespace": "ns1",
+                        "deletionTimestamp": "2022-01-01T00:00:00Z"
+                    },
+                    "spec": {
+                        "nodeName": "node1",
+                        "containers": [
+                            { "name": "app", "resources": { "requests": { "cpu": "1" } } }
+                        ]
+                    },
+                    "status": { "phase": "Running" }
+                }]
+            }))
+            .unwrap()
+        };
+        assert!(is_scheduled(&pods().items[0]));
+        assert!(is_terminating(&pods().items[0]));
+        let mut resources = vec![];
+        extract_allocatable_from_pods(pods(), &mut resources, false, false, false)
+            .await
+            .unwrap();
+        assert!(resources.is_empty());
+        extract_allocatable_from_pods(pods(), &mut resources, false, true, false)
+            .await
+            .unwrap();
+        assert!(!resources.is_empty());
+        assert!(resources.iter().all(|r| r.location.terminating));
+    }
+
+    #[tokio::test]
+    async fn test_extract_utilizations_of_terminating_pods() {
+        let pods: ObjectList<Pod> = serde_json::from_value(serde_json::json!({
+            "metadata": {},
+            "items": [{
+                "metadata": {
+                    "name": "pod1",
+                    "namespace": "ns1",
+                    "deletionTimestamp": "2022-01-01T00:00:00Z"
+                },
+                "spec": {
+                    "nodeName": "node1",
+                    "containers": [
+                        { "name": "app", "resources": { "requests": { "cpu": "1" } } }
+                    ]
+                },
+                "status": { "phase": "Running" }
+            }, {
+                "metadata": { "name": "pod2", "namespace": "ns1" },
+                "spec": {
+                    "nodeName": "node1",
+                    "containers": [
+                        { "name": "app", "resources": { "requests": { "cpu": "1" } } }
+                    ]
+                },
+                "status": { "phase": "Running" }
+            }]
+        }))
+        .unwrap();
+        let pod_metric = |name: &str| {
+            serde_json::json!({
+                "metadata": { "name": name, "namespace": "ns1" },
+                "containers": [{ "name": "app", "usage": { "cpu": "500m", "memory": "1Gi" } }],
+                "timestamp": "2022-01-01T00:00:00Z",
+                "window": "30s"
+            })
+        };
+        let pod_metrics: ObjectList<metrics::PodMetrics> = serde_json::from_value(
+            serde_json::json!({ "metadata": {}, "items": [pod_metric("pod1"), pod_metric("pod2")] }),
+        )
+        .unwrap();
+        let mut resources = vec![];
+        let skipped_pods = extract_allocatable_from_pods(pods, &mut resources, false, false, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            skipped_pods,
+            HashSet::from([("ns1".to_string(), "pod1".to_string())])
+        );
+        extract_utilizations_from_pod_metrics(pod_metrics, &mut resources, &skipped_pods)
+            .await
+            .unwrap();
+        let utilizations = resources
+            .iter()
+            .filter(|r| matches!(r.qualifier, ResourceQualifier::Utilization))
+            .collect::<Vec<_>>();
+        assert_eq!(utilizations.len(), 2);
+        assert!(utilizations.iter().all(|r| {
+            r.location.pod_name.as_deref() == Some("pod2")
+                && r.location.node_name.as_deref() == Some("node1")
+        }));
+    }
+
+    #[test]
+    fn test_is_job() {
+        let pod: Pod = serde_json::from_value(serde_json::json!({
+            "metadata": {
+                "name": "job1-x7zfb",
+                "ownerReferences": [{
+                    "apiVersion": "batch/v1",
+                    "kind": "Job",
+                    "name": "job1",
+                    "uid": "d1f3a9c2",
+                    "controller": true
+                }]
+            }
+        }))
+        .unwrap();
+        assert!(is_job(&pod));
+        assert!(!is_daemonset(&pod));
+    }
+
+    #[test]
+    fn test_token_opts() {
+        use clap::Parser;
+        let cli_opts = CliOpts::parse_from([
+            "view-allocations",
+            "--server",
+            "https://10.0.0.1:6443",
+            "--token",
+            "s3cr3t",
+        ]);
+        assert!(!format!("{:?}", cli_opts).contains("s3cr3t"));
+        assert!(make_auth_info(&cli_opts).unwrap().token.is_some());
+
+        let cli_opts = CliOpts::parse_from([
+            "view-allocations",
+            "--server",
+            "https://10.0.0.1:6443",
+            "--token-file",
+            "/var/run/secrets/kubernetes.io/serviceaccount/token",
+        ]);
+        let auth_info = make_auth_info(&cli_opts).unwrap();
+        assert!(auth_info.token.is_none());
+        assert_eq!(
+            auth_info.token_file.as_deref(),
+            Some("/var/run/secrets/kubernetes.io/serviceaccount/token")
+        );
+        assert!(CliOpts::try_parse_from([
+            "view-allocations",
+            "--server",
+            "https://10.0.0.1:6443",
+            "--token",
+            "s3cr3t",
+            "--token-file",
+            "token",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_exclude_namespace_opts() {
+        use clap::Parser;
+        let cli_opts = CliOpts::parse_from([
+            "view-allocations",
+            "--ignore-namespace",
+            "kube-system,kube-public",
+            "--exclude-namespace",
+            "monitoring",
+            "--exclude-namespace",
+            "kube-system",
+        ]);
+        assert_eq!(
+            ResourceFilter::from(&cli_opts).exclude_namespaces,
+            vec!["kube-system", "kube-public", "monitoring"]
+        );
+    }
+
+    #[test]
+    fn test_missing_labels() {
+        let pod = |name: &str, labels: &[&str]| Location {
+            node_name: Some("node1".to_string()),
+            labels: labels
+                .iter()
+                .map(|k| (k.to_string(), "x".to_string()))
+                .collect(),
+            ..pod_location("ns1", name)
+        };
+        let resources = vec![
+            new_resource(
+                "cpu",
+                ResourceQualifier::Requested,
+                "1",
+                pod("a", &["team", "app"]),
+            ),
+            new_resource("cpu", ResourceQualifier::Requested, "1", pod("b", &["app"])),
+            new_resource(
+                "memory",
+                ResourceQualifier::Requested,
+                "1Gi",
+                pod("b", &["app"]),
+            ),
+            new_resource("cpu", ResourceQualifier::Requested, "1", pod("c", &[])),
+        ];
+        let group_by = vec![GroupBy::node, GroupBy::pod];
+        let values = make_extra_columns(
+            &resources,
+            &group_by,
+            &ResourceFilter::default(),
+            &[ExtraColumn::MissingLabels(vec![
+                "team".to_string(),
+                "app".to_string(),
+            ])],
+        );
+        assert_eq!(values[&key(&["node1"])], vec![Some("2 pods".to_string())]);
+        assert_eq!(values[&key(&["node1", "a"])], vec![None]);
+        assert_eq!(
+            values[&key(&["node1", "b"])],
+            vec![Some("team".to_string())]
+        );
+        assert_eq!(
+            values[&key(&["node1", "c"])],
+            vec![Some("team,app".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_process_resources_max() {
+        use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+        let requests = |cpu: &str| BTreeMap::from([("cpu".to_string(), Quantity(cpu.to_string()))]);
+        let mut effective = BTreeMap::new();
+        for container in [requests("500m"), requests("2"), requests("1")] {
+            process_resources_max(&mut effective, &container).unwrap();
+        }
+        assert_eq!(f64::from(&effective["cpu"]), 2.0);
+
+        let mut effective = BTreeMap::new();
+        for container in [requests("500m"), requests("2"), requests("1")] {
+            process_resources_additive(&mut effective, &container).unwrap();
+        }
+        assert_eq!(f64::from(&effective["cpu"]), 3.5);
+    }
+
+    #[test]
+    fn test_namespace_pattern() {
+        let name = NamespacePattern::from_str("kube-system").unwrap();
+        assert_eq!(name.name(), Some("kube-system"));
+        assert!(name.is_match("kube-system") && !name.is_match("kube-system2"));
+
+        let glob = NamespacePattern::from_str("kube-*").unwrap();
+        assert_eq!(glob.name(), None);
+        assert!(glob.is_match("kube-system") && !glob.is_match("default"));
+
+        let regex = NamespacePattern::from_str("^prod-").unwrap();
+        assert!(regex.is_match("prod-eu") && !regex.is_match("preprod-eu"));
+
+        assert_eq!(
+            NamespacePattern::single_name(std::slice::from_ref(&name)),
+            Some("kube-system")
+        );
+        assert_eq!(NamespacePattern::single_name(&[name, glob]), None);
+        assert!(NamespacePattern::any_match(&[], "default"));
+        assert!(NamespacePattern::from_str("^prod-(").is_err());
+    }
+
+    #[test]
+    fn test_field_selector() {
+        let selector =
+            FieldSelector::from_str("status.phase=Running, spec.nodeName!=node-1").unwrap();
+        assert_eq!(
+            selector.for_pods(),
+            "status.phase=Running,spec.nodeName!=node-1"
+        );
+        assert_eq!(
+            selector.for_nodes(),
+            Some("metadata.name!=node-1".to_string())
+        );
+        let selector = FieldSelector::from_str("status.phase==Running").unwrap();
+        assert_eq!(selector.for_nodes(), None);
+        assert_eq!(
+            FieldSelector::from_str("status.phase=Running,spec.nodeName").unwrap_err(),
+            "invalid requirement 'spec.nodeName', expected `field=value`, `field==value` or `field!=value`"
+        );
+        assert!(FieldSelector::from_str("").is_err());
+    }
+
+    #[test]
+    fn test_label_selector() {
+        for valid in [
+            "env=production",
+            "env==production,tier!=frontend",
+            "app.kubernetes.io/name=nginx",
+            "tier in (frontend, backend),!canary",
+            "env notin (dev),release",
+            "env=",
+        ] {
+            assert_eq!(
+                LabelSelector::from_str(valid).map(|s| s.as_str().to_string()),
+                Ok(valid.to_string()),
+                "{}",
+                valid
+            );
+        }
+        for invalid in [
+            "",
+            "env=prod=eu",
+            "env=-prod",
+            "tier in (frontend",
+            "tier in ()",
+            "Example.com/app=nginx",
+            "env=production,,tier=frontend",
+        ] {
+            assert!(LabelSelector::from_str(invalid).is_err(), "{}", invalid);
+        }
+        assert_eq!(
+            LabelSelector::from_str("env=production,tier=front end").unwrap_err(),
+            "invalid requirement 'tier=front end', expected `key`, `!key`, `key=value`, `key!=value`, `key in (v1,v2)` or `key notin (v1,v2)`"
         );
     }
 }
@@ -0,0 +1,158 @@
+//! Markdown output
+use crate::{
+    format_cell_text, format_overcommit_ratio, format_utilization_ratio, is_full_zero, tree,
+    write_output, Column, DisplayOptions, Error, ExtraColumn, QtyByQualifier,
+};
+use std::collections::HashMap;
+
+/// Display as a GitHub Flavored Markdown table, with the same columns as the table output
+/// (without colors), eg to paste into runbooks, issues or PR comments
+pub fn display_as_markdown(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    extra_columns: &[ExtraColumn],
+    extra_values: &HashMap<Vec<String>, Vec<Option<String>>>,
+    options: &DisplayOptions,
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    write_output(
+        out,
+        &format_as_markdown(data, extra_columns, extra_values, options),
+    )
+}
+
+pub fn format_as_markdown(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    extra_columns: &[ExtraColumn],
+    extra_values: &HashMap<Vec<String>, Vec<Option<String>>>,
+    options: &DisplayOptions,
+) -> String {
+    let filter_full_zero = options.filter_full_zero;
+    let show_utilization = options.show_utilization;
+    let hide_allocatable = options.hide_allocatable;
+    let precision = options.precision.unwrap_or(1);
+    let columns = Column::visible(show_utilization, hide_allocatable, &options.hidden_columns);
+    let with_ratio = columns.contains(&Column::utilization);
+    let mut titles = vec![("Resource", "l")];
+    for column in columns.iter() {
+        titles.push((column.title(), "r"));
+    }
+    if with_ratio {
+        titles.push(("%Util/Req", "r"));
+    }
+    if options.show_overcommit {
+        titles.push(("Overcommit", "r"));
+    }
+    for column in extra_columns.iter().filter(|c| !c.is_marker()) {
+        titles.push((column.title(), column.align()));
+    }
+    let to_line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+    let mut out = to_line(titles.iter().map(|(t, _)| t.to_string()).collect());
+    out.push_str(&to_line(
+        titles
+            .iter()
+            .map(|(_, align)| if *align == "r" { "---:" } else { "---" }.to_string())
+            .collect(),
+    ));
+
+    let data2 = data
+        .iter()
+        .filter(|d| !filter_full_zero || !is_full_zero(&d.1, hide_allocatable))
+        .collect::<Vec<_>>();
+    let prefixes = tree::provide_prefix(&data2, |parent, item| parent.0.len() + 1 == item.0.len());
+    for ((k, oqtys), prefix) in data2.iter().zip(prefixes.iter()) {
+        let qtys = match oqtys {
+            Some(qtys) => qtys,
+            None => continue,
+        };
+        let values = extra_values.get(k);
+        let value_of = |i: usize| {
+            values
+                .and_then(|v| v.get(i).cloned().flatten())
+                .unwrap_or_default()
+        };
+        let mut column0 = format!(
+            "{} {}",
+            prefix,
+            k.last().map(|x| x.as_str()).unwrap_or("???")
+        );
+        for (i, column) in extra_columns.iter().enumerate() {
+            let txt = value_of(i);
+            if column.is_marker() && !txt.is_empty() {
+                column0.push(' ');
+                column0.push_str(&txt);
+            }
+        }
+        let base100 = if hide_allocatable {
+            &None
+        } else {
+            &qtys.allocatable
+        };
+        let mut cells = vec![column0];
+        for column in columns.iter() {
+            let o100 = if column.with_percentage() {
+                base100
+            } else {
+                &None
+            };
+            cells.push(format_cell_text(
+                &column.value_of(qtys),
+                o100,
+                precision,
+                options.percent_only,
+            ));
+        }
+        if with_ratio {
+            cells.push(format_utilization_ratio(qtys.calc_utilization_ratio()));
+        }
+        if options.show_overcommit {
+            cells.push(format_overcommit_ratio(qtys.calc_overcommit_ratio()));
+        }
+        for (i, column) in extra_columns.iter().enumerate() {
+            if !column.is_marker() {
+                cells.push(value_of(i));
+            }
+        }
+        out.push_str(&to_line(
+            cells.iter().map(|c| c.replace('|', "\\|")).collect(),
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn test_format_as_markdown() {
+        let mut rows = vec![
+            row(&["cpu"], "1", Some("4")),
+            row(&["cpu", "node1"], "1", Some("4")),
+            row(&["memory"], "0", Some("0")),
+        ];
+        for (_, qtys) in rows.iter_mut() {
+            let qtys = qtys.as_mut().unwrap();
+            qtys.limit = qtys.requested.clone();
+        }
+        let extra_values =
+            HashMap::from([(key(&["cpu", "node1"]), vec![Some("10.0.0.1".to_string())])]);
+        let md = format_as_markdown(
+            &rows,
+            &[ExtraColumn::NodeIp],
+            &extra_values,
+            &DisplayOptions::default(),
+        );
+        let lines = md.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "| Resource | Requested | Limit | Allocatable | Free | IP |"
+        );
+        assert_eq!(lines[1], "| --- | ---: | ---: | ---: | ---: | --- |");
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[3],
+            "|  └─ node1 | (25%) 1.0 | (25%) 1.0 | 4.0 | 3.0 | 10.0.0.1 |"
+        );
+    }
+}
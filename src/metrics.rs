@@ -43,6 +43,36 @@ impl k8s_openapi::Metadata for PodMetrics {
     }
 }
 
+// kubectl get --raw /apis/metrics.k8s.io/v1beta1/nodes | jq .
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeMetrics {
+    pub metadata: kube::api::ObjectMeta,
+    pub usage: Usage,
+    pub timestamp: String,
+    pub window: String,
+}
+
+impl k8s_openapi::Resource for NodeMetrics {
+    const GROUP: &'static str = "metrics.k8s.io";
+    const KIND: &'static str = "node";
+    const VERSION: &'static str = "v1beta1";
+    const API_VERSION: &'static str = "metrics.k8s.io/v1beta1";
+    const URL_PATH_SEGMENT: &'static str = "nodes";
+    type Scope = k8s_openapi::ClusterResourceScope;
+}
+
+impl k8s_openapi::Metadata for NodeMetrics {
+    type Ty = kube::api::ObjectMeta;
+
+    fn metadata(&self) -> &Self::Ty {
+        &self.metadata
+    }
+
+    fn metadata_mut(&mut self) -> &mut Self::Ty {
+        &mut self.metadata
+    }
+}
+
 // #[derive(Debug, Clone, Serialize, Deserialize)]
 // struct PodMetricsList {
 //     metadata: kube::api::ObjectMeta,
@@ -0,0 +1,153 @@
+//! Prometheus output (text exposition format)
+use crate::qty::Qty;
+use crate::{write_output, Error, GroupBy, QtyByQualifier};
+use itertools::Itertools;
+use std::collections::{BTreeMap, HashMap};
+
+/// The unit of the metrics of a resource (in the Prometheus naming convention)
+pub(crate) fn prometheus_unit(kind: &str) -> &'static str {
+    match kind {
+        "cpu" => "cores",
+        "memory" => "bytes",
+        k if k.ends_with("storage") => "bytes",
+        _ => "units",
+    }
+}
+
+/// Display as Prometheus metrics (text exposition format), see `format_as_prometheus_metrics`
+pub fn display_as_prometheus_metrics(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    write_output(out, &format_as_prometheus_metrics(data, group_by))
+}
+
+/// A quantity of a row to export as a metric (gauge)
+pub(crate) struct Gauge<'a> {
+    pub key: &'a [String],
+    pub kind: &'a str,
+    pub qualifier: &'static str,
+    pub value: f64,
+}
+
+/// The quantities of the rows to export as gauges, with the labels of the path of the row.
+/// A quantity is only exported by the deepest rows defining it (eg: requested by pods,
+/// allocatable by nodes), so sums don't count it twice.
+pub(crate) fn exported_gauges<'a>(
+    data: &'a [(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+) -> Vec<Gauge<'a>> {
+    type Getter = fn(&QtyByQualifier) -> Option<Qty>;
+    let qualifiers: [(&str, Getter); 4] = [
+        ("utilization", |q| q.utilization.clone()),
+        ("requested", |q| q.requested.clone()),
+        ("limit", |q| q.limit.clone()),
+        ("allocatable", |q| q.allocatable.clone()),
+    ];
+    // the qualifiers defined by (at least) a child of the row
+    let mut defined_by_children: HashMap<&[String], [bool; 4]> = HashMap::new();
+    for (k, oqtys) in data {
+        if let (Some(qtys), Some((_, parent))) = (oqtys, k.split_last()) {
+            let flags = defined_by_children.entry(parent).or_default();
+            for (i, (_, get)) in qualifiers.iter().enumerate() {
+                flags[i] |= get(qtys).is_some();
+            }
+        }
+    }
+    let resource_idx = group_by.iter().position(|x| x == &GroupBy::resource);
+    let mut gauges = vec![];
+    for (k, oqtys) in data {
+        let (kind, qtys) = match (resource_idx.and_then(|i| k.get(i)), oqtys) {
+            (Some(kind), Some(qtys)) => (kind, qtys),
+            _ => continue,
+        };
+        let shadowed = defined_by_children.get(k.as_slice());
+        for (i, (qualifier, get)) in qualifiers.iter().enumerate() {
+            if shadowed.is_some_and(|flags| flags[i]) {
+                continue;
+            }
+            if let Some(qty) = get(qtys) {
+                gauges.push(Gauge {
+                    key: k,
+                    kind,
+                    qualifier,
+                    value: f64::from(&qty),
+                });
+            }
+        }
+    }
+    gauges
+}
+
+/// The name of a label for a `GroupBy` (eg: `label_app` for `label:app`)
+pub(crate) fn metric_label_name(g: &GroupBy) -> String {
+    g.to_string()
+        .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+}
+
+/// Format as Prometheus gauges (see `exported_gauges`), eg:
+/// `kubectl_view_allocations_requested_cores{resource="cpu",node="worker-1",pod="nginx"} 0.5`
+pub fn format_as_prometheus_metrics(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+) -> String {
+    let mut metrics: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for gauge in exported_gauges(data, group_by) {
+        let labels = gauge
+            .key
+            .iter()
+            .zip(group_by.iter())
+            .map(|(v, g)| {
+                let value = v
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n");
+                format!("{}=\"{}\"", metric_label_name(g), value)
+            })
+            .join(",");
+        metrics
+            .entry(format!(
+                "kubectl_view_allocations_{}_{}",
+                gauge.qualifier,
+                prometheus_unit(gauge.kind)
+            ))
+            .or_default()
+            .push(format!("{{{}}} {}", labels, gauge.value));
+    }
+    let mut out = String::new();
+    for (metric, samples) in metrics {
+        out.push_str(&format!("# TYPE {} gauge\n", metric));
+        for sample in samples {
+            out.push_str(&format!("{}{}\n", metric, sample));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn test_format_as_prometheus_metrics() {
+        let rows = vec![
+            row(&["cpu"], "1500m", Some("4")),
+            row(&["cpu", "node1"], "1500m", Some("4")),
+            row(&["cpu", "node1", "pod\"1"], "1500m", None),
+            row(&["memory"], "1Gi", None),
+        ];
+        let group_by = vec![GroupBy::resource, GroupBy::node, GroupBy::pod];
+        assert_eq!(
+            format_as_prometheus_metrics(&rows, &group_by),
+            r#"# TYPE kubectl_view_allocations_allocatable_cores gauge
+kubectl_view_allocations_allocatable_cores{resource="cpu",node="node1"} 4
+# TYPE kubectl_view_allocations_requested_bytes gauge
+kubectl_view_allocations_requested_bytes{resource="memory"} 1073741824
+# TYPE kubectl_view_allocations_requested_cores gauge
+kubectl_view_allocations_requested_cores{resource="cpu",node="node1",pod="pod\"1"} 1.5
+"#
+        );
+    }
+}
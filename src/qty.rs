@@ -97,6 +97,38 @@ pub struct Qty {
     pub scale: Scale,
 }
 
+/// Serialized as its value (as `f64`) and its human readable representation (adjusted scale),
+/// eg: `{"value": 3435973836.8, "human": "3.2Gi"}`
+impl serde::Serialize for Qty {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Qty", 2)?;
+        state.serialize_field("value", &f64::from(self))?;
+        state.serialize_field("human", &format!("{}", self.adjust_scale()))?;
+        state.end()
+    }
+}
+
+/// Deserialized from its serialized form, the value and the scale of the human readable representation
+impl<'de> serde::Deserialize<'de> for Qty {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        struct Repr {
+            value: f64,
+            human: String,
+        }
+        let repr = Repr::deserialize(deserializer)?;
+        let label = repr
+            .human
+            .trim_start_matches(|c: char| c.is_ascii_digit() || c == '.' || c == '-');
+        let scale = Scale::from_str(label).map_err(serde::de::Error::custom)?;
+        Ok(Qty {
+            value: (repr.value * 1000f64).round() as i64,
+            scale,
+        })
+    }
+}
+
 impl From<&Qty> for f64 {
     fn from(v: &Qty) -> f64 {
         (v.value as f64) * 0.001
@@ -158,11 +190,13 @@ impl FromStr for Qty {
     }
 }
 
+/// Display the value in its scale, with 1 decimal by default (or the precision of the format, eg: `{:.3}`)
 impl std::fmt::Display for Qty {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{:.1}{}",
+            "{:.*}{}",
+            f.precision().unwrap_or(1),
             (self.value as f64 / (f64::from(&self.scale) * 1000f64)),
             self.scale.label
         )
@@ -249,6 +283,16 @@ impl<'b> std::ops::SubAssign<&'b Qty> for Qty {
     }
 }
 
+impl std::ops::Mul<f64> for &Qty {
+    type Output = Qty;
+    fn mul(self, factor: f64) -> Qty {
+        Qty {
+            value: (self.value as f64 * factor) as i64,
+            scale: self.scale.clone(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -326,6 +370,16 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_display_with_precision() -> Result<(), Box<dyn std::error::Error>> {
+        let qty = Qty::from_str("1234m")?;
+        assert_that!(format!("{:.0}", &qty)).is_equal_to("1234m".to_string());
+        assert_that!(format!("{:.3}", &qty.adjust_scale())).is_equal_to("1.234".to_string());
+        assert_that!(format!("{:.*}", 2, &Qty::from_str("1.5Gi")?.adjust_scale()))
+            .is_equal_to("1.50Gi".to_string());
+        Ok(())
+    }
+
     #[test]
     fn test_f64_from_scale() -> Result<(), Box<dyn std::error::Error>> {
         assert_that!(f64::from(&Scale::from_str("m")?)).is_close_to(0.001, 0.00001);
@@ -373,4 +427,39 @@ mod tests {
         });
         Ok(())
     }
+
+    #[test]
+    fn test_mul() -> Result<(), Box<dyn std::error::Error>> {
+        assert_that!(f64::from(&(&Qty::from_str("1")? * 1.2))).is_close_to(1.2, 0.00001);
+        assert_that!(&(&Qty::from_str("10Gi")? * 0.5)).is_equal_to(&Qty::from_str("5Gi")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize() -> Result<(), Box<dyn std::error::Error>> {
+        assert_that!(serde_json::to_value(Qty::from_str("1536Mi")?)?).is_equal_to(
+            serde_json::json!({
+                "value": 1610612736.0,
+                "human": "1.5Gi",
+            }),
+        );
+        assert_that!(serde_json::to_value(Qty::from_str("250m")?)?).is_equal_to(
+            serde_json::json!({
+                "value": 0.25,
+                "human": "250.0m",
+            }),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize() -> Result<(), Box<dyn std::error::Error>> {
+        for input in ["1536Mi", "250m", "110", "0"] {
+            let qty = Qty::from_str(input)?;
+            let back: Qty = serde_json::from_value(serde_json::to_value(&qty)?)?;
+            assert_that!(back.value).is_equal_to(qty.value);
+            assert_that!(back.adjust_scale()).is_equal_to(qty.adjust_scale());
+        }
+        Ok(())
+    }
 }
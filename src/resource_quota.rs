@@ -0,0 +1,189 @@
+//! ResourceQuota output (terraform resources or kubernetes manifests)
+use crate::qty::Qty;
+use crate::{write_output, Error, QtyByQualifier};
+use itertools::Itertools;
+
+/// The key of a resource in the `hard` section of a ResourceQuota
+fn quota_hard_key(kind: &str) -> String {
+    match kind {
+        "pods" => kind.to_string(),
+        // see `display_kind`
+        "storage" => "requests.ephemeral-storage".to_string(),
+        _ => format!("requests.{}", kind),
+    }
+}
+
+/// The hard limit of a ResourceQuota: `requested * buffer` rounded up (never below the requested),
+/// in the human readable scale when exact (eg: `1.5Gi`), else in millicores for cpu, in units otherwise
+fn quota_hard_value(kind: &str, requested: &Qty, buffer: f64) -> String {
+    let mut millis = (requested.value as f64 * buffer).ceil() as i64;
+    if kind != "cpu" {
+        // only the cpu is divisible below the unit
+        millis = (millis + 999) / 1000 * 1000;
+    }
+    let hard = Qty {
+        value: millis,
+        scale: requested.scale.clone(),
+    }
+    .adjust_scale();
+    // the value of the first decimal of the scale, in millis
+    let step = f64::from(&hard.scale) * 100.0;
+    if kind != "pods" && step >= 1.0 && millis as f64 % step == 0.0 {
+        format!("{}", hard)
+    } else if millis % 1000 == 0 {
+        format!("{}", millis / 1000)
+    } else {
+        format!("{}m", millis)
+    }
+}
+
+/// The name of the terraform resource of a namespace: the namespace with `_` instead of `-`,
+/// prefixed by `_` when it starts with a digit (not allowed as first character of an identifier)
+fn terraform_resource_name(namespace: &str) -> String {
+    let name = namespace.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+/// The hard limits (key, value) of a ResourceQuota per namespace, set to `requested * buffer`
+/// - `data` should be grouped by namespace, resource
+fn quota_hards_by_namespace(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    buffer: f64,
+) -> Vec<(String, Vec<(String, String)>)> {
+    data.iter()
+        .filter(|(k, _)| k.len() == 2)
+        .group_by(|(k, _)| k[0].clone())
+        .into_iter()
+        .map(|(namespace, rows)| {
+            let hard = rows
+                .filter_map(|(k, oqtys)| {
+                    oqtys
+                        .as_ref()
+                        .and_then(|qtys| qtys.requested.as_ref())
+                        .filter(|qty| !qty.is_zero())
+                        .map(|qty| (quota_hard_key(&k[1]), quota_hard_value(&k[1], qty, buffer)))
+                })
+                .collect::<Vec<_>>();
+            (namespace, hard)
+        })
+        .filter(|(_, hard)| !hard.is_empty())
+        .collect()
+}
+
+/// Display a terraform `kubernetes_resource_quota` per namespace, with the hard limits
+/// set to `requested * buffer`
+/// - `data` should be grouped by namespace, resource
+pub fn display_as_terraform(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    buffer: f64,
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    for (namespace, hard) in quota_hards_by_namespace(data, buffer) {
+        let resource = format!(
+            r#"resource "kubernetes_resource_quota" "{}" {{
+  metadata {{
+    name      = "view-allocations"
+    namespace = "{}"
+  }}
+  spec {{
+    hard = {{
+{}
+    }}
+  }}
+}}
+"#,
+            terraform_resource_name(&namespace),
+            namespace,
+            hard.iter()
+                .map(|(k, v)| format!("      \"{}\" = \"{}\"", k, v))
+                .join("\n")
+        );
+        write_output(out, &format!("{}\n", resource))?;
+    }
+    Ok(())
+}
+
+/// Format the manifests (yaml, multi-documents) of a `ResourceQuota` per namespace, with the
+/// hard limits set to `requested * buffer`
+/// - `data` should be grouped by namespace, resource
+pub fn format_as_resource_quotas(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    buffer: f64,
+) -> String {
+    quota_hards_by_namespace(data, buffer)
+        .into_iter()
+        .map(|(namespace, hard)| {
+            format!(
+                r#"---
+apiVersion: v1
+kind: ResourceQuota
+metadata:
+  name: view-allocations
+  namespace: {}
+spec:
+  hard:
+{}
+"#,
+                namespace,
+                hard.iter()
+                    .map(|(k, v)| format!("    {}: \"{}\"", k, v))
+                    .join("\n")
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn test_quota_hard() {
+        assert_eq!(quota_hard_key("cpu"), "requests.cpu");
+        assert_eq!(quota_hard_key("pods"), "pods");
+        assert_eq!(quota_hard_key("storage"), "requests.ephemeral-storage");
+        assert_eq!(quota_hard_value("cpu", &qty("1").unwrap(), 1.2), "1.2");
+        assert_eq!(
+            quota_hard_value("memory", &qty("1Gi").unwrap(), 1.5),
+            "1.5Gi"
+        );
+        assert_eq!(quota_hard_value("pods", &qty("11").unwrap(), 1.2), "14");
+        // not rounded down to the first decimal (1.1Gi), nor to the millicore
+        assert_eq!(
+            quota_hard_value("memory", &qty("950Mi").unwrap(), 1.2),
+            "1195376640"
+        );
+        assert_eq!(quota_hard_value("cpu", &qty("333m").unwrap(), 1.2), "400m");
+        assert_eq!(terraform_resource_name("kube-system"), "kube_system");
+        assert_eq!(terraform_resource_name("1-team"), "_1_team");
+    }
+
+    #[test]
+    fn test_format_as_resource_quotas() {
+        let rows = vec![
+            row(&["ns1"], "1", None),
+            row(&["ns1", "cpu"], "1", None),
+            row(&["ns1", "pods"], "4", None),
+            row(&["ns2", "cpu"], "0", None),
+        ];
+        assert_eq!(
+            format_as_resource_quotas(&rows, 1.2),
+            r#"---
+apiVersion: v1
+kind: ResourceQuota
+metadata:
+  name: view-allocations
+  namespace: ns1
+spec:
+  hard:
+    requests.cpu: "1.2"
+    pods: "5"
+"#
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! upload of the output with sftp
+#[cfg(feature = "sftp")]
+use crate::{dsv, json::format_as_json};
+use crate::{CliOpts, DisplayOptions, Error, GroupBy, QtyByQualifier};
+#[cfg(not(feature = "sftp"))]
+use tracing::warn;
+
+/// Split `--sftp-host` into the host and the port (22 by default): `host`, `host:port`,
+/// `[ipv6]` or `[ipv6]:port` (an ipv6 without brackets is taken as the whole host).
+pub fn parse_sftp_host(input: &str) -> Result<(String, u16), Error> {
+    let invalid = || Error::IncompatibleOptions {
+        reason: format!("invalid --sftp-host '{}'", input),
+    };
+    let parse_port = |port: &str| port.parse::<u16>().map_err(|_| invalid());
+    if let Some(rest) = input.strip_prefix('[') {
+        let (host, port) = rest.split_once(']').ok_or_else(invalid)?;
+        let port = match port {
+            "" => 22,
+            _ => parse_port(port.strip_prefix(':').ok_or_else(invalid)?)?,
+        };
+        return Ok((host.to_string(), port));
+    }
+    match input.split_once(':') {
+        Some((host, port)) if !port.contains(':') => Ok((host.to_string(), parse_port(port)?)),
+        _ => Ok((input.to_string(), 22)),
+    }
+}
+
+#[cfg(not(feature = "sftp"))]
+pub fn upload_with_sftp(
+    _data: &[(Vec<String>, Option<QtyByQualifier>)],
+    _group_by: &[GroupBy],
+    _cli_opts: &CliOpts,
+    _options: &DisplayOptions,
+) -> Result<(), Error> {
+    warn!("feature 'sftp' not enabled");
+    Ok(())
+}
+
+/// Upload the rows to `--sftp-path` on `--sftp-host`, as json when the path ends with `.json`,
+/// as csv otherwise. The host must be known (`~/.ssh/known_hosts`), the user authenticates
+/// with `--sftp-key` (without passphrase) or else with the ssh agent.
+#[cfg(feature = "sftp")]
+pub fn upload_with_sftp(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+    cli_opts: &CliOpts,
+    options: &DisplayOptions,
+) -> Result<(), Error> {
+    use ssh2::{CheckResult, KnownHostFileKind, Session};
+    use std::io::Write;
+    let (host, remote_path) = match (&cli_opts.sftp_host, &cli_opts.sftp_path) {
+        (Some(host), Some(path)) => (host, path),
+        _ => {
+            return Err(Error::MissingOption {
+                option: "--sftp-host or --sftp-path".to_string(),
+                by: "--output sftp".to_string(),
+            })
+        }
+    };
+    if remote_path.contains(['\n', '\r', '\0']) {
+        return Err(Error::IncompatibleOptions {
+            reason: format!("invalid --sftp-path {:?}", remote_path),
+        });
+    }
+    let (host, port) = parse_sftp_host(host)?;
+    let content = if remote_path.ends_with(".json") {
+        format_as_json(data)?
+    } else {
+        dsv::format_as_csv(data, group_by, false, options)
+    };
+    let user = match cli_opts
+        .sftp_user
+        .clone()
+        .or_else(|| std::env::var("USER").ok())
+    {
+        Some(user) => user,
+        None => {
+            return Err(Error::MissingOption {
+                option: "--sftp-user".to_string(),
+                by: "--output sftp (without $USER)".to_string(),
+            })
+        }
+    };
+    let ssh_error = |context: &str| {
+        let context = format!("{} ({}:{})", context, host, port);
+        move |source| Error::SftpError { context, source }
+    };
+    let io_error = |context: &str| {
+        let context = format!("{} ({}:{})", context, host, port);
+        move |source| Error::SftpIoError { context, source }
+    };
+
+    let tcp = std::net::TcpStream::connect((host.as_str(), port))
+        .map_err(io_error("connect to the sftp server"))?;
+    let mut session = Session::new().map_err(ssh_error("create the ssh session"))?;
+    session.set_tcp_stream(tcp);
+    session
+        .handshake()
+        .map_err(ssh_error("handshake with the sftp server"))?;
+
+    // like `StrictHostKeyChecking yes`: only the hosts of known_hosts with the same key
+    let (key, _) = session.host_key().ok_or_else(|| Error::SftpError {
+        context: format!("read the host key of {}:{}", host, port),
+        source: ssh2::Error::unknown(),
+    })?;
+    let mut known_hosts = session
+        .known_hosts()
+        .map_err(ssh_error("init the known hosts"))?;
+    let known_hosts_path = match std::env::var_os("HOME") {
+        Some(home) => std::path::Path::new(&home).join(".ssh").join("known_hosts"),
+        None => {
+            return Err(Error::MissingOption {
+                option: "$HOME (for ~/.ssh/known_hosts)".to_string(),
+                by: "--output sftp".to_string(),
+            })
+        }
+    };
+    known_hosts
+        .read_file(&known_hosts_path, KnownHostFileKind::OpenSSH)
+        .map_err(ssh_error("read ~/.ssh/known_hosts"))?;
+    match known_hosts.check_port(&host, port, key) {
+        CheckResult::Match => {}
+        result => {
+            return Err(Error::IncompatibleOptions {
+                reason: format!(
+                    "the host key of {}:{} is not trusted ({:?} in {})",
+                    host,
+                    port,
+                    result,
+                    known_hosts_path.display()
+                ),
+            })
+        }
+    }
+
+    match &cli_opts.sftp_key {
+        Some(key) => session.userauth_pubkey_file(&user, None, key, None),
+        None => session.userauth_agent(&user),
+    }
+    .map_err(ssh_error(&format!("authenticate as '{}'", user)))?;
+
+    let sftp = session.sftp().map_err(ssh_error("open the sftp channel"))?;
+    let mut file = sftp
+        .create(std::path::Path::new(remote_path))
+        .map_err(ssh_error(&format!("create '{}'", remote_path)))?;
+    file.write_all(content.as_bytes())
+        .map_err(io_error(&format!("write '{}'", remote_path)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "sftp")]
+    fn test_upload_with_sftp_requires_host_and_path() {
+        use clap::Parser;
+        let cli_opts = CliOpts::parse_from(["view-allocations", "--sftp-host", "files:2222"]);
+        assert!(matches!(
+            upload_with_sftp(&[], &[], &cli_opts, &DisplayOptions::default()),
+            Err(Error::MissingOption { .. })
+        ));
+        let cli_opts = CliOpts::parse_from([
+            "view-allocations",
+            "--sftp-host",
+            "files",
+            "--sftp-path",
+            "/data/\"x\"\nrm -rf /",
+        ]);
+        assert!(matches!(
+            upload_with_sftp(&[], &[], &cli_opts, &DisplayOptions::default()),
+            Err(Error::IncompatibleOptions { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_sftp_host() {
+        let parse = |s: &str| parse_sftp_host(s).ok();
+        assert_eq!(parse("files"), Some(("files".to_string(), 22)));
+        assert_eq!(parse("files:2222"), Some(("files".to_string(), 2222)));
+        assert_eq!(parse("10.0.0.1:2222"), Some(("10.0.0.1".to_string(), 2222)));
+        assert_eq!(parse("fd00::1"), Some(("fd00::1".to_string(), 22)));
+        assert_eq!(parse("[fd00::1]"), Some(("fd00::1".to_string(), 22)));
+        assert_eq!(parse("[fd00::1]:2222"), Some(("fd00::1".to_string(), 2222)));
+        assert_eq!(parse("files:ssh"), None);
+        assert_eq!(parse("[fd00::1]2222"), None);
+    }
+}
@@ -0,0 +1,70 @@
+//! StatsD output
+use crate::prometheus::exported_gauges;
+use crate::{write_output, Error, GroupBy, QtyByQualifier};
+use itertools::Itertools;
+
+/// Display as StatsD gauges, see `format_as_statsd`
+pub fn display_as_statsd(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    write_output(out, &format_as_statsd(data, group_by))
+}
+
+/// Format as StatsD gauges (see `exported_gauges`), with tags in the DogStatsD format, eg:
+/// `kubernetes.resource_allocation.cpu.requested:0.50|g|#node:worker-1,pod:nginx`
+pub fn format_as_statsd(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    group_by: &[GroupBy],
+) -> String {
+    let mut out = String::new();
+    for gauge in exported_gauges(data, group_by) {
+        let tags = gauge
+            .key
+            .iter()
+            .zip(group_by.iter())
+            .filter(|(_, g)| *g != &GroupBy::resource)
+            .map(|(v, g)| format!("{}:{}", g, v))
+            .join(",");
+        out.push_str(&format!(
+            "kubernetes.resource_allocation.{}.{}:{:.2}|g",
+            gauge
+                .kind
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_"),
+            gauge.qualifier,
+            gauge.value
+        ));
+        if !tags.is_empty() {
+            out.push_str("|#");
+            out.push_str(&tags);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn test_format_as_statsd() {
+        let rows = vec![
+            row(&["cpu"], "1500m", Some("4")),
+            row(&["cpu", "node1"], "1500m", Some("4")),
+            row(&["cpu", "node1", "pod1"], "1500m", None),
+            row(&["nvidia.com/gpu"], "1", None),
+        ];
+        let group_by = vec![GroupBy::resource, GroupBy::node, GroupBy::pod];
+        // only the deepest rows, the sums of the parents are not sent again
+        assert_eq!(
+            format_as_statsd(&rows, &group_by),
+            "kubernetes.resource_allocation.cpu.allocatable:4.00|g|#node:node1
+kubernetes.resource_allocation.cpu.requested:1.50|g|#node:node1,pod:pod1
+kubernetes.resource_allocation.nvidia_com_gpu.requested:1.00|g
+"
+        );
+    }
+}
@@ -0,0 +1,293 @@
+//! table output (and table of changes), with prettytable
+#[cfg(feature = "prettytable")]
+use crate::{
+    compute_color, format_cell_text, format_delta, format_overcommit_ratio, format_pct_change,
+    format_utilization_ratio, is_full_zero, qty::Qty, tree, truncate_name, Column, OK_STYLE,
+};
+use crate::{ColorTheme, DisplayOptions, Error, ExtraColumn, GroupBy, QtyByQualifier};
+#[cfg(feature = "prettytable")]
+use prettytable::{cell, format, row, Cell, Row, Table};
+use std::collections::HashMap;
+#[cfg(not(feature = "prettytable"))]
+use tracing::warn;
+
+#[cfg(not(feature = "prettytable"))]
+pub fn display_with_prettytable(
+    _data: &[(Vec<String>, Option<QtyByQualifier>)],
+    _color_theme: &ColorTheme,
+    _extra_columns: &[ExtraColumn],
+    _extra_values: &HashMap<Vec<String>, Vec<Option<String>>>,
+    _group_by: &[GroupBy],
+    _options: &DisplayOptions,
+    _out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    warn!("feature 'prettytable' not enabled");
+    Ok(())
+}
+
+#[cfg(feature = "prettytable")]
+pub fn display_with_prettytable(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    color_theme: &ColorTheme,
+    extra_columns: &[ExtraColumn],
+    extra_values: &HashMap<Vec<String>, Vec<Option<String>>>,
+    group_by: &[GroupBy],
+    options: &DisplayOptions,
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    let filter_full_zero = options.filter_full_zero;
+    let show_utilization = options.show_utilization;
+    let hide_allocatable = options.hide_allocatable;
+    let flatten = options.flatten;
+    let (color_warn, color_crit) = options.color_thresholds;
+    let precision = options.precision.unwrap_or(1);
+    // Create the table
+    let mut table = Table::new();
+    let format = format::FormatBuilder::new()
+        // .column_separator('|')
+        // .borders('|')
+        // .separators(&[format::LinePosition::Top,
+        //               format::LinePosition::Bottom],
+        //             format::LineSeparator::new('-', '+', '+', '+'))
+        .separators(&[], format::LineSeparator::new('-', '+', '+', '+'))
+        .padding(1, 1)
+        .build();
+    table.set_format(format);
+    let columns = Column::visible(show_utilization, hide_allocatable, &options.hidden_columns);
+    let with_ratio = columns.contains(&Column::utilization);
+    let mut row_titles = row![bl->"Resource"];
+    for column in columns.iter() {
+        row_titles.add_cell(Cell::new(column.title()).style_spec("br"));
+    }
+    if with_ratio {
+        row_titles.add_cell(Cell::new("%Util/Req").style_spec("br"));
+    }
+    if options.show_overcommit {
+        row_titles.add_cell(Cell::new("Overcommit").style_spec("br"));
+    }
+    if flatten {
+        // one column per level of the tree instead of the tree in the first column
+        row_titles.remove_cell(0);
+        for (i, g) in group_by.iter().enumerate() {
+            let mut title = g.to_string();
+            if let Some(first) = title.get_mut(0..1) {
+                first.make_ascii_uppercase();
+            }
+            row_titles.insert_cell(i, Cell::new(&title).style_spec("bl"));
+        }
+    }
+    for column in extra_columns.iter().filter(|c| !c.is_marker()) {
+        row_titles.add_cell(Cell::new(column.title()).style_spec(&format!("b{}", column.align())));
+    }
+    table.set_titles(row_titles);
+    let data2 = data
+        .iter()
+        .filter(|d| !filter_full_zero || !is_full_zero(&d.1, hide_allocatable))
+        .collect::<Vec<_>>();
+    let resource_idx = group_by.iter().position(|x| x == &GroupBy::resource);
+    let prefixes = tree::provide_prefix(&data2, |parent, item| parent.0.len() + 1 == item.0.len());
+
+    for ((k, oqtys), prefix) in data2.iter().zip(prefixes.iter()) {
+        let values = extra_values.get(k);
+        let value_of = |i: usize| {
+            values
+                .and_then(|v| v.get(i).cloned().flatten())
+                .unwrap_or_default()
+        };
+        let mut column0 = format!(
+            "{} {}",
+            prefix,
+            truncate_name(
+                k.last().map(|x| x.as_str()).unwrap_or("???"),
+                options.max_name_width
+            )
+        );
+        let mut marker_color = None;
+        for (i, column) in extra_columns.iter().enumerate() {
+            let txt = value_of(i);
+            if column.is_marker() && !txt.is_empty() {
+                column0.push(' ');
+                column0.push_str(&txt);
+                marker_color = Some(column.color(&txt));
+            }
+        }
+        if let Some(qtys) = oqtys {
+            let style = match (
+                marker_color,
+                compute_color(qtys, &options.color_by, color_warn, color_crit),
+            ) {
+                (Some(color), _) => format!("r{}", color),
+                (None, OK_STYLE) => resource_idx
+                    .and_then(|i| k.get(i))
+                    .and_then(|kind| color_theme.style_of(kind))
+                    .unwrap_or(OK_STYLE)
+                    .to_string(),
+                (None, style) => style.to_string(),
+            };
+            let style = style.as_str();
+            // without allocatable, there is no base for the percentages
+            let base100 = if hide_allocatable {
+                &None
+            } else {
+                &qtys.allocatable
+            };
+            let mut row = Row::new(vec![Cell::new(&column0)]);
+            for column in columns.iter() {
+                let o100 = if column.with_percentage() {
+                    base100
+                } else {
+                    &None
+                };
+                row.add_cell(
+                    make_cell_for_prettytable(
+                        &column.value_of(qtys),
+                        o100,
+                        precision,
+                        options.percent_only,
+                    )
+                    .style_spec(style),
+                );
+            }
+            if with_ratio {
+                let ratio = qtys.calc_utilization_ratio();
+                // red when using more than requested
+                let ratio_style = if ratio.is_some_and(|r| r > 1.0) {
+                    "rFr"
+                } else {
+                    style
+                };
+                row.add_cell(Cell::new(&format_utilization_ratio(ratio)).style_spec(ratio_style));
+            }
+            if options.show_overcommit {
+                let ratio = qtys.calc_overcommit_ratio();
+                let ratio_style = if ratio.is_some_and(|r| r > options.overcommit_threshold) {
+                    "rFr"
+                } else {
+                    style
+                };
+                row.add_cell(Cell::new(&format_overcommit_ratio(ratio)).style_spec(ratio_style));
+            }
+            if flatten {
+                row.remove_cell(0);
+                for i in 0..group_by.len() {
+                    row.insert_cell(i, Cell::new(k.get(i).map(|x| x.as_str()).unwrap_or("")));
+                }
+            }
+            for (i, column) in extra_columns.iter().enumerate() {
+                if column.is_marker() {
+                    continue;
+                }
+                let txt = value_of(i);
+                row.add_cell(Cell::new(&txt).style_spec(&format!(
+                    "{}{}",
+                    column.align(),
+                    column.color(&txt)
+                )));
+            }
+            table.add_row(row);
+        }
+    }
+
+    print_table(&table, options.colorize, out)
+}
+
+/// Print the table, with the colors of its styles if `colorize` (eg: on a terminal)
+#[cfg(feature = "prettytable")]
+fn print_table(table: &Table, colorize: bool, out: &mut dyn std::io::Write) -> Result<(), Error> {
+    let terminal = if colorize {
+        term::TerminfoTerminal::new(&mut *out)
+    } else {
+        None
+    };
+    match terminal {
+        Some(mut terminal) => table.print_term(&mut terminal),
+        None => table.print(out),
+    }
+    .map(|_| ())
+    .map_err(|source| Error::WriteOutputError { source })
+}
+
+#[cfg(not(feature = "prettytable"))]
+pub fn display_diff_table(
+    _data: &[(Vec<String>, Option<QtyByQualifier>, Option<QtyByQualifier>)],
+    _options: &DisplayOptions,
+    _out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    warn!("feature 'prettytable' not enabled");
+    Ok(())
+}
+
+/// Display the rows of `diff_qualifiers` with the current quantities and their changes
+/// (also as percentages with `options.show_pct_change`),
+/// increases of free (and allocatable) in red and decreases in green, the inverse for
+/// utilization, requested and limit
+#[cfg(feature = "prettytable")]
+pub fn display_diff_table(
+    data: &[(Vec<String>, Option<QtyByQualifier>, Option<QtyByQualifier>)],
+    options: &DisplayOptions,
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    let precision = options.precision.unwrap_or(1);
+    let mut table = Table::new();
+    let format = format::FormatBuilder::new()
+        .separators(&[], format::LineSeparator::new('-', '+', '+', '+'))
+        .padding(1, 1)
+        .build();
+    table.set_format(format);
+    // (title, quantity of the row, is an increase in green)
+    type Field<'a> = (&'a str, fn(&QtyByQualifier) -> Option<Qty>, bool);
+    let mut fields: Vec<Field> = vec![
+        ("Requested", |q| q.requested.clone(), true),
+        ("Limit", |q| q.limit.clone(), true),
+        ("Allocatable", |q| q.allocatable.clone(), false),
+        ("Free", |q| q.calc_free(), false),
+    ];
+    if options.show_utilization {
+        fields.insert(0, ("Utilization", |q| q.utilization.clone(), true));
+    }
+    let mut row_titles = Row::new(vec![Cell::new("Resource").style_spec("bl")]);
+    for (title, _, _) in fields.iter() {
+        row_titles.add_cell(Cell::new(title).style_spec("br"));
+        row_titles.add_cell(Cell::new("+/-").style_spec("br"));
+    }
+    table.set_titles(row_titles);
+
+    let prefixes = tree::provide_prefix(data, |parent, item| parent.0.len() + 1 == item.0.len());
+    for ((k, before, after), prefix) in data.iter().zip(prefixes.iter()) {
+        let mut row = Row::new(vec![Cell::new(&format!(
+            "{} {}",
+            prefix,
+            k.last().map(|x| x.as_str()).unwrap_or("???")
+        ))]);
+        for (_, get, increase_in_green) in fields.iter() {
+            let before = before.as_ref().and_then(get);
+            let after = after.as_ref().and_then(get);
+            let (mut delta, ordering) = format_delta(&before, &after);
+            let pct = format_pct_change(&before, &after);
+            if options.show_pct_change && !pct.is_empty() {
+                delta = format!("{} ({})", delta, pct);
+            }
+            let color = match (ordering, increase_in_green) {
+                (std::cmp::Ordering::Equal, _) => "",
+                (std::cmp::Ordering::Greater, true) | (std::cmp::Ordering::Less, false) => "Fg",
+                _ => "Fr",
+            };
+            row.add_cell(
+                make_cell_for_prettytable(&after, &None, precision, false).style_spec("r"),
+            );
+            row.add_cell(Cell::new(&delta).style_spec(&format!("r{}", color)));
+        }
+        table.add_row(row);
+    }
+    print_table(&table, options.colorize, out)
+}
+
+#[cfg(feature = "prettytable")]
+fn make_cell_for_prettytable(
+    oqty: &Option<Qty>,
+    o100: &Option<Qty>,
+    precision: usize,
+    percent_only: bool,
+) -> Cell {
+    Cell::new(&format_cell_text(oqty, o100, precision, percent_only))
+}
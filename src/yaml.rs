@@ -0,0 +1,100 @@
+//! yaml output (a list, or a stream of documents)
+use crate::json::json_rows;
+use crate::{write_output, Error, QtyByQualifier};
+
+/// Format the rows as a yaml list, with the same structure as the json output
+pub fn format_as_yaml(data: &[(Vec<String>, Option<QtyByQualifier>)]) -> Result<String, Error> {
+    serde_yaml::to_string(&json_rows(data)).map_err(|source| Error::YamlError {
+        context: "serialize the resources as yaml".to_string(),
+        source,
+    })
+}
+
+/// Display the rows as yaml (same structure as the json output), eg to pipe it through `yq`
+pub fn display_as_yaml(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    write_output(out, &format_as_yaml(data)?)
+}
+
+/// Format the rows as a stream of yaml documents (each starting with `---`), one by row,
+/// with the same structure as the json output (`path`, `requested`, `limit`,...)
+pub fn format_as_ndyaml(data: &[(Vec<String>, Option<QtyByQualifier>)]) -> Result<String, Error> {
+    let mut out = String::new();
+    for row in json_rows(data) {
+        let doc = serde_yaml::to_string(&row).map_err(|source| Error::YamlError {
+            context: "serialize the resources as yaml".to_string(),
+            source,
+        })?;
+        // the separator is not written by every version of serde_yaml
+        out.push_str("---\n");
+        out.push_str(doc.strip_prefix("---\n").unwrap_or(&doc));
+    }
+    Ok(out)
+}
+
+/// Display the rows as a stream of yaml documents, see `format_as_ndyaml`
+pub fn display_as_ndyaml(
+    data: &[(Vec<String>, Option<QtyByQualifier>)],
+    out: &mut dyn std::io::Write,
+) -> Result<(), Error> {
+    write_output(out, &format_as_ndyaml(data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn test_format_as_yaml() {
+        let rows = vec![
+            (
+                key(&["cpu", "node1"]),
+                Some(QtyByQualifier {
+                    limit: qty("2"),
+                    requested: qty("1500m"),
+                    allocatable: qty("4"),
+                    utilization: qty("500m"),
+                    ..QtyByQualifier::default()
+                }),
+            ),
+            (vec!["memory".to_string()], None),
+        ];
+        let yaml = format_as_yaml(&rows).unwrap();
+        let parsed: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let entries = parsed.as_sequence().unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(
+            entry["path"],
+            serde_yaml::from_str::<serde_yaml::Value>("[cpu, node1]").unwrap()
+        );
+        for key in ["limit", "requested", "allocatable", "utilization", "free"] {
+            assert!(entry[key].get("value").is_some(), "missing {}", key);
+        }
+        assert_eq!(entry["requested"]["value"].as_f64(), Some(1.5));
+    }
+
+    #[test]
+    fn test_format_as_ndyaml() {
+        let rows = vec![
+            row(&["cpu", "node1"], "1500m", Some("4")),
+            (key(&["memory"]), None),
+            row(&["memory", "node1"], "1", Some("4")),
+        ];
+        let ndyaml = format_as_ndyaml(&rows).unwrap();
+        let docs = ndyaml.split("---\n").skip(1).collect::<Vec<_>>();
+        assert_eq!(docs.len(), 2);
+        let entry: serde_yaml::Value = serde_yaml::from_str(docs[0]).unwrap();
+        assert_eq!(
+            entry["path"],
+            serde_yaml::from_str::<serde_yaml::Value>("[cpu, node1]").unwrap()
+        );
+        for key in ["requested", "allocatable", "free"] {
+            assert!(entry[key].get("value").is_some(), "missing {}", key);
+        }
+        assert_eq!(entry["requested"]["value"].as_f64(), Some(1.5));
+    }
+}